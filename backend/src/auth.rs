@@ -0,0 +1,164 @@
+//! Access control for the `/admin/*` routes: a static bearer token and/or HTTP
+//! basic credentials from `[admin]` in the config.
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose, Engine};
+use sha2::{Digest, Sha256};
+
+use crate::config::AdminConfig;
+use crate::error::AppError;
+use crate::state::AppState;
+
+/// Middleware that lets a request through only with valid admin credentials
+pub async fn require_admin(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let admin = &state.config.admin;
+
+    match authorize(admin, request.headers()) {
+        Ok(()) => next.run(request).await,
+        Err(e) => {
+            let unauthorized = matches!(e, AppError::Unauthorized(_));
+            let mut response = e.into_response();
+            if unauthorized {
+                for challenge in challenges(admin) {
+                    response.headers_mut().append(
+                        header::WWW_AUTHENTICATE,
+                        HeaderValue::from_static(challenge),
+                    );
+                }
+            }
+            response
+        }
+    }
+}
+
+fn authorize(admin: &AdminConfig, headers: &HeaderMap) -> Result<(), AppError> {
+    if !admin.has_credentials() {
+        return Err(AppError::Forbidden(
+            "Admin access is not configured; set ADMIN_TOKEN or ADMIN_USERNAME and ADMIN_PASSWORD"
+                .into(),
+        ));
+    }
+
+    let authorization = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| AppError::Unauthorized("Admin credentials required".into()))?;
+
+    let valid = match authorization.split_once(' ') {
+        Some((scheme, token)) if scheme.eq_ignore_ascii_case("bearer") => admin
+            .token
+            .as_deref()
+            .is_some_and(|expected| secure_eq(token.trim(), expected)),
+        Some((scheme, encoded)) if scheme.eq_ignore_ascii_case("basic") => {
+            check_basic(admin, encoded.trim())
+        }
+        _ => false,
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::Unauthorized("Invalid admin credentials".into()))
+    }
+}
+
+fn check_basic(admin: &AdminConfig, encoded: &str) -> bool {
+    let (Some(username), Some(password)) = (&admin.username, &admin.password) else {
+        return false;
+    };
+    let Some(decoded) = general_purpose::STANDARD
+        .decode(encoded)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+    else {
+        return false;
+    };
+    let Some((given_user, given_password)) = decoded.split_once(':') else {
+        return false;
+    };
+
+    // Compare both so a wrong user takes as long as a wrong password
+    let user_ok = secure_eq(given_user, username);
+    let password_ok = secure_eq(given_password, password);
+    user_ok & password_ok
+}
+
+/// Compare secrets in time independent of where they differ. Hashing first
+/// also hides the expected length.
+fn secure_eq(given: &str, expected: &str) -> bool {
+    let given = Sha256::digest(given.as_bytes());
+    let expected = Sha256::digest(expected.as_bytes());
+    given
+        .iter()
+        .zip(expected.iter())
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+/// `WWW-Authenticate` values for the schemes that are configured
+fn challenges(admin: &AdminConfig) -> Vec<&'static str> {
+    let mut challenges = Vec::new();
+    if admin.token.is_some() {
+        challenges.push("Bearer realm=\"admin\"");
+    }
+    if admin.username.is_some() {
+        challenges.push("Basic realm=\"admin\"");
+    }
+    challenges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(authorization: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, authorization.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_bearer_and_basic() {
+        let admin = AdminConfig {
+            token: Some("s3cret".into()),
+            username: Some("admin".into()),
+            password: Some("hunter2".into()),
+            ..Default::default()
+        };
+        let basic = general_purpose::STANDARD.encode("admin:hunter2");
+
+        assert!(authorize(&admin, &headers("Bearer s3cret")).is_ok());
+        assert!(authorize(&admin, &headers(&format!("basic {}", basic))).is_ok());
+
+        for bad in ["Bearer s3cre", "Bearer", "Basic YWRtaW46", "s3cret"] {
+            let err = authorize(&admin, &headers(bad)).unwrap_err();
+            assert_eq!(err.code(), "unauthorized", "{}", bad);
+        }
+        assert_eq!(
+            authorize(&admin, &HeaderMap::new()).unwrap_err().code(),
+            "unauthorized"
+        );
+    }
+
+    #[test]
+    fn test_unconfigured_admin_is_forbidden() {
+        let err = authorize(&AdminConfig::default(), &headers("Bearer anything")).unwrap_err();
+        assert_eq!(err.code(), "forbidden");
+    }
+
+    #[test]
+    fn test_secure_eq() {
+        assert!(secure_eq("abc", "abc"));
+        assert!(!secure_eq("abc", "abd"));
+        assert!(!secure_eq("abc", "abcd"));
+    }
+}
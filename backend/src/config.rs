@@ -0,0 +1,836 @@
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::lib::image_library::LibrarySource;
+use crate::lib::tags::TagCategory;
+use crate::print::PrintSettings;
+
+/// Config file read when `--config` / `SNAP_CONFIG` is not given, if present
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+#[derive(Debug, Parser)]
+#[command(name = "poem-backend", about = "Hack and Roll Snap API")]
+pub struct Cli {
+    /// Path to a TOML config file (default: config.toml if it exists)
+    #[arg(long, short)]
+    pub config: Option<PathBuf>,
+
+    /// Address to listen on, e.g. 0.0.0.0:8000
+    #[arg(long)]
+    pub bind: Option<String>,
+
+    /// Directory of hamster images
+    #[arg(long)]
+    pub images_dir: Option<PathBuf>,
+
+    /// Image library CSV
+    #[arg(long)]
+    pub library_csv: Option<PathBuf>,
+
+    /// Which LLM runtime to talk to
+    #[arg(long, value_enum)]
+    pub llm_backend: Option<LlmBackendKind>,
+
+    /// Ollama base URL
+    #[arg(long)]
+    pub ollama_url: Option<String>,
+
+    /// OpenAI-compatible base URL, including the `/v1` prefix
+    #[arg(long)]
+    pub openai_url: Option<String>,
+
+    /// Model used for text and vision generation
+    #[arg(long)]
+    pub model: Option<String>,
+
+    /// Don't serve the `/admin/*` routes at all
+    #[arg(long)]
+    pub disable_admin: bool,
+
+    /// Print the effective configuration as TOML and exit
+    #[arg(long)]
+    pub print_config: bool,
+}
+
+/// Effective backend configuration.
+/// Precedence: built-in defaults < config file < environment < CLI flags.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub llm: LlmConfig,
+    pub ollama: OllamaConfig,
+    pub openai: OpenAiConfig,
+    pub library: LibraryConfig,
+    pub matching: MatchingConfig,
+    pub prompts: PromptsConfig,
+    pub roast: RoastConfig,
+    pub sessions: SessionsConfig,
+    pub history: HistoryConfig,
+    pub printer: PrinterConfig,
+    pub admin: AdminConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LlmBackendKind {
+    /// Ollama's native `/api/*` endpoints
+    Ollama,
+    /// OpenAI-compatible `/v1/*` servers (llama.cpp server, vLLM, LM Studio)
+    Openai,
+}
+
+/// Backend-independent generation settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LlmConfig {
+    pub backend: LlmBackendKind,
+    /// Model for poems, roasts and word extraction
+    pub model: String,
+    pub embedding_model: String,
+    /// Per-request timeout; generous for slow devices like Raspberry Pi
+    pub timeout_secs: u64,
+    pub poem_temperature: f32,
+    pub roast_temperature: f32,
+    pub extraction_temperature: f32,
+    /// Extra attempts, each with a corrective message, when a structured JSON
+    /// reply does not parse
+    pub structured_retries: u32,
+    /// Extra generations when a poem misses its requested form or length
+    pub poem_retries: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OllamaConfig {
+    pub base_url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OpenAiConfig {
+    /// Base URL including the `/v1` prefix
+    pub base_url: String,
+    /// Sent as a bearer token when set; most local servers don't need one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LibraryConfig {
+    pub csv_path: PathBuf,
+    pub images_dir: PathBuf,
+    /// Use the built-in library when the CSV file does not exist
+    pub allow_builtin_fallback: bool,
+    /// Poll the library on disk every N seconds and reload on change (0 = off)
+    pub watch_secs: u64,
+}
+
+/// How `/image/match` compares query words with library tags
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    /// Compare the averaged query with each entry's weighted average
+    #[default]
+    Average,
+    /// Read the query words as object, emotion and style, in that order, and
+    /// compare each only with the entry's tags for the same slot
+    Slots,
+}
+
+/// Relative weight of each slot in `slots` mode
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SlotWeights {
+    pub object: f32,
+    pub emotion: f32,
+    pub style: f32,
+}
+
+/// Defaults for `/image/match` and `/image/match/photo`; requests may override
+/// everything but `photo_embeddings`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MatchingConfig {
+    pub mode: MatchMode,
+    pub slot_weights: SlotWeights,
+    /// Load the CLIP image model and embed every library image at startup,
+    /// for `/image/match/photo`
+    pub photo_embeddings: bool,
+    /// Share of the word score when `/image/match/photo` fuses it with the
+    /// photo score, from 0 to 1
+    pub word_weight: f32,
+}
+
+/// How harsh a roast may be
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RoastIntensity {
+    /// Gentle teasing
+    #[default]
+    Wholesome,
+    /// Teasing with some bite
+    Spicy,
+    /// Over-the-top burns
+    Savage,
+}
+
+/// Defaults and safety checks for `/roast/*`; requests may override
+/// `intensity` and `family_friendly`, never the checks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoastConfig {
+    pub intensity: RoastIntensity,
+    /// Ask for roasts without swearing or adult themes
+    pub family_friendly: bool,
+    /// Extra blocked words or phrases, one per line, on top of the built-in list
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocklist_path: Option<PathBuf>,
+    /// Have the model review each roast before it is returned
+    pub classifier: bool,
+    /// Extra generations when a roast is rejected
+    pub retries: u32,
+}
+
+/// Conversations kept in memory for `/chat/session`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionsConfig {
+    /// Forget a session after this many seconds without a message
+    pub ttl_secs: u64,
+    /// Sessions kept at once; new ones are refused beyond this
+    pub max_sessions: usize,
+    /// History sent to the model, in characters; older turns are dropped to fit
+    pub max_history_chars: usize,
+}
+
+/// Every generation saved to SQLite for `/history`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    /// Save generations at all; the `/history` routes are left out when off
+    pub enabled: bool,
+    pub db_path: PathBuf,
+    /// Delete entries older than this many days (0 = keep forever)
+    pub retention_days: u64,
+    /// Keep at most this many entries, dropping the oldest (0 = no limit)
+    pub max_entries: usize,
+    /// Longest side of the stored photo thumbnail, in pixels (0 = no thumbnails)
+    pub thumbnail_px: u32,
+    /// Give every entry a public `/r/{share_id}` page and QR code
+    pub share: bool,
+    /// Address phones reach this backend at, e.g. `http://192.168.1.20:8000`,
+    /// for QR codes; defaults to the bind address
+    pub public_url: Option<String>,
+}
+
+/// Prompt templates; files in `dir` override the built-in ones
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PromptsConfig {
+    pub dir: PathBuf,
+    /// Poll `dir` every N seconds and reload on change (0 = off)
+    pub watch_secs: u64,
+}
+
+/// Defaults for `/print/*`; requests may override energy and feed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PrinterConfig {
+    /// Print head energy; higher is darker
+    pub energy: u16,
+    /// Blank rows fed after each print
+    pub feed_lines: u16,
+    /// Large text at the top of every receipt
+    pub receipt_header: String,
+    /// Small print at the bottom of every receipt
+    pub receipt_footer: String,
+}
+
+/// Access to the `/admin/*` routes. With neither a token nor a user configured,
+/// every admin request is refused.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    /// Serve the admin routes at all; turn off for kiosk deployments
+    pub enabled: bool,
+    /// Accepted as `Authorization: Bearer <token>`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Accepted with `password` as HTTP basic auth
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: "0.0.0.0:8000".into(),
+        }
+    }
+}
+
+impl Default for LlmConfig {
+    fn default() -> Self {
+        Self {
+            backend: LlmBackendKind::Ollama,
+            model: "gemma3:4b".into(),
+            embedding_model: "nomic-embed-text".into(),
+            timeout_secs: 300,
+            poem_temperature: 0.7,
+            roast_temperature: 0.9,
+            extraction_temperature: 0.3,
+            structured_retries: 2,
+            poem_retries: 2,
+        }
+    }
+}
+
+impl Default for OllamaConfig {
+    fn default() -> Self {
+        Self {
+            base_url: "http://localhost:11434".into(),
+        }
+    }
+}
+
+impl Default for OpenAiConfig {
+    fn default() -> Self {
+        Self {
+            base_url: "http://localhost:8080/v1".into(),
+            api_key: None,
+        }
+    }
+}
+
+impl Default for LibraryConfig {
+    fn default() -> Self {
+        Self {
+            csv_path: "image_library.csv".into(),
+            images_dir: "images".into(),
+            allow_builtin_fallback: true,
+            watch_secs: 0,
+        }
+    }
+}
+
+impl Default for RoastConfig {
+    fn default() -> Self {
+        Self {
+            intensity: RoastIntensity::default(),
+            family_friendly: true,
+            blocklist_path: None,
+            classifier: false,
+            retries: 2,
+        }
+    }
+}
+
+impl Default for SessionsConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: 1800,
+            max_sessions: 100,
+            max_history_chars: 12_000,
+        }
+    }
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            db_path: "history.db".into(),
+            retention_days: 30,
+            max_entries: 10_000,
+            thumbnail_px: 256,
+            share: true,
+            public_url: None,
+        }
+    }
+}
+
+impl Default for PromptsConfig {
+    fn default() -> Self {
+        Self {
+            dir: "prompts".into(),
+            watch_secs: 0,
+        }
+    }
+}
+
+impl Default for MatchingConfig {
+    fn default() -> Self {
+        Self {
+            mode: MatchMode::default(),
+            slot_weights: SlotWeights::default(),
+            photo_embeddings: false,
+            word_weight: 0.3,
+        }
+    }
+}
+
+impl Default for SlotWeights {
+    fn default() -> Self {
+        // Style words like "cute" fit most images, so they count for less
+        Self {
+            object: 1.0,
+            emotion: 1.0,
+            style: 0.5,
+        }
+    }
+}
+
+impl Default for PrinterConfig {
+    fn default() -> Self {
+        let settings = PrintSettings::default();
+        Self {
+            energy: settings.energy,
+            feed_lines: settings.feed_lines,
+            receipt_header: "SNAP".into(),
+            receipt_footer: "Hack and Roll".into(),
+        }
+    }
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            token: None,
+            username: None,
+            password: None,
+        }
+    }
+}
+
+impl AdminConfig {
+    /// Whether any credentials are set up
+    pub fn has_credentials(&self) -> bool {
+        self.token.is_some() || self.username.is_some()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.username.is_some() != self.password.is_some() {
+            return Err("admin username and password must be set together".into());
+        }
+        if [&self.token, &self.password]
+            .iter()
+            .any(|secret| secret.as_deref().is_some_and(str::is_empty))
+        {
+            return Err("admin token and password must not be empty".into());
+        }
+        Ok(())
+    }
+}
+
+impl SessionsConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.ttl_secs == 0 || self.max_sessions == 0 || self.max_history_chars == 0 {
+            return Err(
+                "sessions ttl_secs, max_sessions and max_history_chars must be positive".into(),
+            );
+        }
+        Ok(())
+    }
+}
+
+impl HistoryConfig {
+    fn validate(&self) -> Result<(), String> {
+        if let Some(url) = &self.public_url {
+            if !(url.starts_with("http://") || url.starts_with("https://")) {
+                return Err(format!(
+                    "history public_url must start with http:// or https://, got {:?}",
+                    url
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl MatchingConfig {
+    fn validate(&self) -> Result<(), String> {
+        self.slot_weights.validate()?;
+        if !(0.0..=1.0).contains(&self.word_weight) {
+            return Err("matching word_weight must be between 0 and 1".into());
+        }
+        Ok(())
+    }
+}
+
+impl SlotWeights {
+    pub fn get(&self, slot: TagCategory) -> f32 {
+        match slot {
+            TagCategory::Object => self.object,
+            TagCategory::Emotion => self.emotion,
+            TagCategory::Style => self.style,
+        }
+    }
+
+    pub fn set(&mut self, slot: TagCategory, weight: f32) {
+        match slot {
+            TagCategory::Object => self.object = weight,
+            TagCategory::Emotion => self.emotion = weight,
+            TagCategory::Style => self.style = weight,
+        }
+    }
+
+    /// Every weight must be finite and not negative, and at least one positive
+    pub fn validate(&self) -> Result<(), String> {
+        let weights = TagCategory::SLOTS.map(|slot| self.get(slot));
+        if weights.iter().any(|w| !(w.is_finite() && *w >= 0.0)) {
+            return Err("slot weights must be finite and not negative".into());
+        }
+        if weights.iter().all(|w| *w == 0.0) {
+            return Err("at least one slot weight must be positive".into());
+        }
+        Ok(())
+    }
+}
+
+impl LibraryConfig {
+    pub fn source(&self) -> LibrarySource {
+        LibrarySource {
+            csv_path: self.csv_path.clone(),
+            images_dir: self.images_dir.clone(),
+            allow_builtin_fallback: self.allow_builtin_fallback,
+        }
+    }
+}
+
+impl Config {
+    /// Build the effective configuration from the config file, environment and CLI flags
+    pub fn load(cli: &Cli) -> Result<Self, String> {
+        let explicit_path = cli
+            .config
+            .clone()
+            .or_else(|| std::env::var("SNAP_CONFIG").ok().map(PathBuf::from));
+
+        let mut config = match explicit_path {
+            Some(path) => Self::from_file(&path)?,
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => {
+                Self::from_file(Path::new(DEFAULT_CONFIG_PATH))?
+            }
+            None => Self::default(),
+        };
+
+        config.apply_env()?;
+        config.apply_cli(cli);
+        config.admin.validate()?;
+        config.matching.validate()?;
+        config.sessions.validate()?;
+        config.history.validate()?;
+
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config {}: {}", path.display(), e))?;
+
+        toml::from_str(&text).map_err(|e| format!("Invalid config {}: {}", path.display(), e))
+    }
+
+    fn apply_env(&mut self) -> Result<(), String> {
+        if let Some(v) = env("BIND_ADDR") {
+            self.server.bind = v;
+        }
+        if let Some(port) = env("PORT") {
+            let host = self
+                .server
+                .bind
+                .rsplit_once(':')
+                .map_or("0.0.0.0", |(host, _)| host);
+            self.server.bind = format!("{}:{}", host, port);
+        }
+        if let Some(v) = env("LLM_BACKEND") {
+            self.llm.backend = clap::ValueEnum::from_str(&v, true)
+                .map_err(|_| format!("Invalid value for LLM_BACKEND: {}", v))?;
+        }
+        if let Some(v) = env("LLM_MODEL") {
+            self.llm.model = v;
+        }
+        if let Some(v) = env("LLM_EMBEDDING_MODEL") {
+            self.llm.embedding_model = v;
+        }
+        if let Some(v) = env("LLM_TIMEOUT_SECS") {
+            self.llm.timeout_secs = parse("LLM_TIMEOUT_SECS", &v)?;
+        }
+        if let Some(v) = env("LLM_STRUCTURED_RETRIES") {
+            self.llm.structured_retries = parse("LLM_STRUCTURED_RETRIES", &v)?;
+        }
+        if let Some(v) = env("LLM_POEM_RETRIES") {
+            self.llm.poem_retries = parse("LLM_POEM_RETRIES", &v)?;
+        }
+        if let Some(v) = env("OLLAMA_BASE_URL") {
+            self.ollama.base_url = v;
+        }
+        if let Some(v) = env("OPENAI_BASE_URL") {
+            self.openai.base_url = v;
+        }
+        if let Some(v) = env("OPENAI_API_KEY") {
+            self.openai.api_key = Some(v);
+        }
+        if let Some(v) = env("IMAGE_LIBRARY_CSV") {
+            self.library.csv_path = v.into();
+        }
+        if let Some(v) = env("IMAGES_DIR") {
+            self.library.images_dir = v.into();
+        }
+        if let Some(v) = env_bool("IMAGE_LIBRARY_FALLBACK")? {
+            self.library.allow_builtin_fallback = v;
+        }
+        if let Some(v) = env("LIBRARY_WATCH_SECS") {
+            self.library.watch_secs = parse("LIBRARY_WATCH_SECS", &v)?;
+        }
+        if let Some(v) = env("PROMPTS_DIR") {
+            self.prompts.dir = v.into();
+        }
+        if let Some(v) = env("PROMPTS_WATCH_SECS") {
+            self.prompts.watch_secs = parse("PROMPTS_WATCH_SECS", &v)?;
+        }
+        if let Some(v) = env("ROAST_INTENSITY") {
+            self.roast.intensity = clap::ValueEnum::from_str(&v, true)
+                .map_err(|_| format!("Invalid value for ROAST_INTENSITY: {}", v))?;
+        }
+        if let Some(v) = env_bool("ROAST_FAMILY_FRIENDLY")? {
+            self.roast.family_friendly = v;
+        }
+        if let Some(v) = env("ROAST_BLOCKLIST") {
+            self.roast.blocklist_path = Some(v.into());
+        }
+        if let Some(v) = env_bool("ROAST_CLASSIFIER")? {
+            self.roast.classifier = v;
+        }
+        if let Some(v) = env("MATCH_MODE") {
+            self.matching.mode = clap::ValueEnum::from_str(&v, true)
+                .map_err(|_| format!("Invalid value for MATCH_MODE: {}", v))?;
+        }
+        if let Some(v) = env_bool("PHOTO_EMBEDDINGS")? {
+            self.matching.photo_embeddings = v;
+        }
+        if let Some(v) = env("SESSION_TTL_SECS") {
+            self.sessions.ttl_secs = parse("SESSION_TTL_SECS", &v)?;
+        }
+        if let Some(v) = env("SESSION_MAX_HISTORY_CHARS") {
+            self.sessions.max_history_chars = parse("SESSION_MAX_HISTORY_CHARS", &v)?;
+        }
+        if let Some(v) = env_bool("HISTORY_ENABLED")? {
+            self.history.enabled = v;
+        }
+        if let Some(v) = env("HISTORY_DB") {
+            self.history.db_path = v.into();
+        }
+        if let Some(v) = env("HISTORY_RETENTION_DAYS") {
+            self.history.retention_days = parse("HISTORY_RETENTION_DAYS", &v)?;
+        }
+        if let Some(v) = env("HISTORY_MAX_ENTRIES") {
+            self.history.max_entries = parse("HISTORY_MAX_ENTRIES", &v)?;
+        }
+        if let Some(v) = env_bool("HISTORY_SHARE")? {
+            self.history.share = v;
+        }
+        if let Some(v) = env("PUBLIC_URL") {
+            self.history.public_url = Some(v);
+        }
+        if let Some(v) = env_bool("ADMIN_ENABLED")? {
+            self.admin.enabled = v;
+        }
+        if let Some(v) = env("ADMIN_TOKEN") {
+            self.admin.token = Some(v);
+        }
+        if let Some(v) = env("ADMIN_USERNAME") {
+            self.admin.username = Some(v);
+        }
+        if let Some(v) = env("ADMIN_PASSWORD") {
+            self.admin.password = Some(v);
+        }
+        Ok(())
+    }
+
+    fn apply_cli(&mut self, cli: &Cli) {
+        if let Some(v) = &cli.bind {
+            self.server.bind = v.clone();
+        }
+        if let Some(v) = &cli.images_dir {
+            self.library.images_dir = v.clone();
+        }
+        if let Some(v) = &cli.library_csv {
+            self.library.csv_path = v.clone();
+        }
+        if let Some(v) = cli.llm_backend {
+            self.llm.backend = v;
+        }
+        if let Some(v) = &cli.ollama_url {
+            self.ollama.base_url = v.clone();
+        }
+        if let Some(v) = &cli.openai_url {
+            self.openai.base_url = v.clone();
+        }
+        if let Some(v) = &cli.model {
+            self.llm.model = v.clone();
+        }
+        if cli.disable_admin {
+            self.admin.enabled = false;
+        }
+    }
+
+    /// Render as TOML with secrets masked, for `--print-config`
+    pub fn to_toml(&self) -> Result<String, String> {
+        let mut redacted = self.clone();
+        for secret in [
+            &mut redacted.openai.api_key,
+            &mut redacted.admin.token,
+            &mut redacted.admin.password,
+        ] {
+            if secret.is_some() {
+                *secret = Some("***".into());
+            }
+        }
+
+        toml::to_string_pretty(&redacted).map_err(|e| format!("Failed to serialize config: {}", e))
+    }
+}
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.trim().is_empty())
+}
+
+fn parse<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid value for {}: {}", name, value))
+}
+
+/// An on/off setting from the environment; unset or empty is `None`
+fn env_bool(name: &str) -> Result<Option<bool>, String> {
+    env(name).map(|v| parse_bool(name, &v)).transpose()
+}
+
+fn parse_bool(name: &str, value: &str) -> Result<bool, String> {
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(format!("Invalid value for {}: {}", name, value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_file_keeps_defaults() {
+        let config: Config = toml::from_str("[llm]\nmodel = \"llava\"\n").unwrap();
+        assert_eq!(config.llm.model, "llava");
+        assert_eq!(config.llm.backend, LlmBackendKind::Ollama);
+        assert_eq!(config.llm.timeout_secs, 300);
+        assert_eq!(config.server.bind, "0.0.0.0:8000");
+    }
+
+    #[test]
+    fn test_unknown_keys_are_rejected() {
+        assert!(toml::from_str::<Config>("[llm]\nmodle = \"llava\"\n").is_err());
+    }
+
+    #[test]
+    fn test_backend_kind_parses_lowercase() {
+        let config: Config = toml::from_str("[llm]\nbackend = \"openai\"\n").unwrap();
+        assert_eq!(config.llm.backend, LlmBackendKind::Openai);
+    }
+
+    #[test]
+    fn test_printed_config_masks_api_key() {
+        let mut config = Config::default();
+        config.openai.api_key = Some("sk-secret".into());
+        config.admin.token = Some("admin-secret".into());
+        config.admin.password = Some("hunter2".into());
+        let printed = config.to_toml().unwrap();
+        assert!(!printed.contains("sk-secret"));
+        assert!(!printed.contains("admin-secret"));
+        assert!(!printed.contains("hunter2"));
+    }
+
+    #[test]
+    fn test_admin_credentials_must_be_complete() {
+        let mut admin = AdminConfig {
+            username: Some("admin".into()),
+            ..Default::default()
+        };
+        assert!(admin.validate().is_err());
+        admin.password = Some("hunter2".into());
+        assert!(admin.validate().is_ok());
+        admin.token = Some(String::new());
+        assert!(admin.validate().is_err());
+    }
+
+    #[test]
+    fn test_history_public_url_needs_a_scheme() {
+        let mut history = HistoryConfig {
+            public_url: Some("192.168.1.20:8000".into()),
+            ..Default::default()
+        };
+        assert!(history.validate().is_err());
+        history.public_url = Some("http://192.168.1.20:8000".into());
+        assert!(history.validate().is_ok());
+    }
+
+    #[test]
+    fn test_slot_weights() {
+        let config: Config =
+            toml::from_str("[matching]\nmode = \"slots\"\nslot_weights = { style = 0 }\n").unwrap();
+        assert_eq!(config.matching.mode, MatchMode::Slots);
+        assert_eq!(config.matching.slot_weights.get(TagCategory::Style), 0.0);
+        assert_eq!(config.matching.slot_weights.get(TagCategory::Object), 1.0);
+        assert!(config.matching.slot_weights.validate().is_ok());
+
+        let mut weights = SlotWeights::default();
+        weights.set(TagCategory::Emotion, -1.0);
+        assert!(weights.validate().is_err());
+        let zero = SlotWeights {
+            object: 0.0,
+            emotion: 0.0,
+            style: 0.0,
+        };
+        assert!(zero.validate().is_err());
+
+        let matching = MatchingConfig {
+            word_weight: 1.5,
+            ..Default::default()
+        };
+        assert!(matching.validate().is_err());
+    }
+
+    #[test]
+    fn test_parse_bool() {
+        for value in ["1", "true", "Yes", " ON "] {
+            assert_eq!(parse_bool("ADMIN_ENABLED", value), Ok(true));
+        }
+        for value in ["0", "FALSE", "no", "off"] {
+            assert_eq!(parse_bool("ADMIN_ENABLED", value), Ok(false));
+        }
+        assert_eq!(
+            parse_bool("ADMIN_ENABLED", "nope"),
+            Err("Invalid value for ADMIN_ENABLED: nope".into())
+        );
+    }
+
+    #[test]
+    fn test_printed_config_round_trips() {
+        let printed = Config::default().to_toml().unwrap();
+        let parsed: Config = toml::from_str(&printed).unwrap();
+        assert_eq!(parsed.library.csv_path, PathBuf::from("image_library.csv"));
+    }
+}
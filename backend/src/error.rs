@@ -0,0 +1,208 @@
+use axum::{
+    async_trait,
+    extract::{rejection::JsonRejection, FromRequest, Request},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::de::DeserializeOwned;
+use std::fmt;
+
+use crate::models::{ErrorDetail, ErrorResponse};
+
+/// Every way a request can fail.
+///
+/// Rendered as `{"success": false, "error": {"code": ..., "message": ...}}`;
+/// `code` is stable so clients can branch on it instead of parsing `message`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AppError {
+    /// The request is well-formed but its values are not acceptable
+    Validation(String),
+    /// The body is valid JSON but does not match the expected shape
+    InvalidBody(String),
+    /// Missing or wrong admin credentials
+    Unauthorized(String),
+    /// The route is off limits whatever the credentials
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    /// The LLM server could not be reached or reported itself overloaded
+    UpstreamUnavailable(String),
+    /// The LLM server did not answer within the configured timeout
+    UpstreamTimeout(String),
+    /// The LLM server answered with an error or a malformed response
+    Upstream(String),
+    /// The model answered, but not in the format we asked for
+    ModelParse(String),
+    /// Every generated text was rejected by the safety checks
+    UnsafeOutput(String),
+    /// The image library has no entries to match against, or is not loaded yet
+    LibraryEmpty(String),
+    /// The server is at capacity, e.g. every chat session slot is taken
+    Unavailable(String),
+    Io(String),
+    Internal(String),
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::InvalidBody(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::UpstreamUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::UpstreamTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            AppError::Upstream(_) | AppError::ModelParse(_) | AppError::UnsafeOutput(_) => {
+                StatusCode::BAD_GATEWAY
+            }
+            AppError::LibraryEmpty(_) | AppError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Io(_) | AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Stable machine-readable identifier for the error kind
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Validation(_) => "validation_failed",
+            AppError::InvalidBody(_) => "invalid_body",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::NotFound(_) => "not_found",
+            AppError::Conflict(_) => "conflict",
+            AppError::UpstreamUnavailable(_) => "upstream_unavailable",
+            AppError::UpstreamTimeout(_) => "upstream_timeout",
+            AppError::Upstream(_) => "upstream_error",
+            AppError::ModelParse(_) => "model_parse_failed",
+            AppError::UnsafeOutput(_) => "unsafe_output",
+            AppError::LibraryEmpty(_) => "library_empty",
+            AppError::Unavailable(_) => "unavailable",
+            AppError::Io(_) => "io_error",
+            AppError::Internal(_) => "internal_error",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            AppError::Validation(m)
+            | AppError::InvalidBody(m)
+            | AppError::Unauthorized(m)
+            | AppError::Forbidden(m)
+            | AppError::NotFound(m)
+            | AppError::Conflict(m)
+            | AppError::UpstreamUnavailable(m)
+            | AppError::UpstreamTimeout(m)
+            | AppError::Upstream(m)
+            | AppError::ModelParse(m)
+            | AppError::UnsafeOutput(m)
+            | AppError::LibraryEmpty(m)
+            | AppError::Unavailable(m)
+            | AppError::Io(m)
+            | AppError::Internal(m) => m,
+        }
+    }
+
+    /// Prefix the message, keeping the error kind
+    pub fn context(mut self, context: &str) -> Self {
+        match &mut self {
+            AppError::Validation(m)
+            | AppError::InvalidBody(m)
+            | AppError::Unauthorized(m)
+            | AppError::Forbidden(m)
+            | AppError::NotFound(m)
+            | AppError::Conflict(m)
+            | AppError::UpstreamUnavailable(m)
+            | AppError::UpstreamTimeout(m)
+            | AppError::Upstream(m)
+            | AppError::ModelParse(m)
+            | AppError::UnsafeOutput(m)
+            | AppError::LibraryEmpty(m)
+            | AppError::Unavailable(m)
+            | AppError::Io(m)
+            | AppError::Internal(m) => *m = format!("{}: {}", context, m),
+        }
+        self
+    }
+
+    pub fn detail(&self) -> ErrorDetail {
+        ErrorDetail {
+            code: self.code(),
+            message: self.message().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        if status.is_server_error() {
+            tracing::error!("{} ({}): {}", status, self.code(), self.message());
+        }
+
+        let body = ErrorResponse {
+            success: false,
+            error: self.detail(),
+        };
+        (status, Json(body)).into_response()
+    }
+}
+
+impl From<JsonRejection> for AppError {
+    fn from(rejection: JsonRejection) -> Self {
+        match rejection.status() {
+            StatusCode::UNPROCESSABLE_ENTITY => AppError::InvalidBody(rejection.body_text()),
+            _ => AppError::Validation(rejection.body_text()),
+        }
+    }
+}
+
+/// `Json` extractor whose rejections use the `AppError` envelope
+pub struct AppJson<T>(pub T);
+
+#[async_trait]
+impl<S, T> FromRequest<S> for AppJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state).await?;
+        Ok(AppJson(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_and_code() {
+        let err = AppError::UpstreamTimeout("slow".into());
+        assert_eq!(err.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(err.code(), "upstream_timeout");
+        assert_eq!(
+            AppError::ModelParse("x".into()).status(),
+            StatusCode::BAD_GATEWAY
+        );
+    }
+
+    #[test]
+    fn test_context_keeps_kind() {
+        let err = AppError::UpstreamUnavailable("connection refused".into())
+            .context("Failed to embed query");
+        assert_eq!(err.code(), "upstream_unavailable");
+        assert_eq!(err.message(), "Failed to embed query: connection refused");
+    }
+}
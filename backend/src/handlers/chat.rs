@@ -0,0 +1,222 @@
+use axum::extract::{Json, Path, State};
+use serde::Serialize;
+
+use crate::error::{AppError, AppJson};
+use crate::handlers::validate_image_base64;
+use crate::lib::poem_form::PoemOptions;
+use crate::models::{CreateSessionRequest, SessionMessageRequest};
+use crate::service::history::HistoryMode;
+use crate::service::sessions::{SessionKind, SessionSettings, SessionView};
+use crate::state::AppState;
+
+/// Longest message accepted, in characters
+const MAX_MESSAGE_CHARS: usize = 2_000;
+
+#[derive(Debug, Serialize)]
+pub struct ChatSessionResponse {
+    pub success: bool,
+    /// The model's answer to the message just sent, if one was
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply: Option<String>,
+    /// Template of a poem or roast session's opening prompt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_version: Option<String>,
+    /// Id of the reply's public `/r/{share_id}` page, when it was saved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub share_id: Option<String>,
+    pub session: SessionView,
+}
+
+/// Start a session. Poem and roast sessions open with the usual prompt and
+/// answer it right away; a chat session answers `message` if there is one.
+pub async fn create(
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<CreateSessionRequest>,
+) -> Result<Json<ChatSessionResponse>, AppError> {
+    if let Some(image) = &payload.image_base64 {
+        validate_image_base64(image)?;
+    }
+    if let Some(message) = &payload.message {
+        check_message(message)?;
+    }
+
+    let settings = SessionSettings {
+        kind: payload.kind,
+        intensity: (payload.kind == SessionKind::Roast)
+            .then(|| payload.intensity.unwrap_or(state.config.roast.intensity)),
+        family_friendly: payload
+            .family_friendly
+            .unwrap_or(state.config.roast.family_friendly),
+    };
+    let opening = opening_prompt(&state, &settings, &payload)?;
+
+    let session = state.sessions.create(settings)?;
+    let Some((opening, prompt_version)) = opening else {
+        return Ok(Json(ChatSessionResponse {
+            success: true,
+            reply: None,
+            prompt_version: None,
+            share_id: None,
+            session,
+        }));
+    };
+
+    // A session whose opening failed is of no use to anyone
+    let (reply, share_id, session) = take_turn(&state, &session.id, opening, payload.image_base64)
+        .await
+        .inspect_err(|_| {
+            let _ = state.sessions.delete(&session.id);
+        })?;
+
+    Ok(Json(ChatSessionResponse {
+        success: true,
+        reply: Some(reply),
+        prompt_version,
+        share_id,
+        session,
+    }))
+}
+
+/// Send a follow-up, e.g. "make it funnier", and get the reply
+pub async fn send_message(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    AppJson(payload): AppJson<SessionMessageRequest>,
+) -> Result<Json<ChatSessionResponse>, AppError> {
+    check_message(&payload.message)?;
+    if let Some(image) = &payload.image_base64 {
+        validate_image_base64(image)?;
+    }
+
+    let (reply, share_id, session) =
+        take_turn(&state, &id, payload.message, payload.image_base64).await?;
+
+    Ok(Json(ChatSessionResponse {
+        success: true,
+        reply: Some(reply),
+        prompt_version: None,
+        share_id,
+        session,
+    }))
+}
+
+/// The session's settings and history; images appear by id only
+pub async fn get(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<ChatSessionResponse>, AppError> {
+    Ok(Json(ChatSessionResponse {
+        success: true,
+        reply: None,
+        prompt_version: None,
+        share_id: None,
+        session: state.sessions.get(&id)?,
+    }))
+}
+
+/// End a session early, forgetting its history and images
+pub async fn delete(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<ChatSessionResponse>, AppError> {
+    Ok(Json(ChatSessionResponse {
+        success: true,
+        reply: None,
+        prompt_version: None,
+        share_id: None,
+        session: state.sessions.delete(&id)?,
+    }))
+}
+
+/// Send one message, returning the reply, its share id if it was saved, and
+/// the session after it
+async fn take_turn(
+    state: &AppState,
+    id: &str,
+    message: String,
+    image_base64: Option<String>,
+) -> Result<(String, Option<String>, SessionView), AppError> {
+    let mut recorder = state
+        .history
+        .recorder(HistoryMode::Chat, &state.config.llm.model)
+        .prompt(&message);
+    if let Some(image) = &image_base64 {
+        recorder = recorder.image(image);
+    }
+
+    let turn = state.sessions.begin_turn(id, message, image_base64)?;
+    let reply = state
+        .generator
+        .session_reply(&turn.settings, turn.messages.clone())
+        .await?;
+    let session = turn.finish(reply.clone())?;
+    let share_id = recorder.save(&reply).await;
+
+    Ok((reply, share_id, session))
+}
+
+/// The first message of a new session and its template version, or `None`
+/// for a chat session started without one
+fn opening_prompt(
+    state: &AppState,
+    settings: &SessionSettings,
+    payload: &CreateSessionRequest,
+) -> Result<Option<(String, Option<String>)>, AppError> {
+    let message = payload.message.as_deref().map(str::trim);
+    let with_message = |text: String| match message {
+        Some(message) => format!("{}\n\n{}", text, message),
+        None => text,
+    };
+
+    let prompt = match (settings.kind, &payload.image_base64) {
+        (SessionKind::Chat, None) => return Ok(message.map(|m| (m.to_string(), None))),
+        (SessionKind::Chat, Some(_)) => {
+            let message = message.ok_or_else(|| {
+                AppError::Validation("message is required to send an image".into())
+            })?;
+            return Ok(Some((message.to_string(), None)));
+        }
+        (SessionKind::Roast, None) => {
+            return Err(AppError::Validation(
+                "image_base64 is required for roast sessions".into(),
+            ))
+        }
+        (SessionKind::Roast, Some(_)) => {
+            let intensity = settings.intensity.unwrap_or_default();
+            let prompt = state
+                .generator
+                .roast_prompt(intensity, settings.family_friendly)?;
+            (with_message(prompt.text), prompt.version)
+        }
+        (SessionKind::Poem, Some(_)) => {
+            let prompt = state.generator.poem_from_image_prompt(None)?;
+            (with_message(prompt.text), prompt.version)
+        }
+        (SessionKind::Poem, None) => {
+            let theme = message.ok_or_else(|| {
+                AppError::Validation(
+                    "A poem session needs image_base64 or a message with its theme".into(),
+                )
+            })?;
+            let prompt = state
+                .generator
+                .poem_from_text_prompt(theme, &PoemOptions::default())?;
+            (prompt.text, prompt.version)
+        }
+    };
+
+    Ok(Some((prompt.0, Some(prompt.1))))
+}
+
+fn check_message(message: &str) -> Result<(), AppError> {
+    if message.trim().is_empty() {
+        return Err(AppError::Validation("message cannot be empty".into()));
+    }
+    if message.chars().count() > MAX_MESSAGE_CHARS {
+        return Err(AppError::Validation(format!(
+            "message must be at most {} characters",
+            MAX_MESSAGE_CHARS
+        )));
+    }
+    Ok(())
+}
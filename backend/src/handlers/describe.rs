@@ -0,0 +1,80 @@
+use axum::extract::{Json, State};
+
+use crate::error::{AppError, AppJson};
+use crate::handlers::validate_image_base64;
+use crate::lib::image_library::get_word_library;
+use crate::models::{DescribeImageRequest, DescribeImageResponse, DescribedTag};
+use crate::service::{Generated, LocalEmbeddingService};
+use crate::state::AppState;
+
+/// Describe a photo as object, emotion and style words, plus any extras
+pub async fn describe_image(
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<DescribeImageRequest>,
+) -> Result<Json<DescribeImageResponse>, AppError> {
+    validate_image_base64(&payload.image_base64)?;
+
+    let described = describe(&state, &payload.image_base64, payload.vocabulary_only).await?;
+    let tags = described.output;
+
+    Ok(Json(DescribeImageResponse {
+        success: true,
+        words: tags.iter().map(|t| t.tag.word.clone()).collect(),
+        tags,
+        prompt_version: described.prompt_version,
+    }))
+}
+
+/// Ask the vision model for the photo's tags. With `vocabulary_only`, words
+/// outside `get_word_library()` are replaced by the closest vocabulary word.
+pub async fn describe(
+    state: &AppState,
+    image_base64: &str,
+    vocabulary_only: bool,
+) -> Result<Generated<Vec<DescribedTag>>, AppError> {
+    let vocabulary = get_word_library();
+    let extracted = state
+        .generator
+        .extract_tags_from_image(image_base64, &vocabulary, vocabulary_only)
+        .await
+        .map_err(|e| e.context("Failed to describe image"))?;
+    let prompt_version = extracted.prompt_version;
+    let tags = extracted.output;
+
+    if !vocabulary_only {
+        return Ok(Generated {
+            output: tags
+                .into_iter()
+                .map(|tag| DescribedTag {
+                    tag,
+                    extracted: None,
+                    snap_score: None,
+                })
+                .collect(),
+            prompt_version,
+        });
+    }
+
+    let words: Vec<String> = tags.iter().map(|t| t.word.clone()).collect();
+    let snapped = LocalEmbeddingService::new()
+        .snap_to_vocabulary(&words, &vocabulary)
+        .map_err(|e| e.context("Failed to snap words to the vocabulary"))?;
+
+    let output = tags
+        .into_iter()
+        .zip(snapped)
+        .map(|(mut tag, (word, score))| {
+            let extracted = (word != tag.word).then(|| std::mem::replace(&mut tag.word, word));
+            DescribedTag {
+                tag,
+                extracted,
+                snap_score: Some(score),
+            }
+        })
+        .collect();
+
+    Ok(Generated {
+        output,
+        prompt_version,
+    })
+}
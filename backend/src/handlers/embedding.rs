@@ -1,8 +1,10 @@
-use axum::{extract::Json, http::StatusCode};
+use axum::extract::{Json, State};
 use serde::{Deserialize, Serialize};
 
+use crate::error::{AppError, AppJson};
 use crate::models::{SimilarityResult, TextEmbedding};
-use crate::service::{similarity, OllamaService};
+use crate::service::similarity;
+use crate::state::AppState;
 
 #[derive(Debug, Deserialize)]
 pub struct EmbedRequest {
@@ -24,155 +26,86 @@ pub struct SimilaritySearchRequest {
 #[derive(Debug, Serialize)]
 pub struct EmbedResponse {
     pub success: bool,
-    pub embedding: Option<Vec<f32>>,
-    pub error: Option<String>,
+    pub embedding: Vec<f32>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct EmbedBatchResponse {
     pub success: bool,
-    pub embeddings: Option<Vec<Vec<f32>>>,
-    pub error: Option<String>,
+    pub embeddings: Vec<Vec<f32>>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct SimilaritySearchResponse {
     pub success: bool,
-    pub results: Option<Vec<SimilarityResult>>,
-    pub error: Option<String>,
+    pub results: Vec<SimilarityResult>,
 }
 
 /// Embed a single text
 pub async fn embed_text(
-    Json(payload): Json<EmbedRequest>,
-) -> Result<Json<EmbedResponse>, (StatusCode, Json<EmbedResponse>)> {
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<EmbedRequest>,
+) -> Result<Json<EmbedResponse>, AppError> {
     if payload.text.trim().is_empty() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(EmbedResponse {
-                success: false,
-                embedding: None,
-                error: Some("Text cannot be empty".into()),
-            }),
-        ));
+        return Err(AppError::Validation("Text cannot be empty".into()));
     }
 
-    let ollama = OllamaService::new();
-
-    match ollama.embed_text(&payload.text).await {
-        Ok(embedding) => Ok(Json(EmbedResponse {
-            success: true,
-            embedding: Some(embedding),
-            error: None,
-        })),
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(EmbedResponse {
-                success: false,
-                embedding: None,
-                error: Some(e),
-            }),
-        )),
-    }
+    let embedding = state.generator.embed_text(&payload.text).await?;
+
+    Ok(Json(EmbedResponse {
+        success: true,
+        embedding,
+    }))
 }
 
 /// Embed multiple texts
 pub async fn embed_batch(
-    Json(payload): Json<EmbedBatchRequest>,
-) -> Result<Json<EmbedBatchResponse>, (StatusCode, Json<EmbedBatchResponse>)> {
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<EmbedBatchRequest>,
+) -> Result<Json<EmbedBatchResponse>, AppError> {
     if payload.texts.is_empty() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(EmbedBatchResponse {
-                success: false,
-                embeddings: None,
-                error: Some("Texts array cannot be empty".into()),
-            }),
-        ));
+        return Err(AppError::Validation("Texts array cannot be empty".into()));
     }
 
-    let ollama = OllamaService::new();
-
-    match ollama.embed_texts(&payload.texts).await {
-        Ok(embeddings) => Ok(Json(EmbedBatchResponse {
-            success: true,
-            embeddings: Some(embeddings),
-            error: None,
-        })),
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(EmbedBatchResponse {
-                success: false,
-                embeddings: None,
-                error: Some(e),
-            }),
-        )),
-    }
+    let embeddings = state.generator.embed_texts(&payload.texts).await?;
+
+    Ok(Json(EmbedBatchResponse {
+        success: true,
+        embeddings,
+    }))
 }
 
 /// Search for similar texts in a corpus
 pub async fn similarity_search(
-    Json(payload): Json<SimilaritySearchRequest>,
-) -> Result<Json<SimilaritySearchResponse>, (StatusCode, Json<SimilaritySearchResponse>)> {
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<SimilaritySearchRequest>,
+) -> Result<Json<SimilaritySearchResponse>, AppError> {
     if payload.query.trim().is_empty() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(SimilaritySearchResponse {
-                success: false,
-                results: None,
-                error: Some("Query cannot be empty".into()),
-            }),
-        ));
+        return Err(AppError::Validation("Query cannot be empty".into()));
     }
 
     if payload.corpus.is_empty() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(SimilaritySearchResponse {
-                success: false,
-                results: None,
-                error: Some("Corpus cannot be empty".into()),
-            }),
-        ));
+        return Err(AppError::Validation("Corpus cannot be empty".into()));
     }
 
-    let ollama = OllamaService::new();
+    let generator = &state.generator;
     let top_k = payload.top_k.unwrap_or(5);
 
     // Embed query and corpus
-    let query_embedding = match ollama.embed_text(&payload.query).await {
-        Ok(e) => e,
-        Err(e) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(SimilaritySearchResponse {
-                    success: false,
-                    results: None,
-                    error: Some(format!("Failed to embed query: {}", e)),
-                }),
-            ))
-        }
-    };
-
-    let corpus_embeddings = match ollama.create_text_embeddings(&payload.corpus).await {
-        Ok(e) => e,
-        Err(e) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(SimilaritySearchResponse {
-                    success: false,
-                    results: None,
-                    error: Some(format!("Failed to embed corpus: {}", e)),
-                }),
-            ))
-        }
-    };
+    let query_embedding = generator
+        .embed_text(&payload.query)
+        .await
+        .map_err(|e| e.context("Failed to embed query"))?;
+
+    let corpus_embeddings = generator
+        .create_text_embeddings(&payload.corpus)
+        .await
+        .map_err(|e| e.context("Failed to embed corpus"))?;
 
     let results = similarity::find_similar(&query_embedding, &corpus_embeddings, top_k);
 
     Ok(Json(SimilaritySearchResponse {
         success: true,
-        results: Some(results),
-        error: None,
+        results,
     }))
 }
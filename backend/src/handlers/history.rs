@@ -0,0 +1,106 @@
+use axum::{
+    extract::{rejection::QueryRejection, Json, Path, Query, State},
+    http::{header, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::service::history::{HistoryEntry, HistoryMode};
+use crate::state::AppState;
+
+/// Largest `per_page` accepted
+const MAX_PER_PAGE: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    /// 1-based page number (default 1)
+    pub page: Option<usize>,
+    /// Entries per page (default 20, at most 100)
+    pub per_page: Option<usize>,
+    /// Only entries of this mode
+    pub mode: Option<HistoryMode>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistoryListResponse {
+    pub success: bool,
+    pub page: usize,
+    pub per_page: usize,
+    /// Entries matching the filter, across all pages
+    pub total: u64,
+    pub entries: Vec<HistoryEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistoryEntryResponse {
+    pub success: bool,
+    pub entry: HistoryEntry,
+}
+
+/// Saved generations, newest first
+pub async fn list(
+    State(state): State<AppState>,
+    query: Result<Query<HistoryQuery>, QueryRejection>,
+) -> Result<Json<HistoryListResponse>, AppError> {
+    let Query(query) = query.map_err(|e| AppError::Validation(e.body_text()))?;
+    let page = query.page.unwrap_or(1);
+    let per_page = query.per_page.unwrap_or(20);
+    if page == 0 {
+        return Err(AppError::Validation("page must be at least 1".into()));
+    }
+    if !(1..=MAX_PER_PAGE).contains(&per_page) {
+        return Err(AppError::Validation(format!(
+            "per_page must be between 1 and {}",
+            MAX_PER_PAGE
+        )));
+    }
+
+    let (entries, total) = state.history.list(page, per_page, query.mode)?;
+    Ok(Json(HistoryListResponse {
+        success: true,
+        page,
+        per_page,
+        total,
+        entries,
+    }))
+}
+
+pub async fn get(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<HistoryEntryResponse>, AppError> {
+    Ok(Json(HistoryEntryResponse {
+        success: true,
+        entry: state.history.get(parse_id(&id)?)?,
+    }))
+}
+
+/// The entry's photo as a small JPEG
+pub async fn thumbnail(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Response, AppError> {
+    let jpeg = state.history.thumbnail(parse_id(&id)?)?;
+    Ok((
+        [(header::CONTENT_TYPE, HeaderValue::from_static("image/jpeg"))],
+        jpeg,
+    )
+        .into_response())
+}
+
+pub async fn delete(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<HistoryEntryResponse>, AppError> {
+    Ok(Json(HistoryEntryResponse {
+        success: true,
+        entry: state.history.delete(parse_id(&id)?)?,
+    }))
+}
+
+/// Entry ids are integers; anything else can't name an entry
+fn parse_id(id: &str) -> Result<i64, AppError> {
+    id.parse()
+        .map_err(|_| AppError::NotFound(format!("History entry {} not found", id)))
+}
@@ -1,44 +1,229 @@
-use axum::{extract::Json, http::StatusCode};
+use axum::extract::{Json, State};
+use base64::{engine::general_purpose, Engine};
+use std::collections::HashMap;
 
-use crate::models::{ImageMatchRequest, ImageMatchResponse};
-use crate::service::LocalEmbeddingService;
+use crate::config::{MatchMode, SlotWeights};
+use crate::error::{AppError, AppJson};
+use crate::handlers::describe::describe;
+use crate::handlers::validate_image_base64;
+use crate::lib::image_library::get_word_library;
+use crate::lib::tags::TagCategory;
+use crate::models::{
+    HamsterMatchRequest, HamsterMatchResponse, ImageMatchRequest, ImageMatchResponse,
+    PhotoMatchRequest, PhotoMatchResponse,
+};
+use crate::service::history::HistoryMode;
+use crate::service::photo_embeddings::fuse_scores;
+use crate::service::{LocalEmbeddingService, PhotoEmbeddingService};
+use crate::state::AppState;
 
 pub async fn match_image(
-    Json(payload): Json<ImageMatchRequest>,
-) -> Result<Json<ImageMatchResponse>, (StatusCode, Json<ImageMatchResponse>)> {
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<ImageMatchRequest>,
+) -> Result<Json<ImageMatchResponse>, AppError> {
     if payload.words.is_empty() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ImageMatchResponse {
-                success: false,
-                matched_image_url: None,
-                extracted_words: None,
-                similarity_score: None,
-                error: Some("Words cannot be empty".into()),
-            }),
-        ));
+        return Err(AppError::Validation("Words cannot be empty".into()));
     }
 
-    // Use local embeddings to find best match (zero network calls!)
+    let top_k = check_limits(payload.top_k, payload.min_score)?;
+    let (mode, slot_weights) = match_settings(&state, payload.mode, payload.slot_weights)?;
+    check_query_slots(mode, &slot_weights, &payload.words)?;
+
+    // Use local embeddings to rank matches (zero network calls!)
     let local_embeddings = LocalEmbeddingService::new();
 
-    match local_embeddings.find_best_match(&payload.words) {
-        Ok((url, score)) => Ok(Json(ImageMatchResponse {
-            success: true,
-            matched_image_url: Some(url),
-            extracted_words: Some(payload.words),
-            similarity_score: Some(score),
-            error: None,
-        })),
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ImageMatchResponse {
-                success: false,
-                matched_image_url: None,
-                extracted_words: Some(payload.words),
-                similarity_score: None,
-                error: Some(format!("Failed to match image: {}", e)),
-            }),
-        )),
+    let matches = local_embeddings
+        .find_matches(
+            &payload.words,
+            top_k,
+            payload.min_score,
+            mode,
+            &slot_weights,
+        )
+        .map_err(|e| e.context("Failed to match image"))?;
+
+    let best = matches.first();
+    Ok(Json(ImageMatchResponse {
+        success: true,
+        matched_image_url: best.map(|m| m.image_url.clone()),
+        similarity_score: best.map(|m| m.score),
+        extracted_words: payload.words,
+        mode,
+        matches,
+    }))
+}
+
+/// Match a captured photo against the library images by their CLIP image
+/// embeddings, fused with a word match when there are words to match
+pub async fn match_photo(
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<PhotoMatchRequest>,
+) -> Result<Json<PhotoMatchResponse>, AppError> {
+    validate_image_base64(&payload.image_base64)?;
+    let top_k = check_limits(payload.top_k, payload.min_score)?;
+
+    let word_weight = payload
+        .word_weight
+        .unwrap_or(state.config.matching.word_weight);
+    if !(0.0..=1.0).contains(&word_weight) {
+        return Err(AppError::Validation(
+            "word_weight must be between 0 and 1".into(),
+        ));
+    }
+    let (mode, slot_weights) = match_settings(&state, payload.mode, payload.slot_weights)?;
+
+    let mut words = payload.words;
+    if words.is_empty() && payload.extract_words {
+        words = state
+            .generator
+            .extract_tags_from_image(&payload.image_base64, &get_word_library(), false)
+            .await
+            .map_err(|e| e.context("Failed to describe photo"))?
+            .output
+            .into_iter()
+            .map(|tag| tag.word)
+            .collect();
     }
-}
\ No newline at end of file
+    let fuse_words = !words.is_empty() && word_weight > 0.0;
+    check_query_slots(mode, &slot_weights, &words)?;
+
+    let photo = general_purpose::STANDARD
+        .decode(payload.image_base64.trim())
+        .map_err(|_| AppError::Validation("Invalid base64 image data".into()))?;
+
+    // Decoding and embedding the photo are CPU-bound; keep them off the async workers
+    let photo_scores =
+        tokio::task::spawn_blocking(move || PhotoEmbeddingService::new().rank(&photo))
+            .await
+            .map_err(|e| AppError::Internal(format!("Photo match task failed: {}", e)))?
+            .map_err(|e| e.context("Failed to match photo"))?;
+
+    let word_scores: Option<HashMap<String, f32>> = if fuse_words {
+        let matches = LocalEmbeddingService::new()
+            .find_matches(&words, usize::MAX, None, mode, &slot_weights)
+            .map_err(|e| e.context("Failed to match words"))?;
+        Some(
+            matches
+                .into_iter()
+                .map(|m| (m.image_url, m.score))
+                .collect(),
+        )
+    } else {
+        None
+    };
+
+    let matches = fuse_scores(
+        photo_scores,
+        word_scores.as_ref(),
+        word_weight,
+        top_k,
+        payload.min_score,
+    );
+
+    let best = matches.first();
+    Ok(Json(PhotoMatchResponse {
+        success: true,
+        matched_image_url: best.map(|m| m.image_url.clone()),
+        similarity_score: best.map(|m| m.score),
+        extracted_words: words,
+        word_weight: if fuse_words { word_weight } else { 0.0 },
+        matches,
+    }))
+}
+
+/// Describe a photo and match its words against the library in one call
+pub async fn match_hamster(
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<HamsterMatchRequest>,
+) -> Result<Json<HamsterMatchResponse>, AppError> {
+    validate_image_base64(&payload.image_base64)?;
+    let top_k = check_limits(payload.top_k, payload.min_score)?;
+    let (mode, slot_weights) = match_settings(&state, payload.mode, payload.slot_weights)?;
+    let recorder = state
+        .history
+        .recorder(HistoryMode::HamsterMatch, &state.config.llm.model)
+        .image(&payload.image_base64);
+
+    let described = describe(&state, &payload.image_base64, payload.vocabulary_only).await?;
+    let tags = described.output;
+    let words: Vec<String> = tags.iter().map(|t| t.tag.word.clone()).collect();
+    check_query_slots(mode, &slot_weights, &words)?;
+
+    let matches = LocalEmbeddingService::new()
+        .find_matches(&words, top_k, payload.min_score, mode, &slot_weights)
+        .map_err(|e| e.context("Failed to match image"))?;
+
+    let best = matches.first();
+    let share_id = recorder
+        .prompt_version(&described.prompt_version)
+        .matched_image(best.map(|m| m.image_url.clone()))
+        .save(&words.join(", "))
+        .await;
+    Ok(Json(HamsterMatchResponse {
+        success: true,
+        matched_image_url: best.map(|m| m.image_url.clone()),
+        similarity_score: best.map(|m| m.score),
+        extracted_words: words,
+        tags,
+        prompt_version: described.prompt_version,
+        mode,
+        matches,
+        share_id,
+    }))
+}
+
+/// Check `top_k` and `min_score`, returning `top_k` or its default of 1
+fn check_limits(top_k: Option<usize>, min_score: Option<f32>) -> Result<usize, AppError> {
+    let top_k = top_k.unwrap_or(1);
+    if top_k == 0 {
+        return Err(AppError::Validation("top_k must be at least 1".into()));
+    }
+
+    if let Some(min_score) = min_score {
+        if !(-1.0..=1.0).contains(&min_score) {
+            return Err(AppError::Validation(
+                "min_score must be between -1 and 1".into(),
+            ));
+        }
+    }
+
+    Ok(top_k)
+}
+
+/// The request's match mode and slot weights over the configured defaults
+fn match_settings(
+    state: &AppState,
+    mode: Option<MatchMode>,
+    overrides: HashMap<TagCategory, f32>,
+) -> Result<(MatchMode, SlotWeights), AppError> {
+    let mode = mode.unwrap_or(state.config.matching.mode);
+    let mut slot_weights = state.config.matching.slot_weights;
+    for (slot, weight) in overrides {
+        slot_weights.set(slot, weight);
+    }
+    slot_weights.validate().map_err(AppError::Validation)?;
+
+    Ok((mode, slot_weights))
+}
+
+/// In `slots` mode, slots beyond the query's words don't count, so one of the
+/// ones it has must carry weight
+fn check_query_slots(
+    mode: MatchMode,
+    slot_weights: &SlotWeights,
+    words: &[String],
+) -> Result<(), AppError> {
+    let query_slots = &TagCategory::SLOTS[..words.len().min(TagCategory::SLOTS.len())];
+    if mode == MatchMode::Slots
+        && !query_slots.is_empty()
+        && query_slots
+            .iter()
+            .all(|slot| slot_weights.get(*slot) == 0.0)
+    {
+        return Err(AppError::Validation(
+            "slot weights of the query's slots must not all be zero".into(),
+        ));
+    }
+
+    Ok(())
+}
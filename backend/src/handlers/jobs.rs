@@ -0,0 +1,65 @@
+use axum::{
+    extract::{Json, Path, State},
+    http::StatusCode,
+};
+use serde::Serialize;
+
+use crate::error::AppError;
+use crate::service::jobs::Job;
+use crate::state::AppState;
+
+#[derive(Debug, Serialize)]
+pub struct JobResponse {
+    pub success: bool,
+    pub job: Job,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JobListResponse {
+    pub success: bool,
+    pub jobs: Vec<Job>,
+}
+
+/// Recent background jobs, newest first
+pub async fn list(State(state): State<AppState>) -> Json<JobListResponse> {
+    Json(JobListResponse {
+        success: true,
+        jobs: state.jobs.list(),
+    })
+}
+
+/// Status, counters and per-image progress of one job
+pub async fn get(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<JobResponse>, AppError> {
+    Ok(Json(JobResponse {
+        success: true,
+        job: state.jobs.get(&id)?,
+    }))
+}
+
+/// Stop a queued or running job; the image in flight is abandoned and left pending
+pub async fn cancel(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<(StatusCode, Json<JobResponse>), AppError> {
+    let job = state.jobs.cancel(&id)?;
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(JobResponse { success: true, job }),
+    ))
+}
+
+/// Re-run the failed and unprocessed images of a finished job
+pub async fn retry(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<(StatusCode, Json<JobResponse>), AppError> {
+    let job = state.jobs.retry(&id)?;
+    state.library_generator().spawn(job.id.clone());
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(JobResponse { success: true, job }),
+    ))
+}
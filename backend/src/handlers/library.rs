@@ -0,0 +1,457 @@
+use axum::{
+    extract::{Json, Path, State},
+    http::StatusCode,
+};
+use base64::{engine::general_purpose, Engine};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, AppJson};
+use crate::lib::image_library::{
+    image_hash, is_image_file, lock_library, resolve_image_path, write_library_rows,
+    ImageLibraryRow, IMAGE_EXTENSIONS, IMAGE_URL_PREFIX,
+};
+use crate::lib::tags::{format_tags, normalize_tags, Tag};
+use crate::service::local_embeddings::ReloadStatus;
+use crate::service::LocalEmbeddingService;
+use crate::state::AppState;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ReloadLibraryRequest {
+    /// Block until the new library is swapped in instead of reloading in the background
+    #[serde(default)]
+    pub wait: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReloadLibraryResponse {
+    pub success: bool,
+    /// "started", "queued" behind a running reload, or "completed"
+    pub status: String,
+    /// Entries in the library now being served
+    pub entries: Option<usize>,
+}
+
+/// Rebuild the image library and its embeddings from disk.
+/// `/image/match` keeps serving the previous library until the new one is ready.
+pub async fn reload(
+    payload: Option<AppJson<ReloadLibraryRequest>>,
+) -> Result<(StatusCode, Json<ReloadLibraryResponse>), AppError> {
+    let payload = payload.map(|AppJson(p)| p).unwrap_or_default();
+    let local_embeddings = LocalEmbeddingService::new();
+
+    if payload.wait {
+        let count = tokio::task::spawn_blocking(|| LocalEmbeddingService::new().reload_library())
+            .await
+            .map_err(|e| AppError::Internal(format!("Reload task failed: {}", e)))??;
+
+        return Ok((
+            StatusCode::OK,
+            Json(ReloadLibraryResponse {
+                success: true,
+                status: "completed".into(),
+                entries: Some(count),
+            }),
+        ));
+    }
+
+    let status = match local_embeddings.start_reload()? {
+        ReloadStatus::Started => "started",
+        ReloadStatus::Queued => "queued",
+    };
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(ReloadLibraryResponse {
+            success: true,
+            status: status.into(),
+            entries: local_embeddings.library().ok().map(|library| library.len()),
+        }),
+    ))
+}
+
+/// One library entry as the admin API shows it
+#[derive(Debug, Serialize)]
+pub struct LibraryEntry {
+    /// File name under the images directory; identifies the entry in URLs
+    pub id: String,
+    pub image_url: String,
+    pub tags: Vec<Tag>,
+    /// SHA-256 of the image file the tags belong to
+    pub hash: String,
+    /// Kept as is by library generation
+    pub locked: bool,
+}
+
+impl From<ImageLibraryRow> for LibraryEntry {
+    fn from(row: ImageLibraryRow) -> Self {
+        Self {
+            id: row.filename().unwrap_or_default().to_string(),
+            image_url: row.image_url,
+            tags: row.tags,
+            hash: row.hash,
+            locked: row.locked,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct LibraryEntriesResponse {
+    pub success: bool,
+    pub entries: Vec<LibraryEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LibraryEntryResponse {
+    pub success: bool,
+    pub entry: LibraryEntry,
+    /// Whether `/image/match` already serves the change; if not, it is picked
+    /// up by the next reload
+    pub embedded: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateLibraryEntryRequest {
+    /// File name under the images directory, e.g. `party-hamster.jpg`
+    pub filename: String,
+    /// Weighted, optionally categorised tags describing the image
+    pub tags: Option<Vec<Tag>>,
+    /// Shorthand for `tags`: plain words of weight 1
+    pub words: Option<Vec<String>>,
+    /// Upload the image as well; otherwise the file must already be in the images directory
+    pub image_base64: Option<String>,
+    /// Protect the entry from library generation (default true)
+    pub locked: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateLibraryEntryRequest {
+    /// Replace the tags
+    pub tags: Option<Vec<Tag>>,
+    /// Shorthand for `tags`: plain words of weight 1
+    pub words: Option<Vec<String>>,
+    /// Replace the image file
+    pub image_base64: Option<String>,
+    /// Protect the entry from library generation (default true)
+    pub locked: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DeleteLibraryEntryRequest {
+    /// Also delete the image file
+    #[serde(default)]
+    pub delete_image: bool,
+}
+
+/// Every entry of the library CSV, or of the built-in library while there is no CSV
+pub async fn list_entries(
+    State(state): State<AppState>,
+) -> Result<Json<LibraryEntriesResponse>, AppError> {
+    let rows = state.config.library.source().rows().map_err(AppError::Io)?;
+
+    Ok(Json(LibraryEntriesResponse {
+        success: true,
+        entries: rows.into_iter().map(LibraryEntry::from).collect(),
+    }))
+}
+
+pub async fn get_entry(
+    State(state): State<AppState>,
+    Path(filename): Path<String>,
+) -> Result<Json<LibraryEntryResponse>, AppError> {
+    let rows = state.config.library.source().rows().map_err(AppError::Io)?;
+    let row = rows
+        .into_iter()
+        .find(|row| row.filename() == Some(filename.as_str()))
+        .ok_or_else(|| not_found(&filename))?;
+
+    Ok(Json(LibraryEntryResponse {
+        success: true,
+        entry: row.into(),
+        embedded: true,
+    }))
+}
+
+/// Add an entry, optionally uploading its image, and embed just that entry.
+/// Entries added by hand are locked unless asked otherwise.
+pub async fn create_entry(
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<CreateLibraryEntryRequest>,
+) -> Result<(StatusCode, Json<LibraryEntryResponse>), AppError> {
+    check_filename(&payload.filename)?;
+    let tags = parse_tags(payload.tags, payload.words)?
+        .ok_or_else(|| AppError::Validation("tags or words are required".into()))?;
+    let image = payload
+        .image_base64
+        .as_deref()
+        .map(decode_image)
+        .transpose()?;
+    let image_url = format!("{}{}", IMAGE_URL_PREFIX, payload.filename);
+    let images_dir = &state.config.library.images_dir;
+    let mut uploaded = None;
+
+    let edited = edit_rows(&state, |rows| {
+        if rows.iter().any(|row| row.image_url == image_url) {
+            return Err(AppError::Conflict(format!(
+                "{} is already in the library",
+                payload.filename
+            )));
+        }
+
+        let hash = match &image {
+            Some(bytes) => {
+                let path = images_dir.join(&payload.filename);
+                if path.exists() {
+                    return Err(AppError::Conflict(format!(
+                        "{} already exists in the images directory",
+                        payload.filename
+                    )));
+                }
+                std::fs::write(&path, bytes).map_err(|e| {
+                    AppError::Io(format!("Failed to write {}: {}", path.display(), e))
+                })?;
+                uploaded = Some(path);
+                image_hash(bytes)
+            }
+            None => read_image_hash(&image_url, images_dir)?,
+        };
+
+        let row = ImageLibraryRow {
+            image_url: image_url.clone(),
+            tags: tags.clone(),
+            hash,
+            locked: payload.locked.unwrap_or(true),
+        };
+        rows.push(row.clone());
+        Ok(row)
+    });
+    // An uploaded image without its row would still be matched by photo
+    if let (Err(_), Some(path)) = (&edited, &uploaded) {
+        if let Err(e) = std::fs::remove_file(path) {
+            tracing::warn!("Failed to remove {}: {}", path.display(), e);
+        }
+    }
+    let (row, seeded) = edited?;
+
+    tracing::info!("Added library entry {}: {}", image_url, format_tags(&tags));
+    let embedded = refresh_embeddings(seeded, move |service| {
+        service.upsert_entry(&image_url, tags)
+    })
+    .await;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(LibraryEntryResponse {
+            success: true,
+            entry: row.into(),
+            embedded,
+        }),
+    ))
+}
+
+/// Change an entry's tags, image or lock, and re-embed just that entry.
+/// Edited entries are locked unless asked otherwise.
+pub async fn update_entry(
+    State(state): State<AppState>,
+    Path(filename): Path<String>,
+    AppJson(payload): AppJson<UpdateLibraryEntryRequest>,
+) -> Result<Json<LibraryEntryResponse>, AppError> {
+    let tags = parse_tags(payload.tags, payload.words)?;
+    let image = payload
+        .image_base64
+        .as_deref()
+        .map(decode_image)
+        .transpose()?;
+    let images_dir = &state.config.library.images_dir;
+
+    let ((row, tags), seeded) = edit_rows(&state, |rows| {
+        let row = rows
+            .iter_mut()
+            .find(|row| row.filename() == Some(filename.as_str()))
+            .ok_or_else(|| not_found(&filename))?;
+
+        if let Some(tags) = tags {
+            row.tags = tags;
+        }
+        // Stored tags may predate validation, so check them the way loading does
+        let tags = normalize_tags(row.tags.clone())
+            .map_err(|e| AppError::Validation(format!("{} has {}", filename, e)))?;
+
+        row.hash = match &image {
+            Some(bytes) => {
+                let path = images_dir.join(&filename);
+                std::fs::write(&path, bytes).map_err(|e| {
+                    AppError::Io(format!("Failed to write {}: {}", path.display(), e))
+                })?;
+                image_hash(bytes)
+            }
+            None => read_image_hash(&row.image_url, images_dir)?,
+        };
+        row.locked = payload.locked.unwrap_or(true);
+        Ok((row.clone(), tags))
+    })?;
+
+    tracing::info!("Updated library entry {}", row.image_url);
+    let image_url = row.image_url.clone();
+    let embedded = refresh_embeddings(seeded, move |service| {
+        service.upsert_entry(&image_url, tags)
+    })
+    .await;
+
+    Ok(Json(LibraryEntryResponse {
+        success: true,
+        entry: row.into(),
+        embedded,
+    }))
+}
+
+/// Remove an entry and, if asked, its image file
+pub async fn delete_entry(
+    State(state): State<AppState>,
+    Path(filename): Path<String>,
+    payload: Option<AppJson<DeleteLibraryEntryRequest>>,
+) -> Result<Json<LibraryEntryResponse>, AppError> {
+    let payload = payload.map(|AppJson(p)| p).unwrap_or_default();
+    let images_dir = &state.config.library.images_dir;
+
+    let (row, seeded) = edit_rows(&state, |rows| {
+        let index = rows
+            .iter()
+            .position(|row| row.filename() == Some(filename.as_str()))
+            .ok_or_else(|| not_found(&filename))?;
+        Ok(rows.remove(index))
+    })?;
+
+    if payload.delete_image {
+        let path = images_dir.join(&filename);
+        match std::fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(AppError::Io(format!(
+                    "Removed the entry but failed to delete {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        }
+    }
+
+    tracing::info!("Removed library entry {}", row.image_url);
+    let image_url = row.image_url.clone();
+    let embedded =
+        refresh_embeddings(seeded, move |service| service.remove_entry(&image_url)).await;
+
+    Ok(Json(LibraryEntryResponse {
+        success: true,
+        entry: row.into(),
+        embedded,
+    }))
+}
+
+/// Apply `edit` to the library rows and write them back atomically, all under
+/// the library lock. Also reports whether the CSV was just created from the
+/// built-in library.
+fn edit_rows<T>(
+    state: &AppState,
+    edit: impl FnOnce(&mut Vec<ImageLibraryRow>) -> Result<T, AppError>,
+) -> Result<(T, bool), AppError> {
+    let source = state.config.library.source();
+    let _guard = lock_library();
+
+    let seeded = !source.csv_path.exists();
+    let mut rows = source.rows().map_err(|e| {
+        AppError::Validation(format!(
+            "Existing library can't be read, fix it before editing entries: {}",
+            e
+        ))
+    })?;
+    let result = edit(&mut rows)?;
+    write_library_rows(&source.csv_path, &rows).map_err(AppError::Io)?;
+
+    Ok((result, seeded))
+}
+
+/// Apply an edit to the served library without re-embedding every other entry.
+/// A library that was just created from the built-in list is reloaded in full
+/// instead, since entries whose images are missing were left out of it.
+async fn refresh_embeddings(
+    seeded: bool,
+    update: impl FnOnce(&LocalEmbeddingService) -> Result<(), AppError> + Send + 'static,
+) -> bool {
+    let result = tokio::task::spawn_blocking(move || {
+        let service = LocalEmbeddingService::new();
+        if seeded {
+            service.reload_library().map(|_| ())
+        } else {
+            update(&service)
+        }
+    })
+    .await
+    .map_err(|e| AppError::Internal(format!("Embedding task failed: {}", e)))
+    .and_then(|result| result);
+
+    match result {
+        Ok(()) => true,
+        Err(e) => {
+            tracing::warn!("Library entry saved but not embedded yet: {}", e);
+            false
+        }
+    }
+}
+
+/// A plain image file name, without any directory part
+fn check_filename(filename: &str) -> Result<(), AppError> {
+    if filename.is_empty()
+        || filename.starts_with('.')
+        || filename.contains(['/', '\\'])
+        || !is_image_file(filename)
+    {
+        return Err(AppError::Validation(format!(
+            "filename must be a plain file name ending in .{}",
+            IMAGE_EXTENSIONS.join(", .")
+        )));
+    }
+    Ok(())
+}
+
+/// Validated tags from either `tags` or the `words` shorthand, if given
+fn parse_tags(
+    tags: Option<Vec<Tag>>,
+    words: Option<Vec<String>>,
+) -> Result<Option<Vec<Tag>>, AppError> {
+    let tags = match (tags, words) {
+        (Some(tags), None) => tags,
+        (None, Some(words)) => words.into_iter().map(Tag::new).collect(),
+        (None, None) => return Ok(None),
+        (Some(_), Some(_)) => {
+            return Err(AppError::Validation(
+                "Provide either tags or words, not both".into(),
+            ))
+        }
+    };
+    normalize_tags(tags)
+        .map(Some)
+        .map_err(|e| AppError::Validation(format!("Entry has {}", e)))
+}
+
+/// Decode an uploaded image and check it is in a format we can serve
+fn decode_image(image_base64: &str) -> Result<Vec<u8>, AppError> {
+    let bytes = general_purpose::STANDARD
+        .decode(image_base64.trim())
+        .map_err(|_| AppError::Validation("Invalid base64 image data".into()))?;
+    image::guess_format(&bytes)
+        .map_err(|_| AppError::Validation("image_base64 is not a recognised image".into()))?;
+    Ok(bytes)
+}
+
+/// Hash of an existing library image, which must be present
+fn read_image_hash(image_url: &str, images_dir: &std::path::Path) -> Result<String, AppError> {
+    let path = resolve_image_path(image_url, images_dir).map_err(AppError::Validation)?;
+    let bytes = std::fs::read(&path)
+        .map_err(|e| AppError::Io(format!("Failed to read {}: {}", path.display(), e)))?;
+    Ok(image_hash(&bytes))
+}
+
+fn not_found(filename: &str) -> AppError {
+    AppError::NotFound(format!("{} is not in the library", filename))
+}
@@ -1,6 +1,31 @@
+pub mod chat;
+pub mod describe;
 pub mod embedding;
 pub mod health;
+pub mod history;
+pub mod jobs;
+pub mod library;
 pub mod poem;
+pub mod print;
+pub mod prompts;
 pub mod roast;
+pub mod share;
+pub mod stream;
 pub mod image_match;
-pub mod image_library_generator;
\ No newline at end of file
+pub mod image_library_generator;
+
+use base64::{engine::general_purpose, Engine};
+
+use crate::error::AppError;
+
+/// Reject empty or non-base64 image payloads before they reach the LLM
+pub fn validate_image_base64(image_base64: &str) -> Result<(), AppError> {
+    if image_base64.trim().is_empty() {
+        return Err(AppError::Validation("Image data cannot be empty".into()));
+    }
+
+    general_purpose::STANDARD
+        .decode(image_base64)
+        .map(|_| ())
+        .map_err(|_| AppError::Validation("Invalid base64 image data".into()))
+}
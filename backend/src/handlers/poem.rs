@@ -1,88 +1,114 @@
-use axum::{extract::Json, http::StatusCode};
-use base64::{engine::general_purpose, Engine};
+use axum::extract::{Json, State};
 
+use crate::error::{AppError, AppJson};
+use crate::handlers::stream::{sse_from_tokens, EventStream};
+use crate::handlers::validate_image_base64;
 use crate::models::{ImagePoemRequest, PoemResponse, TextPoemRequest};
-use crate::service::OllamaService;
+use crate::service::history::{HistoryMode, Recorder};
+use crate::state::AppState;
 
 pub async fn generate_from_text(
-    Json(payload): Json<TextPoemRequest>,
-) -> Result<Json<PoemResponse>, (StatusCode, Json<PoemResponse>)> {
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<TextPoemRequest>,
+) -> Result<Json<PoemResponse>, AppError> {
     if payload.prompt.trim().is_empty() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(PoemResponse {
-                success: false,
-                poem: None,
-                error: Some("Prompt cannot be empty".into()),
-            }),
-        ));
+        return Err(AppError::Validation("Prompt cannot be empty".into()));
     }
+    payload.options.validate().map_err(AppError::Validation)?;
+    let recorder = state
+        .history
+        .recorder(HistoryMode::PoemText, &state.config.llm.model)
+        .prompt(&payload.prompt);
 
-    let ollama = OllamaService::new();
+    let fitted = state
+        .generator
+        .generate_poem_from_text(&payload.prompt, &payload.options)
+        .await?;
+    let attempts = (!fitted.constraints.is_empty()).then_some(fitted.attempts);
+    let share_id = recorder
+        .prompt_version(&fitted.prompt_version)
+        .save(&fitted.poem)
+        .await;
 
-    match ollama.generate_poem_from_text(&payload.prompt).await {
-        Ok(poem) => Ok(Json(PoemResponse {
-            success: true,
-            poem: Some(poem),
-            error: None,
-        })),
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(PoemResponse {
-                success: false,
-                poem: None,
-                error: Some(e),
-            }),
-        )),
-    }
+    Ok(Json(PoemResponse {
+        success: true,
+        poem: fitted.poem,
+        attempts,
+        constraints: fitted.constraints,
+        prompt_version: fitted.prompt_version,
+        share_id,
+    }))
 }
 
 pub async fn generate_from_image(
-    Json(payload): Json<ImagePoemRequest>,
-) -> Result<Json<PoemResponse>, (StatusCode, Json<PoemResponse>)> {
-    if payload.image_base64.trim().is_empty() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(PoemResponse {
-                success: false,
-                poem: None,
-                error: Some("Image data cannot be empty".into()),
-            }),
-        ));
-    }
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<ImagePoemRequest>,
+) -> Result<Json<PoemResponse>, AppError> {
+    validate_image_base64(&payload.image_base64)?;
+    let recorder = image_poem_recorder(&state, &payload);
+
+    let poem = state
+        .generator
+        .generate_poem_from_image(&payload.image_base64, payload.prompt.as_deref())
+        .await?;
+    let share_id = recorder
+        .prompt_version(&poem.prompt_version)
+        .save(&poem.output)
+        .await;
 
-    if general_purpose::STANDARD
-        .decode(&payload.image_base64)
-        .is_err()
-    {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(PoemResponse {
-                success: false,
-                poem: None,
-                error: Some("Invalid base64 image data".into()),
-            }),
-        ));
+    Ok(Json(PoemResponse {
+        success: true,
+        poem: poem.output,
+        attempts: None,
+        constraints: Vec::new(),
+        prompt_version: poem.prompt_version,
+        share_id,
+    }))
+}
+
+pub async fn stream_from_text(
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<TextPoemRequest>,
+) -> Result<EventStream, AppError> {
+    if payload.prompt.trim().is_empty() {
+        return Err(AppError::Validation("Prompt cannot be empty".into()));
     }
+    payload.options.validate().map_err(AppError::Validation)?;
+    let recorder = state
+        .history
+        .recorder(HistoryMode::PoemText, &state.config.llm.model)
+        .prompt(&payload.prompt);
 
-    let ollama = OllamaService::new();
+    let tokens = state
+        .generator
+        .stream_poem_from_text(&payload.prompt, &payload.options)
+        .await?;
 
-    match ollama
-        .generate_poem_from_image(&payload.image_base64, payload.prompt.as_deref())
-        .await
-    {
-        Ok(poem) => Ok(Json(PoemResponse {
-            success: true,
-            poem: Some(poem),
-            error: None,
-        })),
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(PoemResponse {
-                success: false,
-                poem: None,
-                error: Some(e),
-            }),
-        )),
+    Ok(sse_from_tokens(tokens, recorder))
+}
+
+pub async fn stream_from_image(
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<ImagePoemRequest>,
+) -> Result<EventStream, AppError> {
+    validate_image_base64(&payload.image_base64)?;
+    let recorder = image_poem_recorder(&state, &payload);
+
+    let tokens = state
+        .generator
+        .stream_poem_from_image(&payload.image_base64, payload.prompt.as_deref())
+        .await?;
+
+    Ok(sse_from_tokens(tokens, recorder))
+}
+
+fn image_poem_recorder(state: &AppState, payload: &ImagePoemRequest) -> Recorder {
+    let recorder = state
+        .history
+        .recorder(HistoryMode::PoemImage, &state.config.llm.model)
+        .image(&payload.image_base64);
+    match &payload.prompt {
+        Some(prompt) => recorder.prompt(prompt),
+        None => recorder,
     }
 }
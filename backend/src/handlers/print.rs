@@ -0,0 +1,247 @@
+use axum::Json;
+use axum::{
+    extract::State,
+    http::{header, HeaderName, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose, Engine};
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+use crate::error::{AppError, AppJson};
+use crate::handlers::share::share_url;
+use crate::lib::image_library::resolve_image_path;
+use crate::print::{
+    encode_job, qr_bitmap, render_image, render_receipt, utc_date, DitherMode, PrintSettings,
+    ReceiptContent, PRINTER_WIDTH,
+};
+use crate::state::AppState;
+
+/// Longest paper feed a request may ask for, in rows
+const MAX_FEED_LINES: u16 = 1000;
+
+/// Longest receipt text, in characters
+const MAX_RECEIPT_TEXT: usize = 2000;
+
+/// Longest receipt title or date line, in characters
+const MAX_RECEIPT_HEADING: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct PrintRenderRequest {
+    /// Image to print, e.g. the captured photo
+    pub image_base64: Option<String>,
+    /// Or a library image, e.g. the matched hamster (`/images/<file>`)
+    pub image_url: Option<String>,
+    #[serde(default)]
+    pub dither: DitherMode,
+    /// Luminance below which a dot is printed (default 128)
+    pub threshold: Option<u8>,
+    /// Print head energy; defaults to `[printer] energy`
+    pub energy: Option<u16>,
+    /// Blank rows fed after the image; defaults to `[printer] feed_lines`
+    pub feed_lines: Option<u16>,
+    #[serde(default)]
+    pub format: RenderFormat,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderFormat {
+    /// PNG preview of the dithered bitmap
+    #[default]
+    Png,
+    /// Raw printer command stream, ready to write to the printer
+    Packets,
+}
+
+/// Render an image for the thermal printer.
+/// The number of printed rows is returned in the `X-Print-Rows` header.
+pub async fn render(
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<PrintRenderRequest>,
+) -> Result<Response, AppError> {
+    let image = match (&payload.image_base64, &payload.image_url) {
+        (Some(image_base64), None) => decode_base64(image_base64)?,
+        (None, Some(image_url)) => read_library_image(&state, image_url).await?,
+        _ => {
+            return Err(AppError::Validation(
+                "Provide exactly one of image_base64 or image_url".into(),
+            ))
+        }
+    };
+
+    let settings = PrintSettings {
+        energy: payload.energy.unwrap_or(state.config.printer.energy),
+        feed_lines: payload
+            .feed_lines
+            .unwrap_or(state.config.printer.feed_lines),
+    };
+    if settings.feed_lines > MAX_FEED_LINES {
+        return Err(AppError::Validation(format!(
+            "feed_lines must be at most {}",
+            MAX_FEED_LINES
+        )));
+    }
+
+    let mode = payload.dither;
+    let threshold = payload.threshold.unwrap_or(128);
+    let format = payload.format;
+
+    // Decoding, scaling and dithering are CPU-bound; keep them off the async workers
+    let (bytes, rows) = tokio::task::spawn_blocking(move || {
+        let bitmap = render_image(&image, mode, threshold)?;
+        let bytes = match format {
+            RenderFormat::Png => bitmap.to_png()?,
+            RenderFormat::Packets => encode_job(&bitmap, &settings),
+        };
+        Ok::<_, AppError>((bytes, bitmap.height()))
+    })
+    .await
+    .map_err(|e| AppError::Internal(format!("Render task failed: {}", e)))??;
+
+    let content_type = match format {
+        RenderFormat::Png => "image/png",
+        RenderFormat::Packets => "application/octet-stream",
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, HeaderValue::from_static(content_type)),
+            (
+                HeaderName::from_static("x-print-rows"),
+                HeaderValue::from(rows),
+            ),
+        ],
+        bytes,
+    )
+        .into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PrintReceiptRequest {
+    /// The poem, roast or other text to print
+    pub text: String,
+    /// Heading above the text, e.g. "YOUR ROAST"
+    pub title: Option<String>,
+    /// The captured photo, printed at the top
+    pub photo_base64: Option<String>,
+    /// A library image printed below the text, e.g. the matched hamster (`/images/<file>`)
+    pub image_url: Option<String>,
+    /// Date line under the header; defaults to today's date (UTC)
+    pub date: Option<String>,
+    /// Share id of the result, printed as a QR code of its `/r/{share_id}` page
+    pub share_id: Option<String>,
+    #[serde(default)]
+    pub dither: DitherMode,
+    /// Luminance below which a dot is printed (default 128)
+    pub threshold: Option<u8>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PrintReceiptResponse {
+    pub success: bool,
+    pub width: u32,
+    pub height: u32,
+    /// PNG preview of the receipt
+    pub png_base64: String,
+    /// Packed 1-bit rows, 48 bytes each, least significant bit = leftmost dot, 1 = black
+    pub rows_base64: String,
+}
+
+/// Lay out a poem or roast as a receipt: header and date, the photo, the
+/// word-wrapped text, the matched image, a QR code of its share page and a footer.
+pub async fn receipt(
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<PrintReceiptRequest>,
+) -> Result<Json<PrintReceiptResponse>, AppError> {
+    if payload.text.trim().is_empty() {
+        return Err(AppError::Validation("text is required".into()));
+    }
+    if payload.text.chars().count() > MAX_RECEIPT_TEXT {
+        return Err(AppError::Validation(format!(
+            "text must be at most {} characters",
+            MAX_RECEIPT_TEXT
+        )));
+    }
+    for (name, value) in [("title", &payload.title), ("date", &payload.date)] {
+        if value
+            .as_ref()
+            .is_some_and(|v| v.chars().count() > MAX_RECEIPT_HEADING)
+        {
+            return Err(AppError::Validation(format!(
+                "{} must be at most {} characters",
+                name, MAX_RECEIPT_HEADING
+            )));
+        }
+    }
+
+    let photo = match &payload.photo_base64 {
+        Some(photo_base64) => Some(decode_base64(photo_base64)?),
+        None => None,
+    };
+    let image = match &payload.image_url {
+        Some(image_url) => Some(read_library_image(&state, image_url).await?),
+        None => None,
+    };
+    let share_url = match &payload.share_id {
+        Some(share_id) => {
+            let entry = state.history.find_shared(share_id)?;
+            Some(share_url(&state, &entry.share_id))
+        }
+        None => None,
+    };
+
+    let printer = &state.config.printer;
+    let mut content = ReceiptContent {
+        header: printer.receipt_header.clone(),
+        date: payload.date.unwrap_or_else(|| utc_date(SystemTime::now())),
+        photo: None,
+        title: payload.title,
+        text: payload.text,
+        image: None,
+        qr: None,
+        footer: printer.receipt_footer.clone(),
+    };
+    let mode = payload.dither;
+    let threshold = payload.threshold.unwrap_or(128);
+
+    let (bitmap, png) = tokio::task::spawn_blocking(move || {
+        content.photo = photo
+            .map(|bytes| render_image(&bytes, mode, threshold))
+            .transpose()?;
+        content.image = image
+            .map(|bytes| render_image(&bytes, mode, threshold))
+            .transpose()?;
+        content.qr = share_url.map(|url| qr_bitmap(&url)).transpose()?;
+        let bitmap = render_receipt(&content);
+        let png = bitmap.to_png()?;
+        Ok::<_, AppError>((bitmap, png))
+    })
+    .await
+    .map_err(|e| AppError::Internal(format!("Render task failed: {}", e)))??;
+
+    let rows: Vec<u8> = bitmap.rows().flatten().copied().collect();
+
+    Ok(Json(PrintReceiptResponse {
+        success: true,
+        width: PRINTER_WIDTH,
+        height: bitmap.height(),
+        png_base64: general_purpose::STANDARD.encode(png),
+        rows_base64: general_purpose::STANDARD.encode(rows),
+    }))
+}
+
+fn decode_base64(data: &str) -> Result<Vec<u8>, AppError> {
+    general_purpose::STANDARD
+        .decode(data.trim())
+        .map_err(|_| AppError::Validation("Invalid base64 image data".into()))
+}
+
+/// Read an image from the library's images directory by its `/images/<file>` URL
+async fn read_library_image(state: &AppState, image_url: &str) -> Result<Vec<u8>, AppError> {
+    let path = resolve_image_path(image_url, &state.config.library.images_dir)
+        .map_err(AppError::NotFound)?;
+    tokio::fs::read(&path)
+        .await
+        .map_err(|e| AppError::Io(format!("Failed to read {}: {}", path.display(), e)))
+}
@@ -0,0 +1,39 @@
+use axum::extract::{Json, State};
+use serde::Serialize;
+
+use crate::error::AppError;
+use crate::service::prompts::PromptTemplate;
+use crate::state::AppState;
+
+#[derive(Debug, Serialize)]
+pub struct PromptsResponse {
+    pub success: bool,
+    /// Where template files are read from
+    pub dir: String,
+    pub prompts: Vec<PromptTemplate>,
+}
+
+/// The prompt templates in use, with their versions and variables
+pub async fn list(State(state): State<AppState>) -> Result<Json<PromptsResponse>, AppError> {
+    let prompts = state.generator.prompts();
+    Ok(Json(PromptsResponse {
+        success: true,
+        dir: prompts.dir().display().to_string(),
+        prompts: prompts.list()?,
+    }))
+}
+
+/// Re-read the prompt templates. A broken template fails the reload and the
+/// previous templates stay in use.
+pub async fn reload(State(state): State<AppState>) -> Result<Json<PromptsResponse>, AppError> {
+    let prompts = state.generator.prompts();
+    let templates = prompts
+        .reload()
+        .map_err(|e| e.context("Failed to reload prompts"))?;
+
+    Ok(Json(PromptsResponse {
+        success: true,
+        dir: prompts.dir().display().to_string(),
+        prompts: templates,
+    }))
+}
@@ -1,55 +1,71 @@
-use axum::{extract::Json, http::StatusCode};
-use base64::{engine::general_purpose, Engine};
+use axum::extract::{Json, State};
 
+use crate::config::RoastIntensity;
+use crate::error::{AppError, AppJson};
+use crate::handlers::stream::{sse_from_tokens, EventStream};
+use crate::handlers::validate_image_base64;
 use crate::models::{ImageRoastRequest, RoastResponse};
-use crate::service::OllamaService;
+use crate::service::history::{HistoryMode, Recorder};
+use crate::state::AppState;
 
 pub async fn generate_from_image(
-    Json(payload): Json<ImageRoastRequest>,
-) -> Result<Json<RoastResponse>, (StatusCode, Json<RoastResponse>)> {
-    if payload.image_base64.trim().is_empty() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(RoastResponse {
-                success: false,
-                roast: None,
-                error: Some("Image data cannot be empty".into()),
-            }),
-        ));
-    }
-
-    if general_purpose::STANDARD
-        .decode(&payload.image_base64)
-        .is_err()
-    {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(RoastResponse {
-                success: false,
-                roast: None,
-                error: Some("Invalid base64 image data".into()),
-            }),
-        ));
-    }
-
-    let ollama = OllamaService::new();
-
-    match ollama
-        .generate_roast_from_image(&payload.image_base64)
-        .await
-    {
-        Ok(roast) => Ok(Json(RoastResponse {
-            success: true,
-            roast: Some(roast),
-            error: None,
-        })),
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(RoastResponse {
-                success: false,
-                roast: None,
-                error: Some(e),
-            }),
-        )),
-    }
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<ImageRoastRequest>,
+) -> Result<Json<RoastResponse>, AppError> {
+    validate_image_base64(&payload.image_base64)?;
+    let (intensity, family_friendly) = roast_settings(&state, &payload);
+    let recorder = roast_recorder(&state, &payload);
+
+    let roast = state
+        .generator
+        .generate_roast_from_image(&payload.image_base64, intensity, family_friendly)
+        .await?;
+    let share_id = recorder
+        .prompt_version(&roast.prompt_version)
+        .save(&roast.roast)
+        .await;
+
+    Ok(Json(RoastResponse {
+        success: true,
+        roast: roast.roast,
+        intensity,
+        attempts: roast.attempts,
+        prompt_version: roast.prompt_version,
+        share_id,
+    }))
+}
+
+/// Buffered: nothing is sent until a roast passes the safety checks, after
+/// the same regenerations as `/roast/image`
+pub async fn stream_from_image(
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<ImageRoastRequest>,
+) -> Result<EventStream, AppError> {
+    validate_image_base64(&payload.image_base64)?;
+    let (intensity, family_friendly) = roast_settings(&state, &payload);
+    let recorder = roast_recorder(&state, &payload);
+
+    let tokens = state
+        .generator
+        .stream_roast_from_image(&payload.image_base64, intensity, family_friendly)
+        .await?;
+
+    Ok(sse_from_tokens(tokens, recorder))
+}
+
+fn roast_recorder(state: &AppState, payload: &ImageRoastRequest) -> Recorder {
+    state
+        .history
+        .recorder(HistoryMode::Roast, &state.config.llm.model)
+        .image(&payload.image_base64)
+}
+
+/// The request's intensity and family-friendly flag over the configured defaults
+fn roast_settings(state: &AppState, payload: &ImageRoastRequest) -> (RoastIntensity, bool) {
+    (
+        payload.intensity.unwrap_or(state.config.roast.intensity),
+        payload
+            .family_friendly
+            .unwrap_or(state.config.roast.family_friendly),
+    )
 }
@@ -0,0 +1,209 @@
+use axum::{
+    extract::{rejection::QueryRejection, Path, Query, State},
+    http::{header, HeaderName, HeaderValue, StatusCode},
+    response::{Html, IntoResponse, Response},
+};
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::error::AppError;
+use crate::handlers::print::RenderFormat;
+use crate::print::{encode_job, qr_bitmap, qr_png, utc_date, PrintSettings};
+use crate::service::history::{HistoryEntry, HistoryMode};
+use crate::state::AppState;
+
+/// Share pages load nothing but their own images, and stay out of search results
+const PAGE_HEADERS: [(HeaderName, &str); 4] = [
+    (
+        header::CONTENT_SECURITY_POLICY,
+        "default-src 'none'; img-src 'self'; style-src 'unsafe-inline'",
+    ),
+    (header::REFERRER_POLICY, "no-referrer"),
+    (HeaderName::from_static("x-robots-tag"), "noindex"),
+    (header::CACHE_CONTROL, "private, max-age=300"),
+];
+
+#[derive(Debug, Deserialize)]
+pub struct QrQuery {
+    /// `png`, or `packets` for the receipt printer
+    #[serde(default)]
+    pub format: RenderFormat,
+}
+
+/// The result page a visitor's QR code points to: their photo, the text and
+/// the matched hamster
+pub async fn page(State(state): State<AppState>, Path(share_id): Path<String>) -> Response {
+    let (status, body) = match state.history.find_shared(&share_id) {
+        Ok(entry) => (StatusCode::OK, render_page(&state, &entry)),
+        Err(AppError::NotFound(_)) => (StatusCode::NOT_FOUND, render_missing(&state)),
+        Err(e) => return e.into_response(),
+    };
+
+    let headers = PAGE_HEADERS.map(|(name, value)| (name, HeaderValue::from_static(value)));
+    (status, headers, Html(body)).into_response()
+}
+
+/// The shared result's photo, as the saved thumbnail
+pub async fn photo(
+    State(state): State<AppState>,
+    Path(share_id): Path<String>,
+) -> Result<Response, AppError> {
+    let entry = state.history.find_shared(&share_id)?;
+    let jpeg = state.history.thumbnail(entry.id)?;
+    Ok((
+        [(header::CONTENT_TYPE, HeaderValue::from_static("image/jpeg"))],
+        jpeg,
+    )
+        .into_response())
+}
+
+/// QR code of the share page's full URL, as a PNG or as printer packets.
+/// The number of printed rows is returned in the `X-Print-Rows` header.
+pub async fn qr(
+    State(state): State<AppState>,
+    Path(share_id): Path<String>,
+    query: Result<Query<QrQuery>, QueryRejection>,
+) -> Result<Response, AppError> {
+    let Query(query) = query.map_err(|e| AppError::Validation(e.body_text()))?;
+    let entry = state.history.find_shared(&share_id)?;
+    let url = share_url(&state, &entry.share_id);
+
+    match query.format {
+        RenderFormat::Png => Ok((
+            [(header::CONTENT_TYPE, HeaderValue::from_static("image/png"))],
+            qr_png(&url)?,
+        )
+            .into_response()),
+        RenderFormat::Packets => {
+            let bitmap = qr_bitmap(&url)?;
+            let settings = PrintSettings {
+                energy: state.config.printer.energy,
+                feed_lines: state.config.printer.feed_lines,
+            };
+            Ok((
+                [
+                    (
+                        header::CONTENT_TYPE,
+                        HeaderValue::from_static("application/octet-stream"),
+                    ),
+                    (
+                        HeaderName::from_static("x-print-rows"),
+                        HeaderValue::from(bitmap.height()),
+                    ),
+                ],
+                encode_job(&bitmap, &settings),
+            )
+                .into_response())
+        }
+    }
+}
+
+/// Full URL of a share page: `[history] public_url` if set, otherwise the
+/// configured bind address. Never the request's `Host`, which the client controls.
+pub fn share_url(state: &AppState, share_id: &str) -> String {
+    let base = match &state.config.history.public_url {
+        Some(url) => url.trim_end_matches('/').to_string(),
+        None => format!("http://{}", bind_host(&state.config.server.bind)),
+    };
+    format!("{}/r/{}", base, share_id)
+}
+
+/// The bind address as a host to put in a URL; listening on every interface
+/// becomes `localhost`
+fn bind_host(bind: &str) -> String {
+    match bind.parse::<SocketAddr>() {
+        Ok(addr) if addr.ip().is_unspecified() => format!("localhost:{}", addr.port()),
+        _ => bind.to_string(),
+    }
+}
+
+fn render_page(state: &AppState, entry: &HistoryEntry) -> String {
+    let heading = match entry.mode {
+        HistoryMode::PoemText | HistoryMode::PoemImage => "Your poem",
+        HistoryMode::Roast => "Your roast",
+        HistoryMode::HamsterMatch => "Your hamster",
+        HistoryMode::Chat => "Your reply",
+    };
+
+    let mut body = String::new();
+    if entry.thumbnail_url.is_some() {
+        body.push_str(&format!(
+            r#"<img src="/r/{}/photo.jpg" alt="Your photo">"#,
+            escape_html(&entry.share_id)
+        ));
+    }
+    body.push_str(&format!(
+        "<h2>{}</h2><p class=\"text\">{}</p>",
+        heading,
+        escape_html(&entry.output)
+    ));
+    if let Some(url) = &entry.matched_image_url {
+        body.push_str(&format!(
+            r#"<img src="{}" alt="Your matched hamster">"#,
+            escape_html(url)
+        ));
+    }
+
+    let date = utc_date(UNIX_EPOCH + Duration::from_secs(entry.created_at));
+    layout(
+        state,
+        heading,
+        &format!("<p class=\"date\">{}</p>{}", date, body),
+    )
+}
+
+fn render_missing(state: &AppState) -> String {
+    layout(
+        state,
+        "Not found",
+        "<h2>This result is gone</h2><p>Results are only kept for a while. Thanks for stopping by!</p>",
+    )
+}
+
+/// The page around `body`, branded like the receipt
+fn layout(state: &AppState, title: &str, body: &str) -> String {
+    let printer = &state.config.printer;
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title} | {header}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; max-width: 32rem; margin: 0 auto; padding: 1.5rem; text-align: center; color: #222; }}
+img {{ max-width: 100%; border-radius: 0.5rem; }}
+.text {{ white-space: pre-wrap; font-size: 1.15rem; line-height: 1.5; text-align: left; }}
+.date, footer {{ color: #777; font-size: 0.9rem; }}
+</style>
+</head>
+<body>
+<h1>{header}</h1>
+{body}
+<footer>{footer}</footer>
+</body>
+</html>
+"#,
+        title = escape_html(title),
+        header = escape_html(&printer.receipt_header),
+        footer = escape_html(&printer.receipt_footer),
+        body = body,
+    )
+}
+
+/// Make text safe to place in HTML content or a quoted attribute
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
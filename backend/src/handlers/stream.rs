@@ -0,0 +1,81 @@
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures_util::StreamExt;
+use std::convert::Infallible;
+use std::time::Instant;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::models::{StreamDoneEvent, StreamErrorEvent, StreamTokenEvent};
+use crate::service::history::Recorder;
+use crate::service::{Generated, TokenStream};
+
+pub type EventStream = Sse<ReceiverStream<Result<Event, Infallible>>>;
+
+/// Forward generated tokens to the client as Server-Sent Events.
+///
+/// Emits one `token` event per chunk, then a single `done` event with the
+/// full text, prompt version and timing, or an `error` event if generation
+/// fails midway. The full text is saved to history just before the `done`
+/// event is sent.
+pub fn sse_from_tokens(stream: Generated<TokenStream>, recorder: Recorder) -> EventStream {
+    let (tx, rx) = mpsc::channel(32);
+    let Generated {
+        output: mut tokens,
+        prompt_version,
+    } = stream;
+    let recorder = recorder.prompt_version(&prompt_version);
+
+    tokio::spawn(async move {
+        let started = Instant::now();
+        let mut first_token_ms = None;
+        let mut text = String::new();
+
+        while let Some(item) = tokens.next().await {
+            let token = match item {
+                Ok(token) => token,
+                Err(e) => {
+                    tracing::error!("Stream failed: {}", e);
+                    let event = StreamErrorEvent { error: e.detail() };
+                    let _ = tx.send(Ok(json_event("error", &event))).await;
+                    return;
+                }
+            };
+
+            if token.is_empty() {
+                continue;
+            }
+
+            first_token_ms.get_or_insert(started.elapsed().as_millis() as u64);
+            text.push_str(&token);
+
+            // Client went away, stop pulling from Ollama
+            if tx
+                .send(Ok(json_event("token", &StreamTokenEvent { token })))
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        let share_id = recorder.save(&text).await;
+        let done = StreamDoneEvent {
+            text,
+            elapsed_ms,
+            first_token_ms,
+            prompt_version,
+            share_id,
+        };
+        let _ = tx.send(Ok(json_event("done", &done))).await;
+    });
+
+    Sse::new(ReceiverStream::new(rx)).keep_alive(KeepAlive::default())
+}
+
+fn json_event<T: serde::Serialize>(name: &str, data: &T) -> Event {
+    Event::default()
+        .event(name)
+        .json_data(data)
+        .unwrap_or_else(|_| Event::default().event(name))
+}
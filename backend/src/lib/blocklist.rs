@@ -0,0 +1,107 @@
+//! Words and phrases a generated text must not contain.
+//!
+//! Matching is on whole words, ignoring case and punctuation, so `fat` catches
+//! "Fat!" but not "fathom". Plurals ending in `s`, `es` or `ies` also match.
+
+/// Blocked terms, each split into lowercase words
+#[derive(Debug, Clone, Default)]
+pub struct Blocklist {
+    phrases: Vec<Vec<String>>,
+}
+
+impl Blocklist {
+    pub fn new<S: AsRef<str>>(terms: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            phrases: terms
+                .into_iter()
+                .map(|term| words(term.as_ref()))
+                .filter(|phrase| !phrase.is_empty())
+                .collect(),
+        }
+    }
+
+    /// One term per line; blank lines and `#` comments are skipped
+    pub fn parse(text: &str) -> Self {
+        Self::new(
+            text.lines()
+                .map(|line| line.split('#').next().unwrap_or_default()),
+        )
+    }
+
+    pub fn extend(&mut self, other: Blocklist) {
+        self.phrases.extend(other.phrases);
+    }
+
+    /// The first blocked term found in `text`, as written in the list
+    pub fn find(&self, text: &str) -> Option<String> {
+        let text = words(text);
+        self.phrases
+            .iter()
+            .find(|phrase| {
+                text.windows(phrase.len()).any(|window| {
+                    let (last, rest) = phrase.split_last().expect("phrases are not empty");
+                    window[..rest.len()] == *rest && is_word_or_plural(&window[rest.len()], last)
+                })
+            })
+            .map(|phrase| phrase.join(" "))
+    }
+}
+
+/// `word` is `term`, or its plural: `-ies` for `-y`, `-es` only after
+/// s, x, z, ch or sh (so `fat` doesn't match "fates"), otherwise `-s`
+fn is_word_or_plural(word: &str, term: &str) -> bool {
+    let plural_y = term
+        .strip_suffix('y')
+        .and_then(|stem| word.strip_prefix(stem))
+        .is_some_and(|suffix| suffix == "ies");
+    let takes_es = ["s", "x", "z", "ch", "sh"]
+        .iter()
+        .any(|ending| term.ends_with(ending));
+    plural_y
+        || word.strip_prefix(term).is_some_and(|suffix| {
+            suffix.is_empty() || suffix == "s" || (takes_es && suffix == "es")
+        })
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_whole_words_and_plurals() {
+        let blocklist = Blocklist::new(["fat", "beer belly"]);
+        assert_eq!(blocklist.find("What a FAT cat!"), Some("fat".into()));
+        assert_eq!(
+            blocklist.find("Nice beer-bellies."),
+            Some("beer belly".into())
+        );
+        assert_eq!(blocklist.find("Two beer bellys"), Some("beer belly".into()));
+        assert_eq!(blocklist.find("I can't fathom that hat"), None);
+        assert_eq!(blocklist.find("a beer, a belly"), None);
+    }
+
+    #[test]
+    fn test_find_es_plural_only_after_sibilants() {
+        let blocklist = Blocklist::new(["fat", "mustache"]);
+        assert_eq!(blocklist.find("The fates are kind"), None);
+        assert_eq!(blocklist.find("Two fats"), Some("fat".into()));
+        assert_eq!(blocklist.find("Big mustaches"), Some("mustache".into()));
+
+        let blocklist = Blocklist::new(["paunch"]);
+        assert_eq!(blocklist.find("Matching paunches"), Some("paunch".into()));
+    }
+
+    #[test]
+    fn test_parse_skips_comments_and_blanks() {
+        let blocklist = Blocklist::parse("# body\nchubby  # cheeks too\n\n  \nbald\n");
+        assert_eq!(blocklist.phrases.len(), 2);
+        assert_eq!(blocklist.find("so bald"), Some("bald".into()));
+    }
+}
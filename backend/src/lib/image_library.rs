@@ -1,133 +1,350 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::lib::tags::{format_tags, normalize_tags, parse_tags, Tag};
+
+/// URL prefix under which files in the images directory are served
+pub const IMAGE_URL_PREFIX: &str = "/images/";
+
+/// File extensions treated as library images
+pub const IMAGE_EXTENSIONS: [&str; 5] = ["jpg", "jpeg", "png", "gif", "webp"];
+
+/// Held across every read-modify-write of the library CSV in this process
+static LIBRARY_LOCK: Mutex<()> = Mutex::new(());
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImageEntry {
     pub image_url: String,
-    pub words: [String; 3],
+    pub tags: Vec<Tag>,
+}
+
+impl ImageEntry {
+    /// Entry tagged with one word per prompt slot: object, emotion, style
+    fn slots(image_url: &str, words: [&str; 3]) -> Self {
+        Self {
+            image_url: image_url.to_string(),
+            tags: Tag::slots(words),
+        }
+    }
+}
+
+/// One row of `image_library.csv`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "CsvRow", into = "CsvRow")]
+pub struct ImageLibraryRow {
+    pub image_url: String,
+    pub tags: Vec<Tag>,
+    /// SHA-256 of the image the tags were generated from; empty for hand-written rows
+    pub hash: String,
+    /// Hand-edited row that the generator must never overwrite
+    pub locked: bool,
+}
+
+impl ImageLibraryRow {
+    /// File name under the images directory, if the URL is well-formed
+    pub fn filename(&self) -> Option<&str> {
+        self.image_url.strip_prefix(IMAGE_URL_PREFIX)
+    }
+}
+
+impl From<ImageEntry> for ImageLibraryRow {
+    fn from(entry: ImageEntry) -> Self {
+        Self {
+            image_url: entry.image_url,
+            tags: entry.tags,
+            ..Default::default()
+        }
+    }
+}
+
+/// A row as stored: tags in one `tags` cell (see `lib::tags`). Files from before
+/// tags had `word1..word3` columns instead, read as object, emotion and style.
+#[derive(Serialize, Deserialize)]
+struct CsvRow {
+    image_url: String,
+    #[serde(default)]
+    tags: String,
+    #[serde(default, skip_serializing)]
+    word1: String,
+    #[serde(default, skip_serializing)]
+    word2: String,
+    #[serde(default, skip_serializing)]
+    word3: String,
+    #[serde(default)]
+    hash: String,
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    locked: bool,
+}
+
+impl TryFrom<CsvRow> for ImageLibraryRow {
+    type Error = String;
+
+    fn try_from(row: CsvRow) -> Result<Self, Self::Error> {
+        let words = [row.word1, row.word2, row.word3];
+        let tags = if !row.tags.trim().is_empty() {
+            parse_tags(&row.tags)?
+        } else if words.iter().any(|w| !w.trim().is_empty()) {
+            // Keep empty words so validation can point them out
+            Tag::slots(words)
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self {
+            image_url: row.image_url,
+            tags,
+            hash: row.hash,
+            locked: row.locked,
+        })
+    }
 }
 
+impl From<ImageLibraryRow> for CsvRow {
+    fn from(row: ImageLibraryRow) -> Self {
+        Self {
+            image_url: row.image_url,
+            tags: format_tags(&row.tags),
+            word1: String::new(),
+            word2: String::new(),
+            word3: String::new(),
+            hash: row.hash,
+            locked: row.locked,
+        }
+    }
+}
+
+/// Accept `true`/`false`, `1`/`0`, `yes`/`no` or an empty cell
+fn deserialize_flag<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    match value.trim().to_lowercase().as_str() {
+        "" | "false" | "0" | "no" => Ok(false),
+        "true" | "1" | "yes" => Ok(true),
+        other => Err(serde::de::Error::custom(format!("invalid locked flag '{}'", other))),
+    }
+}
+
+/// Where the image library is loaded from
+#[derive(Debug, Clone)]
+pub struct LibrarySource {
+    pub csv_path: PathBuf,
+    pub images_dir: PathBuf,
+    /// Use the built-in list when the CSV file does not exist
+    pub allow_builtin_fallback: bool,
+}
+
+impl LibrarySource {
+    /// Load and validate the library, falling back to the built-in list if allowed
+    pub fn load(&self) -> Result<Vec<ImageEntry>, String> {
+        if !self.csv_path.exists() {
+            if self.allow_builtin_fallback {
+                tracing::warn!(
+                    "Image library {} not found, using built-in library",
+                    self.csv_path.display()
+                );
+                return Ok(get_image_library());
+            }
+            return Err(format!(
+                "Image library {} does not exist",
+                self.csv_path.display()
+            ));
+        }
+
+        let file = std::fs::File::open(&self.csv_path)
+            .map_err(|e| format!("Failed to open {}: {}", self.csv_path.display(), e))?;
+
+        parse_image_library(file, &self.images_dir)
+            .map_err(|e| format!("Invalid image library {}:\n{}", self.csv_path.display(), e))
+    }
+
+    /// Rows to edit the library from: the CSV's, or while there is no CSV yet and
+    /// the built-in list is being served, its entries whose images exist.
+    pub fn rows(&self) -> Result<Vec<ImageLibraryRow>, String> {
+        if !self.csv_path.exists() && self.allow_builtin_fallback {
+            return Ok(get_image_library()
+                .into_iter()
+                .filter(|entry| resolve_image_path(&entry.image_url, &self.images_dir).is_ok())
+                .map(ImageLibraryRow::from)
+                .collect());
+        }
+
+        read_library_rows(&self.csv_path)
+    }
+}
+
+/// Parse library CSV rows and check every image exists under `images_dir`.
+/// All bad rows are reported together so they can be fixed in one pass.
+pub fn parse_image_library<R: Read>(reader: R, images_dir: &Path) -> Result<Vec<ImageEntry>, String> {
+    let mut rdr = csv::Reader::from_reader(reader);
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+
+    for (idx, result) in rdr.deserialize::<ImageLibraryRow>().enumerate() {
+        // Line 1 is the header
+        let line = idx + 2;
+
+        let row = match result {
+            Ok(row) => row,
+            Err(e) => {
+                errors.push(format!("  line {}: {}", line, e));
+                continue;
+            }
+        };
+
+        match validate_row(row, images_dir) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => errors.push(format!("  line {}: {}", line, e)),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+
+    if entries.is_empty() {
+        return Err("  no entries".into());
+    }
+
+    Ok(entries)
+}
+
+fn validate_row(row: ImageLibraryRow, images_dir: &Path) -> Result<ImageEntry, String> {
+    let tags = normalize_tags(row.tags).map_err(|e| format!("{} has {}", row.image_url, e))?;
+
+    resolve_image_path(&row.image_url, images_dir)?;
+
+    Ok(ImageEntry {
+        image_url: row.image_url,
+        tags,
+    })
+}
+
+/// Serialise edits to the library CSV. Hold the guard from reading the rows
+/// until they are written back so concurrent edits are not lost.
+pub fn lock_library() -> MutexGuard<'static, ()> {
+    LIBRARY_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Whether a file name has one of the `IMAGE_EXTENSIONS`
+pub fn is_image_file(filename: &str) -> bool {
+    Path::new(filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| IMAGE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+/// Read the rows of a library CSV without validating them; a missing file has no rows
+pub fn read_library_rows(csv_path: &Path) -> Result<Vec<ImageLibraryRow>, String> {
+    if !csv_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut rdr = csv::Reader::from_path(csv_path)
+        .map_err(|e| format!("Failed to open {}: {}", csv_path.display(), e))?;
+    rdr.deserialize()
+        .enumerate()
+        .map(|(idx, row)| {
+            // Line 1 is the header
+            row.map_err(|e| format!("{} line {}: {}", csv_path.display(), idx + 2, e))
+        })
+        .collect()
+}
+
+/// Replace the library CSV atomically: rows are written and synced to a temp file
+/// next to it, which is then renamed over the original. Readers see either the
+/// old library or the new one, never a partial file.
+pub fn write_library_rows(csv_path: &Path, rows: &[ImageLibraryRow]) -> Result<(), String> {
+    let file_name = csv_path
+        .file_name()
+        .ok_or_else(|| format!("{} is not a file path", csv_path.display()))?;
+    let mut tmp_name = file_name.to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = csv_path.with_file_name(tmp_name);
+
+    let result = (|| {
+        let file = std::fs::File::create(&tmp_path)
+            .map_err(|e| format!("Failed to create {}: {}", tmp_path.display(), e))?;
+        let mut wtr = csv::Writer::from_writer(file);
+        for row in rows {
+            wtr.serialize(row)
+                .map_err(|e| format!("Failed to write {}: {}", row.image_url, e))?;
+        }
+        let mut file = wtr
+            .into_inner()
+            .map_err(|e| format!("Failed to flush {}: {}", tmp_path.display(), e))?;
+        file.flush()
+            .and_then(|_| file.sync_all())
+            .map_err(|e| format!("Failed to sync {}: {}", tmp_path.display(), e))?;
+
+        std::fs::rename(&tmp_path, csv_path).map_err(|e| {
+            format!("Failed to replace {}: {}", csv_path.display(), e)
+        })
+    })();
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Hex SHA-256 of an image file, used to notice when an image changes
+pub fn image_hash(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Map an `/images/<file>` URL to a file under `images_dir`, checking it exists
+pub fn resolve_image_path(image_url: &str, images_dir: &Path) -> Result<PathBuf, String> {
+    let filename = image_url
+        .strip_prefix(IMAGE_URL_PREFIX)
+        .ok_or_else(|| format!("{} must start with {}", image_url, IMAGE_URL_PREFIX))?;
+
+    if filename.is_empty() || filename.contains('/') || filename.contains('\\') || filename == ".." {
+        return Err(format!("{} is not a plain file name", image_url));
+    }
+
+    let path = images_dir.join(filename);
+    if !path.is_file() {
+        return Err(format!("{} does not exist", path.display()));
+    }
+
+    Ok(path)
+}
+
+/// Built-in library, used only when no CSV is available
 pub fn get_image_library() -> Vec<ImageEntry> {
     vec![
-        ImageEntry {
-            image_url: "/images/biggest-eater-hamster.jpg".to_string(),
-            words: ["smug".to_string(), "hungry".to_string(), "comical".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/birthday-hamster.jpg".to_string(),
-            words: ["cake".to_string(), "confused".to_string(), "whimsical".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/bleh-hamster.jpg".to_string(),
-            words: ["smug".to_string(), "playful".to_string(), "cartoon".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/bonita-hamster.png".to_string(),
-            words: ["cute".to_string(), "relaxed".to_string(), "whimsical".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/burger-hamster.jpg".to_string(),
-            words: ["hungry".to_string(), "confused".to_string(), "spooky".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/chad-hamster.jpg".to_string(),
-            words: ["smug".to_string(), "confused".to_string(), "minimalist".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/crying-hamster.jpg".to_string(),
-            words: ["confused".to_string(), "frustrated".to_string(), "comical".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/detective-hamster.jpg".to_string(),
-            words: ["detective".to_string(), "contemplative".to_string(), "thinking".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/devil-hamster.jpg".to_string(),
-            words: ["smug".to_string(), "evil".to_string(), "comical".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/emo-hamster.jpg".to_string(),
-            words: ["emotional".to_string(), "anxious".to_string(), "sketchy".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/femboy-lover-hamster.jpg".to_string(),
-            words: ["smug".to_string(), "confident".to_string(), "playful".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/flower-hamster.jpg".to_string(),
-            words: ["floral".to_string(), "serene".to_string(), "whimsical".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/free-hamster.jpg".to_string(),
-            words: ["freedom".to_string(), "curious".to_string(), "playful".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/french-hamster.jpg".to_string(),
-            words: ["baker".to_string(), "smug".to_string(), "elegant".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/furious-hamster.jpg".to_string(),
-            words: ["smug".to_string(), "angry".to_string(), "comical".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/homeless-hamster.jpg".to_string(),
-            words: ["homeless".to_string(), "melancholic".to_string(), "surreal".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/intimidating-hamster.jpg".to_string(),
-            words: ["smug".to_string(), "scary".to_string(), "skeptical".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/lolipop-hamster.jpg".to_string(),
-            words: ["candy".to_string(), "playful".to_string(), "whimsical".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/make-up-hamster.jpg".to_string(),
-            words: ["make up".to_string(), "pretty".to_string(), "curious".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/nerd-hamster.jpg".to_string(),
-            words: ["smug".to_string(), "confused".to_string(), "detective".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/pig-hamster.jpg".to_string(),
-            words: ["pig".to_string(), "costume".to_string(), "comical".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/poor-hamster.jpg".to_string(),
-            words: ["poor".to_string(), "contemplative".to_string(), "no money".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/rag-hamster.jpg".to_string(),
-            words: ["smug".to_string(), "confused".to_string(), "comical".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/schemy-hamster.jpg".to_string(),
-            words: ["smug".to_string(), "confused".to_string(), "detective".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/sick-hamster.jpg".to_string(),
-            words: ["sick".to_string(), "dying".to_string(), "whimsical".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/thirsty-hamster.jpg".to_string(),
-            words: ["confused".to_string(), "thirsty".to_string(), "whimsical".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/thumbs-down-hamster.jpg".to_string(),
-            words: ["thumbs down".to_string(), "skeptical".to_string(), "disapprove".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/thumbs-up-hamster.jpg".to_string(),
-            words: ["thumbs up".to_string(), "smile".to_string(), "approve".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/watermelon-hamster.jpg".to_string(),
-            words: ["confused".to_string(), "whimsical".to_string(), "watermelon".to_string()],
-        },
-        ImageEntry {
-            image_url: "/images/wizard-hamster.jpg".to_string(),
-            words: ["wizard".to_string(), "neutral".to_string(), "whimsical".to_string()],
-        },
+        ImageEntry::slots("/images/biggest-eater-hamster.jpg", ["smug", "hungry", "comical"]),
+        ImageEntry::slots("/images/birthday-hamster.jpg", ["cake", "confused", "whimsical"]),
+        ImageEntry::slots("/images/bleh-hamster.jpg", ["smug", "playful", "cartoon"]),
+        ImageEntry::slots("/images/bonita-hamster.png", ["cute", "relaxed", "whimsical"]),
+        ImageEntry::slots("/images/burger-hamster.jpg", ["hungry", "confused", "spooky"]),
+        ImageEntry::slots("/images/chad-hamster.jpg", ["smug", "confused", "minimalist"]),
+        ImageEntry::slots("/images/crying-hamster.jpg", ["confused", "frustrated", "comical"]),
+        ImageEntry::slots("/images/detective-hamster.jpg", ["detective", "contemplative", "thinking"]),
+        ImageEntry::slots("/images/devil-hamster.jpg", ["smug", "evil", "comical"]),
+        ImageEntry::slots("/images/emo-hamster.jpg", ["emotional", "anxious", "sketchy"]),
+        ImageEntry::slots("/images/femboy-lover-hamster.jpg", ["smug", "confident", "playful"]),
+        ImageEntry::slots("/images/flower-hamster.jpg", ["floral", "serene", "whimsical"]),
+        ImageEntry::slots("/images/free-hamster.jpg", ["freedom", "curious", "playful"]),
+        ImageEntry::slots("/images/french-hamster.jpg", ["baker", "smug", "elegant"]),
+        ImageEntry::slots("/images/furious-hamster.jpg", ["smug", "angry", "comical"]),
+        ImageEntry::slots("/images/homeless-hamster.jpg", ["homeless", "melancholic", "surreal"]),
+        ImageEntry::slots("/images/intimidating-hamster.jpg", ["smug", "scary", "skeptical"]),
+        ImageEntry::slots("/images/lolipop-hamster.jpg", ["candy", "playful", "whimsical"]),
+        ImageEntry::slots("/images/make-up-hamster.jpg", ["make up", "pretty", "curious"]),
+        ImageEntry::slots("/images/nerd-hamster.jpg", ["smug", "confused", "detective"]),
+        ImageEntry::slots("/images/pig-hamster.jpg", ["pig", "costume", "comical"]),
+        ImageEntry::slots("/images/poor-hamster.jpg", ["poor", "contemplative", "no money"]),
+        ImageEntry::slots("/images/rag-hamster.jpg", ["smug", "confused", "comical"]),
+        ImageEntry::slots("/images/schemy-hamster.jpg", ["smug", "confused", "detective"]),
+        ImageEntry::slots("/images/sick-hamster.jpg", ["sick", "dying", "whimsical"]),
+        ImageEntry::slots("/images/thirsty-hamster.jpg", ["confused", "thirsty", "whimsical"]),
+        ImageEntry::slots("/images/thumbs-down-hamster.jpg", ["thumbs down", "skeptical", "disapprove"]),
+        ImageEntry::slots("/images/thumbs-up-hamster.jpg", ["thumbs up", "smile", "approve"]),
+        ImageEntry::slots("/images/watermelon-hamster.jpg", ["confused", "whimsical", "watermelon"]),
+        ImageEntry::slots("/images/wizard-hamster.jpg", ["wizard", "neutral", "whimsical"]),
     ]
 }
 
@@ -162,4 +379,92 @@ pub fn get_word_library() -> Vec<String> {
     .iter()
     .map(|s| s.to_string())
     .collect()
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lib::tags::TagCategory;
+
+    #[test]
+    fn test_parse_valid_library() {
+        let csv = "image_url,word1,word2,word3\n/images/nerd-hamster.jpg,Book, glasses ,smart\n";
+        let entries = parse_image_library(csv.as_bytes(), Path::new("images")).unwrap();
+        assert_eq!(entries.len(), 1);
+        let words: Vec<&str> = entries[0].tags.iter().map(|t| t.word.as_str()).collect();
+        assert_eq!(words, ["book", "glasses", "smart"]);
+        assert_eq!(entries[0].tags[0].category, Some(TagCategory::Object));
+    }
+
+    #[test]
+    fn test_parse_tags_column() {
+        let csv = "image_url,tags\n/images/nerd-hamster.jpg,object:Book*2; smart; glasses*0.5\n";
+        let entries = parse_image_library(csv.as_bytes(), Path::new("images")).unwrap();
+        let tags = &entries[0].tags;
+        assert_eq!(tags.len(), 3);
+        assert_eq!(tags[0].word, "book");
+        assert_eq!(tags[0].weight, 2.0);
+        assert_eq!(tags[1].category, None);
+
+        let csv = "image_url,tags\n/images/nerd-hamster.jpg,book*0\n";
+        let err = parse_image_library(csv.as_bytes(), Path::new("images")).unwrap_err();
+        assert!(err.contains("not positive"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_reports_every_bad_row() {
+        let csv = "image_url,word1,word2,word3\n\
+            /images/missing.jpg,a,b,c\n\
+            /images/nerd-hamster.jpg,a,,c\n\
+            /images/../Cargo.toml,a,b,c\n\
+            /images/nerd-hamster.jpg,a,b\n";
+        let err = parse_image_library(csv.as_bytes(), Path::new("images")).unwrap_err();
+        for line in ["line 2", "line 3", "line 4", "line 5"] {
+            assert!(err.contains(line), "missing {} in {}", line, err);
+        }
+    }
+
+    #[test]
+    fn test_rows_round_trip_with_hash_and_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let csv_path = dir.path().join("library.csv");
+
+        // Older files have no hash or locked columns
+        std::fs::write(&csv_path, "image_url,word1,word2,word3\n/images/a.png,a,b,c\n").unwrap();
+        let mut rows = read_library_rows(&csv_path).unwrap();
+        assert_eq!(rows[0].hash, "");
+        assert!(!rows[0].locked);
+        assert_eq!(rows[0].filename(), Some("a.png"));
+
+        rows[0].locked = true;
+        rows[0].hash = image_hash(b"png");
+        write_library_rows(&csv_path, &rows).unwrap();
+
+        assert_eq!(read_library_rows(&csv_path).unwrap(), rows);
+        let written = std::fs::read_to_string(&csv_path).unwrap();
+        assert!(written.starts_with("image_url,tags,hash,locked\n"), "{}", written);
+        assert!(!dir.path().join("library.csv.tmp").exists());
+        assert!(read_library_rows(&dir.path().join("missing.csv")).unwrap().is_empty());
+        assert!(image_hash(b"").starts_with("e3b0c442"));
+    }
+
+    #[test]
+    fn test_locked_flag_spellings() {
+        let csv = "image_url,word1,word2,word3,hash,locked\n\
+            /images/a.png,a,b,c,,yes\n\
+            /images/b.png,a,b,c,,\n\
+            /images/c.png,a,b,c,,maybe\n";
+        let mut rdr = csv::Reader::from_reader(csv.as_bytes());
+        let rows: Vec<Result<ImageLibraryRow, _>> = rdr.deserialize().collect();
+        assert!(rows[0].as_ref().unwrap().locked);
+        assert!(!rows[1].as_ref().unwrap().locked);
+        assert!(rows[2].is_err());
+    }
+
+    #[test]
+    fn test_bundled_csv_matches_images() {
+        let file = std::fs::File::open("image_library.csv").unwrap();
+        let entries = parse_image_library(file, Path::new("images")).unwrap();
+        assert!(!entries.is_empty());
+    }
+}
@@ -1 +1,4 @@
+pub mod blocklist;
 pub mod image_library;
+pub mod poem_form;
+pub mod tags;
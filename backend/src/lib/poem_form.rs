@@ -0,0 +1,330 @@
+//! Poem forms, tone, length and language, and the checks a generated poem is
+//! held to. Line counts are exact; syllables are counted with an English
+//! spelling heuristic, so they are only checked for English poems and allow
+//! one syllable of slack per line.
+
+use serde::{Deserialize, Serialize};
+
+/// Longest poem `max_lines` may ask for
+pub const MAX_POEM_LINES: usize = 40;
+
+/// Longest `tone` or `language` accepted, in characters
+const MAX_OPTION_CHARS: usize = 40;
+
+/// How far a line's counted syllables may be from the form's
+const SYLLABLE_TOLERANCE: usize = 1;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PoemStyle {
+    #[default]
+    FreeVerse,
+    Haiku,
+    Limerick,
+    Sonnet,
+}
+
+impl PoemStyle {
+    /// Lines the form requires, if it fixes them
+    pub fn line_count(self) -> Option<usize> {
+        match self {
+            PoemStyle::FreeVerse => None,
+            PoemStyle::Haiku => Some(3),
+            PoemStyle::Limerick => Some(5),
+            PoemStyle::Sonnet => Some(14),
+        }
+    }
+
+    /// Syllables per line the form requires, if it fixes them
+    fn syllables(self) -> Option<&'static [usize]> {
+        match self {
+            PoemStyle::Haiku => Some(&[5, 7, 5]),
+            _ => None,
+        }
+    }
+
+    fn instructions(self) -> Option<&'static str> {
+        match self {
+            PoemStyle::FreeVerse => None,
+            PoemStyle::Haiku => {
+                Some("Write it as a haiku: exactly three lines of 5, 7 and 5 syllables.")
+            }
+            PoemStyle::Limerick => Some(
+                "Write it as a limerick: exactly five lines rhyming AABBA, \
+                with lines 1, 2 and 5 longer than lines 3 and 4.",
+            ),
+            PoemStyle::Sonnet => Some(
+                "Write it as a sonnet: exactly fourteen lines of iambic pentameter \
+                rhyming ABAB CDCD EFEF GG.",
+            ),
+        }
+    }
+}
+
+/// How a poem should be written; every field is optional
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PoemOptions {
+    #[serde(default)]
+    pub style: PoemStyle,
+    /// Free-form mood, e.g. "playful" or "melancholic"
+    pub tone: Option<String>,
+    pub max_lines: Option<usize>,
+    /// Language to write in, e.g. "French"; defaults to English
+    pub language: Option<String>,
+}
+
+/// Whether a poem met one requested constraint
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConstraintCheck {
+    /// `line_count`, `max_lines` or `syllables`
+    pub constraint: &'static str,
+    pub satisfied: bool,
+    /// What was expected and what the poem has
+    pub detail: String,
+}
+
+impl PoemOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(max_lines) = self.max_lines {
+            if !(1..=MAX_POEM_LINES).contains(&max_lines) {
+                return Err(format!(
+                    "max_lines must be between 1 and {}",
+                    MAX_POEM_LINES
+                ));
+            }
+            if let Some(lines) = self.style.line_count() {
+                if max_lines < lines {
+                    return Err(format!(
+                        "max_lines must be at least {} for this style",
+                        lines
+                    ));
+                }
+            }
+        }
+
+        for (name, value) in [("tone", &self.tone), ("language", &self.language)] {
+            if let Some(value) = value {
+                if value.trim().is_empty() || value.chars().count() > MAX_OPTION_CHARS {
+                    return Err(format!(
+                        "{} must be 1 to {} characters",
+                        name, MAX_OPTION_CHARS
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Prompt sentences asking for the style, tone, length and language
+    pub fn instructions(&self) -> String {
+        let mut sentences: Vec<String> = Vec::new();
+        if let Some(form) = self.style.instructions() {
+            sentences.push(form.into());
+        }
+        if let Some(tone) = &self.tone {
+            sentences.push(format!("Make the tone {}.", tone.trim()));
+        }
+        if let Some(max_lines) = self.max_lines {
+            sentences.push(format!("Use at most {} lines.", max_lines));
+        }
+        if let Some(language) = &self.language {
+            sentences.push(format!("Write it in {}.", language.trim()));
+        }
+        sentences.join(" ")
+    }
+
+    /// Check a poem against the constraints that can be measured. Empty when
+    /// there is nothing to check, e.g. free verse without `max_lines`.
+    pub fn check(&self, poem: &str) -> Vec<ConstraintCheck> {
+        let lines: Vec<&str> = poem
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        let mut checks = Vec::new();
+
+        if let Some(expected) = self.style.line_count() {
+            checks.push(ConstraintCheck {
+                constraint: "line_count",
+                satisfied: lines.len() == expected,
+                detail: format!("expected {} lines, got {}", expected, lines.len()),
+            });
+        }
+
+        if let Some(max_lines) = self.max_lines {
+            checks.push(ConstraintCheck {
+                constraint: "max_lines",
+                satisfied: lines.len() <= max_lines,
+                detail: format!("expected at most {} lines, got {}", max_lines, lines.len()),
+            });
+        }
+
+        if let Some(expected) = self.style.syllables().filter(|_| self.is_english()) {
+            let counted: Vec<usize> = lines
+                .iter()
+                .map(|line| count_line_syllables(line))
+                .collect();
+            let satisfied = counted.len() == expected.len()
+                && counted
+                    .iter()
+                    .zip(expected)
+                    .all(|(got, want)| got.abs_diff(*want) <= SYLLABLE_TOLERANCE);
+            checks.push(ConstraintCheck {
+                constraint: "syllables",
+                satisfied,
+                detail: format!(
+                    "expected {}, counted {}",
+                    join_counts(expected),
+                    join_counts(&counted)
+                ),
+            });
+        }
+
+        checks
+    }
+
+    fn is_english(&self) -> bool {
+        self.language.as_deref().is_none_or(|language| {
+            let language = language.trim().to_lowercase();
+            language == "en" || language.starts_with("en-") || language.starts_with("english")
+        })
+    }
+}
+
+fn join_counts(counts: &[usize]) -> String {
+    counts
+        .iter()
+        .map(usize::to_string)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn count_line_syllables(line: &str) -> usize {
+    line.split(|c: char| c.is_whitespace() || c == '-')
+        .map(count_syllables)
+        .sum()
+}
+
+/// Approximate syllables in an English word: groups of vowels, less a silent
+/// final `e`
+fn count_syllables(word: &str) -> usize {
+    let word: String = word
+        .chars()
+        .filter(char::is_ascii_alphabetic)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    if word.is_empty() {
+        return 0;
+    }
+
+    let mut count = 0;
+    let mut previous_vowel = false;
+    for c in word.chars() {
+        let vowel = "aeiouy".contains(c);
+        if vowel && !previous_vowel {
+            count += 1;
+        }
+        previous_vowel = vowel;
+    }
+    if word.ends_with('e') && !word.ends_with("le") && count > 1 {
+        count -= 1;
+    }
+
+    count.max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn haiku() -> PoemOptions {
+        PoemOptions {
+            style: PoemStyle::Haiku,
+            ..PoemOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_count_syllables() {
+        assert_eq!(count_syllables("hamster"), 2);
+        assert_eq!(count_syllables("the"), 1);
+        assert_eq!(count_syllables("make"), 1);
+        assert_eq!(count_syllables("little"), 2);
+        assert_eq!(count_syllables("Cheeks,"), 1);
+        assert_eq!(count_syllables("..."), 0);
+    }
+
+    #[test]
+    fn test_haiku_checks() {
+        let poem = "Tiny paws scurry\nthrough the sawdust in the night\nthe wheel never stops\n";
+        let checks = haiku().check(poem);
+        assert_eq!(checks.len(), 2);
+        assert!(checks.iter().all(|c| c.satisfied), "{:?}", checks);
+
+        let checks = haiku().check("A hamster\n\nsleeps");
+        assert_eq!(checks[0].constraint, "line_count");
+        assert!(!checks[0].satisfied);
+        assert_eq!(checks[0].detail, "expected 3 lines, got 2");
+        assert!(!checks[1].satisfied);
+    }
+
+    #[test]
+    fn test_syllables_are_only_checked_in_english() {
+        let options = PoemOptions {
+            language: Some("French".into()),
+            ..haiku()
+        };
+        let checks = options.check("un\ndeux\ntrois");
+        assert_eq!(checks.len(), 1);
+        assert!(checks[0].satisfied);
+    }
+
+    #[test]
+    fn test_free_verse_checks_only_max_lines() {
+        assert!(PoemOptions::default().check("one\ntwo").is_empty());
+
+        let options = PoemOptions {
+            max_lines: Some(1),
+            ..PoemOptions::default()
+        };
+        let checks = options.check("one\ntwo");
+        assert_eq!(checks[0].constraint, "max_lines");
+        assert!(!checks[0].satisfied);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(PoemOptions::default().validate().is_ok());
+        let sonnet = PoemOptions {
+            style: PoemStyle::Sonnet,
+            max_lines: Some(10),
+            ..PoemOptions::default()
+        };
+        assert!(sonnet.validate().is_err());
+        let long = PoemOptions {
+            max_lines: Some(MAX_POEM_LINES + 1),
+            ..PoemOptions::default()
+        };
+        assert!(long.validate().is_err());
+        let blank_tone = PoemOptions {
+            tone: Some(" ".into()),
+            ..PoemOptions::default()
+        };
+        assert!(blank_tone.validate().is_err());
+    }
+
+    #[test]
+    fn test_instructions() {
+        let options = PoemOptions {
+            tone: Some("playful".into()),
+            language: Some("French".into()),
+            ..haiku()
+        };
+        let instructions = options.instructions();
+        assert!(instructions.starts_with("Write it as a haiku"));
+        assert!(instructions.contains("Make the tone playful."));
+        assert!(instructions.ends_with("Write it in French."));
+        assert_eq!(PoemOptions::default().instructions(), "");
+    }
+}
@@ -0,0 +1,250 @@
+//! Weighted, optionally categorised tags describing a library image.
+//!
+//! In the library CSV a tag list is one cell of `;`-separated tags, each written
+//! `[category:]word[*weight]`, e.g. `object:cake*1.5; emotion:happy; party`.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Most tags one entry may carry
+pub const MAX_TAGS: usize = 16;
+
+/// Separators of the CSV tag syntax, which no word may contain
+pub const RESERVED_CHARS: [char; 3] = [';', ':', '*'];
+
+/// What a tag describes; these are the three slots the extraction prompt asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TagCategory {
+    /// The main visible object, item or profession
+    Object,
+    /// The character's emotion or state
+    Emotion,
+    /// The overall style or theme
+    Style,
+}
+
+impl TagCategory {
+    /// The prompt's slots, in the order it asks for them
+    pub const SLOTS: [TagCategory; 3] = [
+        TagCategory::Object,
+        TagCategory::Emotion,
+        TagCategory::Style,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TagCategory::Object => "object",
+            TagCategory::Emotion => "emotion",
+            TagCategory::Style => "style",
+        }
+    }
+}
+
+impl FromStr for TagCategory {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "object" => Ok(TagCategory::Object),
+            "emotion" => Ok(TagCategory::Emotion),
+            "style" => Ok(TagCategory::Style),
+            other => Err(format!("unknown tag category '{}'", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tag {
+    pub word: String,
+    /// Relative importance when matching (default 1)
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<TagCategory>,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+impl Tag {
+    /// An uncategorised tag of weight 1
+    pub fn new(word: impl Into<String>) -> Self {
+        Self {
+            word: word.into(),
+            weight: default_weight(),
+            category: None,
+        }
+    }
+
+    pub fn with_category(mut self, category: TagCategory) -> Self {
+        self.category = Some(category);
+        self
+    }
+
+    pub fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    /// Tags for the prompt's slots from words in its order: object, emotion,
+    /// style. Words beyond the three slots are dropped.
+    pub fn slots<S: Into<String>>(words: impl IntoIterator<Item = S>) -> Vec<Tag> {
+        words
+            .into_iter()
+            .zip(TagCategory::SLOTS)
+            .map(|(word, category)| Tag::new(word).with_category(category))
+            .collect()
+    }
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(category) = self.category {
+            write!(f, "{}:", category.as_str())?;
+        }
+        f.write_str(&self.word)?;
+        if self.weight != 1.0 {
+            write!(f, "*{}", self.weight)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Tag {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (rest, weight) = match s.rsplit_once('*') {
+            Some((rest, weight)) => {
+                let weight = weight
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("invalid weight in tag '{}'", s.trim()))?;
+                (rest, weight)
+            }
+            None => (s, default_weight()),
+        };
+        let (category, word) = match rest.split_once(':') {
+            Some((category, word)) => (Some(category.parse()?), word),
+            None => (None, rest),
+        };
+
+        Ok(Tag {
+            word: word.trim().to_string(),
+            weight,
+            category,
+        })
+    }
+}
+
+/// Parse a `;`-separated tag list as written in the library CSV
+pub fn parse_tags(s: &str) -> Result<Vec<Tag>, String> {
+    s.split(';')
+        .filter(|tag| !tag.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// Write tags the way `parse_tags` reads them
+pub fn format_tags(tags: &[Tag]) -> String {
+    tags.iter()
+        .map(Tag::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Trim and lowercase the words, rejecting empty lists, empty words, words
+/// with a `RESERVED_CHARS` separator and weights that are not positive
+pub fn normalize_tags(tags: Vec<Tag>) -> Result<Vec<Tag>, String> {
+    if tags.is_empty() {
+        return Err("no tags".into());
+    }
+    if tags.len() > MAX_TAGS {
+        return Err(format!("more than {} tags", MAX_TAGS));
+    }
+
+    tags.into_iter()
+        .map(|mut tag| {
+            tag.word = tag.word.trim().to_lowercase();
+            if tag.word.is_empty() {
+                return Err("an empty tag".to_string());
+            }
+            if tag.word.contains(RESERVED_CHARS) {
+                return Err(format!("a tag with ';', ':' or '*' in it: '{}'", tag.word));
+            }
+            if !(tag.weight.is_finite() && tag.weight > 0.0) {
+                return Err(format!("a weight that is not positive for '{}'", tag.word));
+            }
+            Ok(tag)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format_round_trip() {
+        let tags = parse_tags("object:cake*1.5; Emotion: happy ;party;").unwrap();
+        assert_eq!(
+            tags,
+            [
+                Tag::new("cake")
+                    .with_category(TagCategory::Object)
+                    .with_weight(1.5),
+                Tag::new("happy").with_category(TagCategory::Emotion),
+                Tag::new("party"),
+            ]
+        );
+        assert_eq!(format_tags(&tags), "object:cake*1.5; emotion:happy; party");
+        assert_eq!(parse_tags(&format_tags(&tags)).unwrap(), tags);
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_tags() {
+        assert!(parse_tags("colour:red").is_err());
+        assert!(parse_tags("red*heavy").is_err());
+        assert!(parse_tags("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_normalize_tags() {
+        let tags = normalize_tags(vec![Tag::new(" Cake ")]).unwrap();
+        assert_eq!(tags[0].word, "cake");
+
+        assert!(normalize_tags(Vec::new()).is_err());
+        assert!(normalize_tags(vec![Tag::new(" ")]).is_err());
+        assert!(normalize_tags(vec![Tag::new("cake").with_weight(0.0)]).is_err());
+        assert!(normalize_tags(vec![Tag::new("cake").with_weight(f32::NAN)]).is_err());
+        assert!(normalize_tags(vec![Tag::new("cake"); MAX_TAGS + 1]).is_err());
+    }
+
+    #[test]
+    fn test_normalize_tags_rejects_separators() {
+        for word in ["a;b", "x:y", "a*b"] {
+            // Written as-is, the word would not read back as one tag
+            let tags = vec![Tag::new(word)];
+            assert_ne!(
+                parse_tags(&format_tags(&tags)),
+                Ok(tags.clone()),
+                "{}",
+                word
+            );
+            assert!(normalize_tags(tags).is_err(), "{}", word);
+        }
+
+        let tags = normalize_tags(vec![Tag::new("top-hat").with_weight(1.5)]).unwrap();
+        assert_eq!(parse_tags(&format_tags(&tags)).unwrap(), tags);
+    }
+
+    #[test]
+    fn test_slots_follow_prompt_order() {
+        let tags = Tag::slots(["cake", "happy", "cute", "extra"]);
+        assert_eq!(tags.len(), 3);
+        assert_eq!(tags[2].category, Some(TagCategory::Style));
+    }
+}
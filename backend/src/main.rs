@@ -1,18 +1,43 @@
+mod auth;
+mod config;
+mod error;
 mod handlers;
 mod lib;
 mod models;
+mod print;
+mod routes;
 mod service;
+mod state;
 
-use axum::{
-    routing::{get, post},
-    Router,
-};
-use tower_http::cors::{Any, CorsLayer};
-use tower_http::trace::TraceLayer;
-use tower_http::services::ServeDir;
+#[cfg(test)]
+mod tests;
+
+use clap::Parser;
+use config::{Cli, Config};
+use state::AppState;
 
 #[tokio::main]
 async fn main() {
+    let cli = Cli::parse();
+    let config = match Config::load(&cli) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if cli.print_config {
+        match config.to_toml() {
+            Ok(toml) => print!("{}", toml),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     tracing_subscriber::fmt()
         .with_env_filter(
             tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()),
@@ -21,42 +46,63 @@ async fn main() {
 
     // Initialize local embedding model and pre-compute library embeddings
     tracing::info!("Initializing local embeddings...");
-    service::init_embeddings();
+    let library_source = config.library.source();
+    if let Err(e) = service::init_embeddings(&library_source) {
+        tracing::error!("Failed to load image library: {}", e);
+        std::process::exit(1);
+    }
+
+    // The CLIP model is large, so photo matching is opt-in
+    if config.matching.photo_embeddings {
+        if let Err(e) = service::init_photo_embeddings(&config.library.images_dir) {
+            tracing::error!("Failed to load image embeddings: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    // Optionally poll the library on disk and reload when it changes
+    if config.library.watch_secs > 0 {
+        let secs = config.library.watch_secs;
+        tracing::info!("Watching image library for changes every {}s", secs);
+        service::spawn_library_watcher(library_source.clone(), std::time::Duration::from_secs(secs));
+    }
     tracing::info!("Local embeddings ready!");
 
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
+    let state = match AppState::new(config.clone()) {
+        Ok(state) => state,
+        Err(e) => {
+            tracing::error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    tracing::info!(
+        "Using {} backend with model {}",
+        state.generator.backend_name(),
+        config.llm.model
+    );
 
-    let app = Router::new()
-        .route("/", get(|| async { "Hack and Roll Snap API" }))
-        .route("/health", get(handlers::health::check))
-        // Poem routes
-        .route("/poem/text", post(handlers::poem::generate_from_text))
-        .route("/poem/image", post(handlers::poem::generate_from_image))
-        // Roast routes
-        .route("/roast/image", post(handlers::roast::generate_from_image))
-        // Embedding routes
-        .route("/embed", post(handlers::embedding::embed_text))
-        .route("/embed/batch", post(handlers::embedding::embed_batch))
-        .route(
-            "/embed/search",
-            post(handlers::embedding::similarity_search),
-        )
-        // Image matching route
-        .route("/image/match", post(handlers::image_match::match_image))
-        // Image library generator
-        .route(
-            "/admin/generate-library",
-            post(handlers::image_library_generator::generate_library),
-        )
-        // Serve static images
-        .nest_service("/images", ServeDir::new("images"))
-        .layer(cors)
-        .layer(TraceLayer::new_for_http());
+    // Optionally poll the prompt templates and reload when they change
+    if config.prompts.watch_secs > 0 {
+        let secs = config.prompts.watch_secs;
+        tracing::info!("Watching prompts in {} every {}s", config.prompts.dir.display(), secs);
+        service::spawn_prompt_watcher(
+            state.generator.prompts().clone(),
+            std::time::Duration::from_secs(secs),
+        );
+    }
+
+    if !config.admin.enabled {
+        tracing::info!("Admin routes are disabled");
+    } else if !config.admin.has_credentials() {
+        tracing::warn!(
+            "No admin credentials configured; /admin/* will refuse every request \
+             (set ADMIN_TOKEN or ADMIN_USERNAME and ADMIN_PASSWORD)"
+        );
+    }
+
+    let app = routes::router(state);
 
-    let addr = "0.0.0.0:8000";
+    let addr = config.server.bind.as_str();
     tracing::info!("Server running on http://{}", addr);
 
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
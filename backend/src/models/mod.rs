@@ -1,8 +1,10 @@
 mod ollama;
+mod openai;
 mod request;
 mod response;
 
 pub use ollama::*;
+pub use openai::*;
 pub use request::*;
 pub use response::*;
 pub use request::ImageMatchRequest;
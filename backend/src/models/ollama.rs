@@ -6,6 +6,9 @@ pub struct OllamaGenerateRequest {
     pub prompt: String,
     pub stream: bool,
     pub options: OllamaOptions,
+    /// JSON schema for structured output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Serialize)]
@@ -14,6 +17,9 @@ pub struct OllamaChatRequest {
     pub messages: Vec<OllamaChatMessage>,
     pub stream: bool,
     pub options: OllamaOptions,
+    /// JSON schema for structured output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Serialize)]
@@ -44,6 +50,27 @@ pub struct OllamaChatMessageResponse {
     pub content: String,
 }
 
+// ============================================================================
+// Streaming (NDJSON, one object per line)
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct OllamaGenerateStreamChunk {
+    #[serde(default)]
+    pub response: String,
+    #[serde(default)]
+    pub done: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OllamaChatStreamChunk {
+    pub message: Option<OllamaChatMessageResponse>,
+    #[serde(default)]
+    pub done: bool,
+    pub error: Option<String>,
+}
+
 // ============================================================================
 // Embeddings
 // ============================================================================
@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+// ============================================================================
+// Chat completions (`/v1/chat/completions`)
+// ============================================================================
+
+#[derive(Debug, Serialize)]
+pub struct OpenAiChatRequest {
+    pub model: String,
+    pub messages: Vec<OpenAiChatMessage>,
+    pub temperature: f32,
+    pub stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<OpenAiResponseFormat>,
+}
+
+/// Structured output: the reply must match `json_schema`
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OpenAiResponseFormat {
+    JsonSchema { json_schema: OpenAiJsonSchema },
+}
+
+#[derive(Debug, Serialize)]
+pub struct OpenAiJsonSchema {
+    pub name: String,
+    pub schema: serde_json::Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OpenAiChatMessage {
+    pub role: String,
+    pub content: OpenAiContent,
+}
+
+/// Plain text, or a list of parts when images are attached
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum OpenAiContent {
+    Text(String),
+    Parts(Vec<OpenAiContentPart>),
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OpenAiContentPart {
+    Text { text: String },
+    ImageUrl { image_url: OpenAiImageUrl },
+}
+
+#[derive(Debug, Serialize)]
+pub struct OpenAiImageUrl {
+    /// `data:<mime>;base64,<data>` URL
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenAiChatResponse {
+    pub choices: Vec<OpenAiChoice>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenAiChoice {
+    pub message: OpenAiResponseMessage,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenAiResponseMessage {
+    pub content: Option<String>,
+}
+
+/// One `data:` event of a streaming chat completion
+#[derive(Debug, Deserialize)]
+pub struct OpenAiChatStreamChunk {
+    #[serde(default)]
+    pub choices: Vec<OpenAiStreamChoice>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenAiStreamChoice {
+    pub delta: OpenAiDelta,
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenAiDelta {
+    pub content: Option<String>,
+}
+
+// ============================================================================
+// Embeddings (`/v1/embeddings`)
+// ============================================================================
+
+#[derive(Debug, Serialize)]
+pub struct OpenAiEmbeddingRequest {
+    pub model: String,
+    pub input: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenAiEmbeddingResponse {
+    pub data: Vec<OpenAiEmbeddingData>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenAiEmbeddingData {
+    pub index: usize,
+    pub embedding: Vec<f32>,
+}
@@ -1,8 +1,17 @@
 use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::config::{MatchMode, RoastIntensity};
+use crate::lib::poem_form::PoemOptions;
+use crate::lib::tags::TagCategory;
+use crate::service::sessions::SessionKind;
 
 #[derive(Debug, Deserialize)]
 pub struct TextPoemRequest {
     pub prompt: String,
+    /// `style`, `tone`, `max_lines` and `language`
+    #[serde(flatten)]
+    pub options: PoemOptions,
 }
 
 #[derive(Debug, Deserialize)]
@@ -14,10 +23,94 @@ pub struct ImagePoemRequest {
 #[derive(Debug, Deserialize)]
 pub struct ImageRoastRequest {
     pub image_base64: String,
+    /// `wholesome`, `spicy` or `savage`; defaults to `[roast] intensity`
+    pub intensity: Option<RoastIntensity>,
+    /// Defaults to `[roast] family_friendly`
+    pub family_friendly: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSessionRequest {
+    /// `chat` (default), `poem` or `roast`
+    #[serde(default)]
+    pub kind: SessionKind,
+    /// Photo to open with; required for roast sessions
+    pub image_base64: Option<String>,
+    /// First message; for a poem session without a photo, the theme
+    pub message: Option<String>,
+    /// Roast sessions only; defaults to `[roast] intensity`
+    pub intensity: Option<RoastIntensity>,
+    /// Roast sessions only; defaults to `[roast] family_friendly`
+    pub family_friendly: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SessionMessageRequest {
+    pub message: String,
+    pub image_base64: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ImageMatchRequest {
     /// Keywords/moods to match against the image library
     pub words: Vec<String>,
-}
\ No newline at end of file
+    /// Number of ranked candidates to return (default 1)
+    pub top_k: Option<usize>,
+    /// Drop candidates scoring below this cosine similarity
+    pub min_score: Option<f32>,
+    /// Defaults to `[matching] mode`
+    pub mode: Option<MatchMode>,
+    /// Per-slot weights for `slots` mode, on top of `[matching] slot_weights`
+    #[serde(default)]
+    pub slot_weights: HashMap<TagCategory, f32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PhotoMatchRequest {
+    /// The captured photo
+    pub image_base64: String,
+    /// Words describing the photo, fused with the photo score when given
+    #[serde(default)]
+    pub words: Vec<String>,
+    /// Without `words`, have the vision model describe the photo first
+    #[serde(default)]
+    pub extract_words: bool,
+    /// Share of the word score in the fused score, from 0 to 1; defaults to
+    /// `[matching] word_weight`
+    pub word_weight: Option<f32>,
+    /// Number of ranked candidates to return (default 1)
+    pub top_k: Option<usize>,
+    /// Drop candidates whose fused score is below this
+    pub min_score: Option<f32>,
+    /// How the words are matched; defaults to `[matching] mode`
+    pub mode: Option<MatchMode>,
+    /// Per-slot weights for `slots` mode, on top of `[matching] slot_weights`
+    #[serde(default)]
+    pub slot_weights: HashMap<TagCategory, f32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DescribeImageRequest {
+    pub image_base64: String,
+    /// Only use words from the built-in vocabulary, snapping any others to the closest one
+    #[serde(default)]
+    pub vocabulary_only: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HamsterMatchRequest {
+    /// The captured photo
+    pub image_base64: String,
+    /// Only use words from the built-in vocabulary, snapping any others to the closest one
+    #[serde(default)]
+    pub vocabulary_only: bool,
+    /// Number of ranked candidates to return (default 1)
+    pub top_k: Option<usize>,
+    /// Drop candidates scoring below this cosine similarity
+    pub min_score: Option<f32>,
+    /// Defaults to `[matching] mode`
+    pub mode: Option<MatchMode>,
+    /// Per-slot weights for `slots` mode, on top of `[matching] slot_weights`
+    #[serde(default)]
+    pub slot_weights: HashMap<TagCategory, f32>,
+}
@@ -1,24 +1,192 @@
 use serde::Serialize;
 
+use crate::config::{MatchMode, RoastIntensity};
+use crate::lib::poem_form::ConstraintCheck;
+use crate::lib::tags::{Tag, TagCategory};
+
+/// Body of every failed request
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    pub success: bool,
+    pub error: ErrorDetail,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorDetail {
+    /// Stable identifier, e.g. `upstream_timeout`; see `AppError::code`
+    pub code: &'static str,
+    pub message: String,
+}
+
 #[derive(Debug, Serialize)]
 pub struct PoemResponse {
     pub success: bool,
-    pub poem: Option<String>,
-    pub error: Option<String>,
+    pub poem: String,
+    /// Generations it took to meet the constraints; only when there were any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attempts: Option<u32>,
+    /// How the returned poem fared against each measurable constraint
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub constraints: Vec<ConstraintCheck>,
+    /// Template the poem came from, e.g. `poem_image@1a2b3c4d`, or `custom` for
+    /// a caller's own `/poem/image` prompt; see `/admin/prompts`
+    pub prompt_version: String,
+    /// Id of the result's public `/r/{share_id}` page, when it was saved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub share_id: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct RoastResponse {
     pub success: bool,
-    pub roast: Option<String>,
-    pub error: Option<String>,
+    pub roast: String,
+    pub intensity: RoastIntensity,
+    /// Generations made; more than 1 when earlier roasts failed the safety checks
+    pub attempts: u32,
+    /// Template the roast came from, e.g. `roast@1a2b3c4d`; see `/admin/prompts`
+    pub prompt_version: String,
+    /// Id of the result's public `/r/{share_id}` page, when it was saved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub share_id: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct ImageMatchResponse {
     pub success: bool,
+    /// Best candidate, if any scored above `min_score`
+    pub matched_image_url: Option<String>,
+    pub extracted_words: Vec<String>,
+    pub similarity_score: Option<f32>,
+    pub mode: MatchMode,
+    /// Ranked candidates, best first
+    pub matches: Vec<ImageMatchCandidate>,
+}
+
+/// A library image ranked against the query words
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageMatchCandidate {
+    pub image_url: String,
+    /// The words of `tags`
+    pub words: Vec<String>,
+    pub tags: Vec<Tag>,
+    /// Cosine similarity between the averaged query and the weighted library
+    /// embeddings, or in `slots` mode the weighted mean of `slot_scores`
+    pub score: f32,
+    /// Closest library word for each query word
+    pub word_scores: Vec<WordSimilarity>,
+    /// How each query slot compared, in `slots` mode
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub slot_scores: Vec<SlotScore>,
+}
+
+/// One query slot compared with the entry's tags for the same slot
+#[derive(Debug, Clone, Serialize)]
+pub struct SlotScore {
+    pub slot: TagCategory,
+    pub query_word: String,
+    /// Closest library tag for the slot; `None` if the entry has no tag to compare
+    pub library_word: Option<String>,
+    pub score: f32,
+    pub weight: f32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WordSimilarity {
+    pub query_word: String,
+    pub library_word: String,
+    pub score: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PhotoMatchResponse {
+    pub success: bool,
+    /// Best candidate, if any scored above `min_score`
     pub matched_image_url: Option<String>,
-    pub extracted_words: Option<Vec<String>>,
     pub similarity_score: Option<f32>,
-    pub error: Option<String>,
-}
\ No newline at end of file
+    /// Words fused into the score, given or extracted; empty if none were
+    pub extracted_words: Vec<String>,
+    /// Share of the word score actually used; 0 without words
+    pub word_weight: f32,
+    /// Ranked candidates, best first
+    pub matches: Vec<PhotoMatchCandidate>,
+}
+
+/// A library image ranked against the photo
+#[derive(Debug, Clone, Serialize)]
+pub struct PhotoMatchCandidate {
+    pub image_url: String,
+    /// `photo_score`, or its weighted mean with `word_score`
+    pub score: f32,
+    /// Cosine similarity between the photo's and the image's CLIP embeddings
+    pub photo_score: f32,
+    /// The word match score, when words were fused in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub word_score: Option<f32>,
+}
+
+/// A tag the vision model gave a photo
+#[derive(Debug, Clone, Serialize)]
+pub struct DescribedTag {
+    #[serde(flatten)]
+    pub tag: Tag,
+    /// The model's own word, when it was snapped to a different vocabulary word
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extracted: Option<String>,
+    /// Similarity between the model's word and `word`, when snapping to the vocabulary
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snap_score: Option<f32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DescribeImageResponse {
+    pub success: bool,
+    /// The words of `tags`: object, emotion and style first, then any extras
+    pub words: Vec<String>,
+    pub tags: Vec<DescribedTag>,
+    /// Template the tags came from, e.g. `extract_tags@1a2b3c4d`; see `/admin/prompts`
+    pub prompt_version: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HamsterMatchResponse {
+    pub success: bool,
+    /// Best candidate, if any scored above `min_score`
+    pub matched_image_url: Option<String>,
+    pub similarity_score: Option<f32>,
+    /// The words the photo was matched on
+    pub extracted_words: Vec<String>,
+    pub tags: Vec<DescribedTag>,
+    /// Template the tags came from, e.g. `extract_tags@1a2b3c4d`; see `/admin/prompts`
+    pub prompt_version: String,
+    pub mode: MatchMode,
+    /// Ranked candidates, best first
+    pub matches: Vec<ImageMatchCandidate>,
+    /// Id of the result's public `/r/{share_id}` page, when it was saved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub share_id: Option<String>,
+}
+
+/// SSE `token` event: one chunk of generated text
+#[derive(Debug, Serialize)]
+pub struct StreamTokenEvent {
+    pub token: String,
+}
+
+/// SSE `done` event: the full generated text plus timing
+#[derive(Debug, Serialize)]
+pub struct StreamDoneEvent {
+    pub text: String,
+    pub elapsed_ms: u64,
+    pub first_token_ms: Option<u64>,
+    /// Template the text came from, as in the non-streaming response
+    pub prompt_version: String,
+    /// Id of the result's public `/r/{share_id}` page, when it was saved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub share_id: Option<String>,
+}
+
+/// SSE `error` event: generation failed after the stream was opened
+#[derive(Debug, Serialize)]
+pub struct StreamErrorEvent {
+    pub error: ErrorDetail,
+}
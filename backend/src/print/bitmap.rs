@@ -0,0 +1,131 @@
+use image::{GrayImage, ImageFormat, Luma};
+use std::io::Cursor;
+
+use crate::error::AppError;
+
+use super::protocol::BYTES_PER_LINE;
+use super::PRINTER_WIDTH;
+
+/// A 1-bit image exactly one print head wide, packed the way the printer wants it:
+/// 48 bytes per row, least significant bit = leftmost dot, 1 = black.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bitmap {
+    data: Vec<u8>,
+}
+
+impl Bitmap {
+    /// A blank (all white) bitmap
+    pub fn new(height: u32) -> Self {
+        Self {
+            data: vec![0; height as usize * BYTES_PER_LINE],
+        }
+    }
+
+    /// Pack row-major dots (`true` = black), left-aligned; dots past the print head are dropped
+    pub fn from_dots(dots: &[bool], width: usize) -> Self {
+        let height = dots.len().checked_div(width).unwrap_or(0);
+        let mut bitmap = Self::new(height as u32);
+
+        for (y, row) in dots.chunks_exact(width).enumerate() {
+            for (x, black) in row.iter().enumerate().take(PRINTER_WIDTH as usize) {
+                if *black {
+                    bitmap.set(x as u32, y as u32, true);
+                }
+            }
+        }
+
+        bitmap
+    }
+
+    pub fn height(&self) -> u32 {
+        (self.data.len() / BYTES_PER_LINE) as u32
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> bool {
+        let (byte, bit) = Self::index(x, y);
+        self.data.get(byte).is_some_and(|b| b & (1 << bit) != 0)
+    }
+
+    /// Set one dot; out-of-range coordinates are ignored
+    pub fn set(&mut self, x: u32, y: u32, black: bool) {
+        if x >= PRINTER_WIDTH {
+            return;
+        }
+
+        let (byte, bit) = Self::index(x, y);
+        if let Some(b) = self.data.get_mut(byte) {
+            if black {
+                *b |= 1 << bit;
+            } else {
+                *b &= !(1 << bit);
+            }
+        }
+    }
+
+    /// Add `rows` blank rows at the bottom and return the index of the first one
+    pub fn add_rows(&mut self, rows: u32) -> u32 {
+        let first = self.height();
+        self.data
+            .resize(self.data.len() + rows as usize * BYTES_PER_LINE, 0);
+        first
+    }
+
+    /// Stack `other` below this bitmap
+    pub fn append(&mut self, other: &Bitmap) {
+        self.data.extend_from_slice(&other.data);
+    }
+
+    fn index(x: u32, y: u32) -> (usize, u32) {
+        (y as usize * BYTES_PER_LINE + x as usize / 8, x % 8)
+    }
+
+    /// Printer rows, top to bottom, `BYTES_PER_LINE` bytes each
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        self.data.chunks_exact(BYTES_PER_LINE)
+    }
+
+    /// Black-on-white PNG preview of exactly what will be printed
+    pub fn to_png(&self) -> Result<Vec<u8>, AppError> {
+        let image = GrayImage::from_fn(PRINTER_WIDTH, self.height(), |x, y| {
+            Luma([if self.get(x, y) { 0 } else { 255 }])
+        });
+
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .map_err(|e| AppError::Internal(format!("Failed to encode PNG: {}", e)))?;
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bits_are_lsb_first() {
+        let mut dots = vec![false; 16];
+        dots[0] = true;
+        dots[9] = true;
+        let bitmap = Bitmap::from_dots(&dots, 16);
+
+        let row = bitmap.rows().next().unwrap();
+        assert_eq!(row.len(), BYTES_PER_LINE);
+        assert_eq!(row[0], 0b0000_0001);
+        assert_eq!(row[1], 0b0000_0010);
+        assert!(bitmap.get(9, 0));
+        assert!(!bitmap.get(8, 0));
+    }
+
+    #[test]
+    fn test_png_preview_round_trips() {
+        let mut bitmap = Bitmap::new(2);
+        bitmap.set(383, 1, true);
+
+        let png = bitmap.to_png().unwrap();
+        let decoded = image::load_from_memory(&png).unwrap().to_luma8();
+        assert_eq!(decoded.dimensions(), (384, 2));
+        assert_eq!(decoded.get_pixel(383, 1).0, [0]);
+        assert_eq!(decoded.get_pixel(0, 0).0, [255]);
+    }
+}
@@ -0,0 +1,102 @@
+use serde::Deserialize;
+
+/// How grayscale is reduced to black and white dots
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DitherMode {
+    /// Error diffusion to four neighbours; best for photos
+    #[default]
+    FloydSteinberg,
+    /// Diffuses only 3/4 of the error: crisper, higher-contrast output
+    Atkinson,
+    /// Plain cut-off; best for line art and text
+    Threshold,
+}
+
+/// Dither row-major luminance (0 = black, 255 = white) into dots, `true` = black.
+/// Anything darker than `threshold` becomes a dot.
+pub fn dither(mut luma: Vec<f32>, width: usize, mode: DitherMode, threshold: u8) -> Vec<bool> {
+    if width == 0 {
+        return Vec::new();
+    }
+
+    let height = luma.len() / width;
+    let threshold = threshold as f32;
+
+    // (dx, dy, weight) of each neighbour that receives part of the error
+    let diffusion: &[(isize, usize, f32)] = match mode {
+        DitherMode::FloydSteinberg => &[
+            (1, 0, 7.0 / 16.0),
+            (-1, 1, 3.0 / 16.0),
+            (0, 1, 5.0 / 16.0),
+            (1, 1, 1.0 / 16.0),
+        ],
+        DitherMode::Atkinson => &[
+            (1, 0, 1.0 / 8.0),
+            (2, 0, 1.0 / 8.0),
+            (-1, 1, 1.0 / 8.0),
+            (0, 1, 1.0 / 8.0),
+            (1, 1, 1.0 / 8.0),
+            (0, 2, 1.0 / 8.0),
+        ],
+        DitherMode::Threshold => &[],
+    };
+
+    let mut dots = vec![false; width * height];
+
+    for y in 0..height {
+        for x in 0..width {
+            let idx = y * width + x;
+            let old = luma[idx];
+            let black = old < threshold;
+            dots[idx] = black;
+
+            let error = old - if black { 0.0 } else { 255.0 };
+            for &(dx, dy, weight) in diffusion {
+                let nx = x as isize + dx;
+                let ny = y + dy;
+                if nx < 0 || nx as usize >= width || ny >= height {
+                    continue;
+                }
+                luma[ny * width + nx as usize] += error * weight;
+            }
+        }
+    }
+
+    dots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coverage(dots: &[bool]) -> f32 {
+        dots.iter().filter(|d| **d).count() as f32 / dots.len() as f32
+    }
+
+    #[test]
+    fn test_threshold() {
+        let dots = dither(
+            vec![0.0, 127.0, 128.0, 255.0],
+            4,
+            DitherMode::Threshold,
+            128,
+        );
+        assert_eq!(dots, [true, true, false, false]);
+    }
+
+    #[test]
+    fn test_floyd_steinberg_preserves_mid_gray() {
+        let dots = dither(vec![128.0; 64 * 64], 64, DitherMode::FloydSteinberg, 128);
+        let coverage = coverage(&dots);
+        assert!((0.45..=0.55).contains(&coverage), "coverage {}", coverage);
+    }
+
+    #[test]
+    fn test_atkinson_pushes_dark_gray_darker() {
+        let dark = vec![40.0; 64 * 64];
+        let floyd = coverage(&dither(dark.clone(), 64, DitherMode::FloydSteinberg, 128));
+        let atkinson = coverage(&dither(dark, 64, DitherMode::Atkinson, 128));
+        assert!(atkinson > floyd, "{} vs {}", atkinson, floyd);
+    }
+}
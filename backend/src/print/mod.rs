@@ -0,0 +1,103 @@
+//! Rendering for 384-dot thermal "cat" printers (GB01/GB02/X5 and clones):
+//! images are scaled to the print head, dithered to 1 bit and encoded as the
+//! printer's `0x51 0x78` command stream, ready to be written over Bluetooth.
+//! Receipts lay out text with an embedded bitmap font around those images.
+
+mod bitmap;
+mod dither;
+mod font;
+mod protocol;
+mod qr;
+mod receipt;
+
+pub use bitmap::Bitmap;
+pub use dither::{dither, DitherMode};
+pub use protocol::{encode_job, PrintSettings};
+pub use qr::{qr_bitmap, qr_png};
+pub use receipt::{render_receipt, utc_date, ReceiptContent};
+
+use image::imageops::FilterType;
+use image::GenericImageView;
+
+use crate::error::AppError;
+
+/// Dots across the print head
+pub const PRINTER_WIDTH: u32 = 384;
+
+/// Tallest image we will render, about half a metre of paper
+pub const MAX_HEIGHT: u32 = 4096;
+
+/// Decode an image, scale it to the printer width and dither it to 1 bit
+pub fn render_image(bytes: &[u8], mode: DitherMode, threshold: u8) -> Result<Bitmap, AppError> {
+    let image = image::load_from_memory(bytes)
+        .map_err(|e| AppError::Validation(format!("Could not decode image: {}", e)))?;
+
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return Err(AppError::Validation("Image is empty".into()));
+    }
+
+    let scaled_height = ((height as u64 * PRINTER_WIDTH as u64) / width as u64).max(1);
+    if scaled_height > MAX_HEIGHT as u64 {
+        return Err(AppError::Validation(format!(
+            "Image is too tall to print: {} rows at {} dots wide (max {})",
+            scaled_height, PRINTER_WIDTH, MAX_HEIGHT
+        )));
+    }
+    let scaled_height = scaled_height as u32;
+
+    let rgba = image
+        .resize_exact(PRINTER_WIDTH, scaled_height, FilterType::Triangle)
+        .to_rgba8();
+
+    let luma: Vec<f32> = rgba.pixels().map(|p| luminance_on_white(p.0)).collect();
+
+    let dots = dither(luma, PRINTER_WIDTH as usize, mode, threshold);
+    Ok(Bitmap::from_dots(&dots, PRINTER_WIDTH as usize))
+}
+
+/// Perceived brightness (0-255) of a pixel composited over white paper
+fn luminance_on_white([r, g, b, a]: [u8; 4]) -> f32 {
+    let luma = 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32;
+    let alpha = a as f32 / 255.0;
+    luma * alpha + 255.0 * (1.0 - alpha)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, Rgba, RgbaImage};
+    use std::io::Cursor;
+
+    fn png(image: RgbaImage) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_render_image_scales_to_printer_width() {
+        let bytes = png(RgbaImage::from_pixel(96, 48, Rgba([0, 0, 0, 255])));
+        let bitmap = render_image(&bytes, DitherMode::Threshold, 128).unwrap();
+        assert_eq!(bitmap.height(), 192);
+        assert!(bitmap.rows().all(|row| row.iter().all(|b| *b == 0xff)));
+    }
+
+    #[test]
+    fn test_transparent_pixels_print_as_paper() {
+        let bytes = png(RgbaImage::from_pixel(384, 2, Rgba([0, 0, 0, 0])));
+        let bitmap = render_image(&bytes, DitherMode::FloydSteinberg, 128).unwrap();
+        assert!(bitmap.rows().all(|row| row.iter().all(|b| *b == 0)));
+    }
+
+    #[test]
+    fn test_render_image_rejects_garbage_and_tall_images() {
+        assert!(render_image(b"not an image", DitherMode::Threshold, 128).is_err());
+
+        let tall = png(RgbaImage::new(1, 20));
+        let err = render_image(&tall, DitherMode::Threshold, 128).unwrap_err();
+        assert_eq!(err.code(), "validation_failed");
+    }
+}
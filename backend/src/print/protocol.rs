@@ -0,0 +1,146 @@
+//! Cat printer command packets, as used by the kiosk's Bluetooth printer.
+//!
+//! Every command is `51 78 <opcode> 00 <len> 00 <data..> <crc8(data)> ff`.
+//! See <https://github.com/JJJollyjim/catprinter/blob/main/COMMANDS.md>.
+
+use super::Bitmap;
+
+/// Bytes of bitmap data per printed row (384 dots)
+pub const BYTES_PER_LINE: usize = 48;
+
+const CMD_FEED_PAPER: u8 = 0xa1;
+const CMD_DRAW_BITMAP: u8 = 0xa2;
+const CMD_LATTICE: u8 = 0xa6;
+const CMD_ENERGY: u8 = 0xaf;
+const CMD_DRAWING_MODE: u8 = 0xbe;
+
+const LATTICE_START: [u8; 11] = [
+    0xaa, 0x55, 0x17, 0x38, 0x44, 0x5f, 0x5f, 0x5f, 0x44, 0x38, 0x2c,
+];
+const LATTICE_END: [u8; 11] = [
+    0xaa, 0x55, 0x17, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x17,
+];
+
+/// Per-job printer settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrintSettings {
+    /// Print head energy; higher is darker. GB01 accepts roughly 8000-17500.
+    pub energy: u16,
+    /// Blank rows fed after the image so it clears the tear bar
+    pub feed_lines: u16,
+}
+
+impl Default for PrintSettings {
+    fn default() -> Self {
+        Self {
+            energy: 8000,
+            feed_lines: 100,
+        }
+    }
+}
+
+/// Full command stream for one print: setup, one draw command per row, feed, finish
+pub fn encode_job(bitmap: &Bitmap, settings: &PrintSettings) -> Vec<u8> {
+    let mut out = Vec::new();
+
+    // Image mode, energy, then the lattice start sequence
+    command(&mut out, CMD_DRAWING_MODE, &[0x00]);
+    command(&mut out, CMD_ENERGY, &settings.energy.to_le_bytes());
+    command(&mut out, CMD_LATTICE, &LATTICE_START);
+
+    for row in bitmap.rows() {
+        command(&mut out, CMD_DRAW_BITMAP, row);
+    }
+
+    // Feed one row at a time; multi-row feeds are unreliable on some clones
+    for _ in 0..settings.feed_lines {
+        command(&mut out, CMD_FEED_PAPER, &[0x01, 0x00]);
+    }
+
+    command(&mut out, CMD_LATTICE, &LATTICE_END);
+    out
+}
+
+/// Append a single framed command
+fn command(out: &mut Vec<u8>, opcode: u8, data: &[u8]) {
+    debug_assert!(data.len() <= u8::MAX as usize);
+
+    out.extend_from_slice(&[0x51, 0x78, opcode, 0x00, data.len() as u8, 0x00]);
+    out.extend_from_slice(data);
+    out.push(crc8(data));
+    out.push(0xff);
+}
+
+/// CRC-8 with polynomial 0x07, as checked by the printer firmware
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc8_matches_printer_table() {
+        // Spot checks against the lookup table the frontend used
+        assert_eq!(crc8(&[0x01]), 0x07);
+        assert_eq!(crc8(&[0x80]), 0x89);
+        assert_eq!(crc8(&[0xff]), 0xf3);
+    }
+
+    #[test]
+    fn test_command_framing() {
+        let mut out = Vec::new();
+        command(&mut out, CMD_ENERGY, &8000u16.to_le_bytes());
+        assert_eq!(
+            out,
+            [
+                0x51,
+                0x78,
+                0xaf,
+                0x00,
+                0x02,
+                0x00,
+                0x40,
+                0x1f,
+                crc8(&[0x40, 0x1f]),
+                0xff
+            ]
+        );
+    }
+
+    #[test]
+    fn test_encode_job_layout() {
+        let bitmap = Bitmap::new(3);
+        let settings = PrintSettings {
+            energy: 12000,
+            feed_lines: 2,
+        };
+        let job = encode_job(&bitmap, &settings);
+
+        let packet = |data_len: usize| 8 + data_len;
+        let expected_len = packet(1)
+            + packet(2)
+            + packet(11)
+            + 3 * packet(BYTES_PER_LINE)
+            + 2 * packet(2)
+            + packet(11);
+        assert_eq!(job.len(), expected_len);
+
+        // First draw command follows the three setup commands
+        let first_row = packet(1) + packet(2) + packet(11);
+        assert_eq!(
+            &job[first_row..first_row + 5],
+            &[0x51, 0x78, 0xa2, 0x00, 48]
+        );
+        assert_eq!(job.last(), Some(&0xff));
+    }
+}
@@ -0,0 +1,128 @@
+//! QR codes for share links: a crisp PNG for screens, and a bitmap centred on
+//! the print head for receipts.
+
+use image::{GrayImage, ImageFormat, Luma};
+use qrcode::{Color, EcLevel, QrCode};
+use std::io::Cursor;
+
+use crate::error::AppError;
+
+use super::{Bitmap, PRINTER_WIDTH};
+
+/// Light modules around the code, as scanners expect
+const QUIET_ZONE: u32 = 4;
+
+/// Pixels per module in the PNG
+const SCREEN_SCALE: u32 = 8;
+
+/// Widest printed code, in dots; about two thirds of the paper
+const MAX_PRINTED_DOTS: u32 = 256;
+
+/// The module grid of a code, quiet zone included
+struct Modules {
+    /// Modules across the code itself
+    width: u32,
+    dark: Vec<bool>,
+}
+
+impl Modules {
+    fn encode(data: &str) -> Result<Self, AppError> {
+        let code = QrCode::with_error_correction_level(data, EcLevel::M)
+            .map_err(|e| AppError::Internal(format!("Failed to encode QR code: {}", e)))?;
+        Ok(Self {
+            width: code.width() as u32,
+            dark: code
+                .into_colors()
+                .into_iter()
+                .map(|color| color == Color::Dark)
+                .collect(),
+        })
+    }
+
+    /// Modules across, quiet zone included
+    fn size(&self) -> u32 {
+        self.width + 2 * QUIET_ZONE
+    }
+
+    /// Whether the module at (`x`, `y`), counted from the quiet zone's corner, is dark
+    fn is_dark(&self, x: u32, y: u32) -> bool {
+        let (Some(x), Some(y)) = (x.checked_sub(QUIET_ZONE), y.checked_sub(QUIET_ZONE)) else {
+            return false;
+        };
+        x < self.width && y < self.width && self.dark[(y * self.width + x) as usize]
+    }
+}
+
+/// PNG of a QR code for `data`, black on white
+pub fn qr_png(data: &str) -> Result<Vec<u8>, AppError> {
+    let modules = Modules::encode(data)?;
+    let side = modules.size() * SCREEN_SCALE;
+    let image = GrayImage::from_fn(side, side, |x, y| {
+        let dark = modules.is_dark(x / SCREEN_SCALE, y / SCREEN_SCALE);
+        Luma([if dark { 0 } else { 255 }])
+    });
+
+    let mut bytes = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+        .map_err(|e| AppError::Internal(format!("Failed to encode PNG: {}", e)))?;
+    Ok(bytes)
+}
+
+/// A QR code for `data`, centred across the print head and scaled by whole
+/// dots so every module stays square
+pub fn qr_bitmap(data: &str) -> Result<Bitmap, AppError> {
+    let modules = Modules::encode(data)?;
+    let scale = (MAX_PRINTED_DOTS / modules.size()).max(1);
+    let side = modules.size() * scale;
+    let left = PRINTER_WIDTH.saturating_sub(side) / 2;
+
+    let mut bitmap = Bitmap::new(side);
+    for y in 0..side {
+        for x in 0..side {
+            if modules.is_dark(x / scale, y / scale) {
+                bitmap.set(left + x, y, true);
+            }
+        }
+    }
+    Ok(bitmap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &str = "http://192.168.1.20:8000/r/k3m9x2p7qd";
+
+    #[test]
+    fn test_qr_png_has_quiet_zone_and_finder() {
+        let modules = Modules::encode(URL).unwrap();
+        let png = qr_png(URL).unwrap();
+        let image = image::load_from_memory(&png).unwrap().to_luma8();
+
+        let side = modules.size() * SCREEN_SCALE;
+        assert_eq!(image.dimensions(), (side, side));
+        assert_eq!(image.get_pixel(0, 0).0, [255]);
+        // The top-left finder pattern starts right after the quiet zone
+        let corner = QUIET_ZONE * SCREEN_SCALE;
+        assert_eq!(image.get_pixel(corner, corner).0, [0]);
+        assert_eq!(image.get_pixel(corner - 1, corner).0, [255]);
+    }
+
+    #[test]
+    fn test_qr_bitmap_is_centred() {
+        let modules = Modules::encode(URL).unwrap();
+        let bitmap = qr_bitmap(URL).unwrap();
+
+        let scale = MAX_PRINTED_DOTS / modules.size();
+        let side = modules.size() * scale;
+        assert!(side <= MAX_PRINTED_DOTS);
+        assert_eq!(bitmap.height(), side);
+
+        let left = (PRINTER_WIDTH - side) / 2;
+        let corner = QUIET_ZONE * scale;
+        assert!(bitmap.get(left + corner, corner));
+        assert!(!bitmap.get(left + corner - 1, corner));
+        assert!((0..PRINTER_WIDTH).all(|x| !bitmap.get(x, 0)));
+    }
+}
@@ -0,0 +1,259 @@
+//! Receipt layout: branding, photos and word-wrapped text stacked on one strip of paper.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::font::{glyph, to_printable, GLYPH_SIZE};
+use super::{Bitmap, PRINTER_WIDTH};
+
+/// Blank dots kept clear on the left and right of text
+const MARGIN: u32 = 8;
+
+/// Scale of the header, body and small print, in dots per font pixel
+const HEADER_SCALE: u32 = 3;
+const BODY_SCALE: u32 = 2;
+const SMALL_SCALE: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Align {
+    Left,
+    Center,
+}
+
+/// Everything that goes on one receipt, top to bottom
+#[derive(Debug, Clone, Default)]
+pub struct ReceiptContent {
+    pub header: String,
+    pub date: String,
+    /// The captured photo, already dithered
+    pub photo: Option<Bitmap>,
+    /// Heading above the text, e.g. "YOUR ROAST"
+    pub title: Option<String>,
+    pub text: String,
+    /// The matched library image, already dithered
+    pub image: Option<Bitmap>,
+    /// QR code of the result's share page, printed with a caption
+    pub qr: Option<Bitmap>,
+    pub footer: String,
+}
+
+/// Lay out a full receipt
+pub fn render_receipt(content: &ReceiptContent) -> Bitmap {
+    let mut receipt = Receipt::new();
+
+    receipt.space(16);
+    receipt.text(&content.header, HEADER_SCALE, Align::Center);
+    receipt.text(&content.date, SMALL_SCALE, Align::Center);
+    receipt.rule();
+
+    if let Some(photo) = &content.photo {
+        receipt.image(photo);
+        receipt.space(12);
+    }
+    if let Some(title) = &content.title {
+        receipt.text(title, BODY_SCALE, Align::Center);
+        receipt.space(8);
+    }
+    receipt.text(&content.text, BODY_SCALE, Align::Left);
+    if let Some(image) = &content.image {
+        receipt.space(12);
+        receipt.image(image);
+    }
+    if let Some(qr) = &content.qr {
+        receipt.space(12);
+        receipt.text("Scan to take it home", SMALL_SCALE, Align::Center);
+        receipt.space(4);
+        receipt.image(qr);
+    }
+
+    receipt.rule();
+    receipt.text(&content.footer, SMALL_SCALE, Align::Center);
+    receipt.space(16);
+
+    receipt.finish()
+}
+
+/// A bitmap that grows downwards as blocks are added
+#[derive(Debug, Default)]
+struct Receipt {
+    bitmap: Bitmap,
+}
+
+impl Receipt {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Word-wrapped text; explicit line breaks are kept
+    fn text(&mut self, text: &str, scale: u32, align: Align) -> &mut Self {
+        let cell = GLYPH_SIZE * scale;
+        let columns = ((PRINTER_WIDTH - 2 * MARGIN) / cell) as usize;
+        let line_height = cell + 3 * scale;
+
+        for line in wrap(&to_printable(text), columns) {
+            let top = self.bitmap.add_rows(line_height);
+            let width = line.len() as u32 * cell;
+            let left = match align {
+                Align::Left => MARGIN,
+                Align::Center => (PRINTER_WIDTH - width) / 2,
+            };
+
+            for (i, c) in line.chars().enumerate() {
+                self.glyph(c, left + i as u32 * cell, top, scale);
+            }
+        }
+        self
+    }
+
+    /// A full-width image
+    fn image(&mut self, image: &Bitmap) -> &mut Self {
+        self.bitmap.append(image);
+        self
+    }
+
+    /// A dashed separator with some breathing room
+    fn rule(&mut self) -> &mut Self {
+        self.space(8);
+        let top = self.bitmap.add_rows(2);
+        for x in (MARGIN..PRINTER_WIDTH - MARGIN).filter(|x| x % 8 < 5) {
+            self.bitmap.set(x, top, true);
+            self.bitmap.set(x, top + 1, true);
+        }
+        self.space(10)
+    }
+
+    fn space(&mut self, rows: u32) -> &mut Self {
+        self.bitmap.add_rows(rows);
+        self
+    }
+
+    fn finish(self) -> Bitmap {
+        self.bitmap
+    }
+
+    fn glyph(&mut self, c: char, left: u32, top: u32, scale: u32) {
+        for (gy, bits) in glyph(c).iter().enumerate() {
+            for gx in 0..GLYPH_SIZE {
+                if bits & (1 << gx) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let x = left + gx * scale + dx;
+                        let y = top + gy as u32 * scale + dy;
+                        self.bitmap.set(x, y, true);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Greedy word wrap to `columns` characters. Blank lines are kept and words
+/// longer than a line are broken.
+fn wrap(text: &str, columns: usize) -> Vec<String> {
+    let columns = columns.max(1);
+    let mut lines = Vec::new();
+
+    for paragraph in text.trim_end().lines() {
+        let mut line = String::new();
+
+        for word in paragraph.split_whitespace() {
+            let mut word = word;
+
+            if !line.is_empty() && line.len() + 1 + word.len() > columns {
+                lines.push(std::mem::take(&mut line));
+            }
+            while word.len() > columns {
+                if !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                }
+                let (head, tail) = word.split_at(columns);
+                lines.push(head.to_string());
+                word = tail;
+            }
+
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Today's date in UTC as `YYYY-MM-DD`
+pub fn utc_date(now: SystemTime) -> String {
+    let days = now
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() / 86_400)
+        .unwrap_or(0) as i64;
+
+    // Howard Hinnant's civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(
+            wrap("the quick brown fox\n\njumps", 10),
+            ["the quick", "brown fox", "", "jumps"]
+        );
+        assert_eq!(wrap("abcdefghij kl", 4), ["abcd", "efgh", "ij", "kl"]);
+        assert!(wrap("", 10).is_empty());
+    }
+
+    #[test]
+    fn test_utc_date() {
+        assert_eq!(utc_date(UNIX_EPOCH), "1970-01-01");
+        let leap_day = UNIX_EPOCH + Duration::from_secs(1_709_164_800);
+        assert_eq!(utc_date(leap_day), "2024-02-29");
+    }
+
+    #[test]
+    fn test_receipt_stacks_blocks() {
+        let text_only = render_receipt(&ReceiptContent {
+            header: "SNAP".into(),
+            text: "Hello".into(),
+            ..Default::default()
+        });
+
+        let with_photo = render_receipt(&ReceiptContent {
+            header: "SNAP".into(),
+            text: "Hello".into(),
+            photo: Some(Bitmap::new(100)),
+            ..Default::default()
+        });
+
+        assert_eq!(with_photo.height(), text_only.height() + 100 + 12);
+
+        let with_qr = render_receipt(&ReceiptContent {
+            header: "SNAP".into(),
+            text: "Hello".into(),
+            qr: Some(Bitmap::new(200)),
+            ..Default::default()
+        });
+        // Space, one caption line, space, then the code
+        assert_eq!(with_qr.height(), text_only.height() + 12 + 11 + 4 + 200);
+        // Something was drawn, and nothing in the margin
+        assert!(text_only.rows().any(|row| row.iter().any(|b| *b != 0)));
+        assert!((0..text_only.height()).all(|y| !text_only.get(0, y)));
+    }
+}
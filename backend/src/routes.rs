@@ -0,0 +1,133 @@
+use axum::{
+    http::HeaderName,
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use tower_http::cors::{Any, CorsLayer};
+use tower_http::services::ServeDir;
+use tower_http::trace::TraceLayer;
+
+use crate::auth;
+use crate::handlers;
+use crate::state::AppState;
+
+/// Build the full application router
+pub fn router(state: AppState) -> Router {
+    let cors = CorsLayer::new()
+        .allow_origin(Any)
+        .allow_methods(Any)
+        .allow_headers(Any)
+        .expose_headers([HeaderName::from_static("x-print-rows")]);
+
+    let mut router = Router::new()
+        .route("/", get(|| async { "Hack and Roll Snap API" }))
+        .route("/health", get(handlers::health::check))
+        // Poem routes
+        .route("/poem/text", post(handlers::poem::generate_from_text))
+        .route("/poem/image", post(handlers::poem::generate_from_image))
+        .route("/poem/text/stream", post(handlers::poem::stream_from_text))
+        .route(
+            "/poem/image/stream",
+            post(handlers::poem::stream_from_image),
+        )
+        // Roast routes
+        .route("/roast/image", post(handlers::roast::generate_from_image))
+        .route(
+            "/roast/image/stream",
+            post(handlers::roast::stream_from_image),
+        )
+        // Conversation routes
+        .route("/chat/session", post(handlers::chat::create))
+        .route(
+            "/chat/session/:id",
+            get(handlers::chat::get).delete(handlers::chat::delete),
+        )
+        .route(
+            "/chat/session/:id/messages",
+            post(handlers::chat::send_message),
+        )
+        // Embedding routes
+        .route("/embed", post(handlers::embedding::embed_text))
+        .route("/embed/batch", post(handlers::embedding::embed_batch))
+        .route(
+            "/embed/search",
+            post(handlers::embedding::similarity_search),
+        )
+        // Image description and matching routes
+        .route("/image/describe", post(handlers::describe::describe_image))
+        .route("/image/match", post(handlers::image_match::match_image))
+        .route(
+            "/image/match/photo",
+            post(handlers::image_match::match_photo),
+        )
+        .route("/hamster/match", post(handlers::image_match::match_hamster))
+        // Thermal printer rendering
+        .route("/print/render", post(handlers::print::render))
+        .route("/print/receipt", post(handlers::print::receipt));
+
+    // Public result pages, reached by QR code
+    if state.history.shares() {
+        router = router
+            .route("/r/:id", get(handlers::share::page))
+            .route("/r/:id/photo.jpg", get(handlers::share::photo))
+            .route("/r/:id/qr.png", get(handlers::share::qr));
+    }
+
+    // Kiosk deployments can leave the admin routes out entirely
+    if state.config.admin.enabled {
+        router = router.merge(admin_router(state.clone()));
+    }
+
+    // Saved generations, kept even when the admin routes are left out
+    if state.history.enabled() {
+        router = router.merge(history_router(state.clone()));
+    }
+
+    router
+        // Serve static images
+        .nest_service("/images", ServeDir::new(&state.config.library.images_dir))
+        .layer(cors)
+        .layer(TraceLayer::new_for_http())
+        .with_state(state)
+}
+
+/// `/history/*`, which shows visitors' photos, so it sits behind admin authentication
+fn history_router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/history", get(handlers::history::list))
+        .route(
+            "/history/:id",
+            get(handlers::history::get).delete(handlers::history::delete),
+        )
+        .route("/history/:id/thumbnail", get(handlers::history::thumbnail))
+        .route_layer(middleware::from_fn_with_state(state, auth::require_admin))
+}
+
+/// `/admin/*`, behind admin authentication
+fn admin_router(state: AppState) -> Router<AppState> {
+    Router::new()
+        // Image library generator
+        .route(
+            "/admin/generate-library",
+            post(handlers::image_library_generator::generate_library),
+        )
+        .route("/admin/jobs", get(handlers::jobs::list))
+        .route("/admin/jobs/:id", get(handlers::jobs::get))
+        .route("/admin/jobs/:id/cancel", post(handlers::jobs::cancel))
+        .route("/admin/jobs/:id/retry", post(handlers::jobs::retry))
+        .route(
+            "/admin/library",
+            get(handlers::library::list_entries).post(handlers::library::create_entry),
+        )
+        .route(
+            "/admin/library/:filename",
+            get(handlers::library::get_entry)
+                .put(handlers::library::update_entry)
+                .delete(handlers::library::delete_entry),
+        )
+        .route("/admin/library/reload", post(handlers::library::reload))
+        .route("/admin/prompts", get(handlers::prompts::list))
+        .route("/admin/prompts/reload", post(handlers::prompts::reload))
+        .route_layer(middleware::from_fn_with_state(state, auth::require_admin))
+}
@@ -0,0 +1,616 @@
+use futures_util::{future, stream, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::config::{LlmConfig, RoastIntensity};
+use crate::error::AppError;
+use crate::lib::poem_form::{ConstraintCheck, PoemOptions};
+use crate::lib::tags::{Tag, TagCategory, RESERVED_CHARS};
+use crate::models::TextEmbedding;
+use crate::service::llm::{ChatMessage, GenerateOptions, LlmBackend, TokenStream};
+use crate::service::prompts::{PromptKind, PromptRegistry, RenderedPrompt};
+use crate::service::roast_guard::{RoastGuard, SafetyVerdict};
+use crate::service::sessions::{SessionKind, SessionSettings};
+use crate::service::structured::{chat_structured, StructuredOutput};
+
+/// A generated poem and how it fared against the requested form
+#[derive(Debug, Clone)]
+pub struct FittedPoem {
+    pub poem: String,
+    /// Generations made, including the returned one
+    pub attempts: u32,
+    pub constraints: Vec<ConstraintCheck>,
+    pub prompt_version: String,
+}
+
+/// Model output and the version of the prompt template that produced it
+#[derive(Debug, Clone)]
+pub struct Generated<T = String> {
+    pub output: T,
+    pub prompt_version: String,
+}
+
+/// A roast that passed the safety checks
+#[derive(Debug, Clone)]
+pub struct SafeRoast {
+    pub roast: String,
+    /// Generations made, including the returned one
+    pub attempts: u32,
+    pub prompt_version: String,
+}
+
+impl FittedPoem {
+    fn satisfied(&self) -> usize {
+        self.constraints.iter().filter(|c| c.satisfied).count()
+    }
+}
+
+/// Poem, roast and word-extraction prompts on top of whichever `LlmBackend` is configured
+#[derive(Clone)]
+pub struct GenerationService {
+    backend: Arc<dyn LlmBackend>,
+    config: LlmConfig,
+    prompts: Arc<PromptRegistry>,
+    roast_guard: Arc<RoastGuard>,
+}
+
+impl GenerationService {
+    pub fn new(
+        backend: Arc<dyn LlmBackend>,
+        config: &LlmConfig,
+        prompts: Arc<PromptRegistry>,
+        roast_guard: Arc<RoastGuard>,
+    ) -> Self {
+        Self {
+            backend,
+            config: config.clone(),
+            prompts,
+            roast_guard,
+        }
+    }
+
+    pub fn backend_name(&self) -> &'static str {
+        self.backend.name()
+    }
+
+    pub fn prompts(&self) -> &Arc<PromptRegistry> {
+        &self.prompts
+    }
+
+    fn options(&self, temperature: f32) -> GenerateOptions {
+        GenerateOptions {
+            model: self.config.model.clone(),
+            temperature,
+            format: None,
+        }
+    }
+
+    /// Chat for a JSON reply of type `T`, retrying as configured while it
+    /// doesn't parse
+    pub async fn chat_json<T: StructuredOutput>(
+        &self,
+        messages: Vec<ChatMessage>,
+        temperature: f32,
+    ) -> Result<T, AppError> {
+        chat_structured(
+            self.backend.as_ref(),
+            messages,
+            &self.options(temperature),
+            self.config.structured_retries,
+        )
+        .await
+    }
+
+    /// Write a poem on a theme in the requested form. A poem that misses a
+    /// measurable constraint is regenerated, up to `poem_retries` times, with
+    /// the misses pointed out; the attempt meeting the most constraints wins.
+    pub async fn generate_poem_from_text(
+        &self,
+        prompt: &str,
+        form: &PoemOptions,
+    ) -> Result<FittedPoem, AppError> {
+        let options = self.options(self.config.poem_temperature);
+        let base_prompt = self.poem_from_text_prompt(prompt, form)?;
+        let mut prompt = base_prompt.text.clone();
+        let mut best: Option<FittedPoem> = None;
+
+        for attempt in 1..=self.config.poem_retries + 1 {
+            let poem = self.backend.generate(&prompt, &options).await?;
+            let constraints = form.check(&poem);
+            let misses: Vec<&str> = constraints
+                .iter()
+                .filter(|c| !c.satisfied)
+                .map(|c| c.detail.as_str())
+                .collect();
+            if misses.is_empty() {
+                return Ok(FittedPoem {
+                    poem,
+                    attempts: attempt,
+                    constraints,
+                    prompt_version: base_prompt.version,
+                });
+            }
+
+            tracing::info!("Poem attempt {} missed: {}", attempt, misses.join("; "));
+            prompt = format!(
+                "{}\n\nAn earlier attempt did not fit ({}). Try again.",
+                base_prompt.text,
+                misses.join("; ")
+            );
+
+            let fitted = FittedPoem {
+                poem,
+                attempts: attempt,
+                constraints,
+                prompt_version: base_prompt.version.clone(),
+            };
+            if best
+                .as_ref()
+                .is_none_or(|best| fitted.satisfied() > best.satisfied())
+            {
+                best = Some(fitted);
+            }
+        }
+
+        let mut best = best.expect("at least one attempt is made");
+        best.attempts = self.config.poem_retries + 1;
+        Ok(best)
+    }
+
+    pub async fn generate_poem_from_image(
+        &self,
+        image_base64: &str,
+        custom_prompt: Option<&str>,
+    ) -> Result<Generated, AppError> {
+        let prompt = self.poem_from_image_prompt(custom_prompt)?;
+        let message = ChatMessage::user_with_image(prompt.text, image_base64);
+        let options = self.options(self.config.poem_temperature);
+        Ok(Generated {
+            output: self.backend.chat(&[message], &options).await?,
+            prompt_version: prompt.version,
+        })
+    }
+
+    /// Roast an image at the given intensity. A roast that hits the
+    /// blocklist, or that the classifier flags when enabled, is regenerated
+    /// up to `retries` times; if every attempt is rejected the request fails
+    /// with `AppError::UnsafeOutput` rather than returning any of them.
+    pub async fn generate_roast_from_image(
+        &self,
+        image_base64: &str,
+        intensity: RoastIntensity,
+        family_friendly: bool,
+    ) -> Result<SafeRoast, AppError> {
+        let prompt = self.roast_prompt(intensity, family_friendly)?;
+        let message = ChatMessage::user_with_image(prompt.text, image_base64);
+        let (roast, attempts) = self.safe_roast(vec![message], family_friendly).await?;
+
+        Ok(SafeRoast {
+            roast,
+            attempts,
+            prompt_version: prompt.version,
+        })
+    }
+
+    /// Reply in a `/chat/session` conversation. Roast sessions go through the
+    /// same safety checks as `/roast/image`.
+    pub async fn session_reply(
+        &self,
+        settings: &SessionSettings,
+        messages: Vec<ChatMessage>,
+    ) -> Result<String, AppError> {
+        match settings.kind {
+            SessionKind::Roast => Ok(self.safe_roast(messages, settings.family_friendly).await?.0),
+            SessionKind::Poem | SessionKind::Chat => {
+                let options = self.options(self.config.poem_temperature);
+                self.backend.chat(&messages, &options).await
+            }
+        }
+    }
+
+    /// Chat for a roast that passes the safety checks, returning it and the
+    /// generations made. After a rejection the last message is resent with a
+    /// note asking for a different roast.
+    async fn safe_roast(
+        &self,
+        mut messages: Vec<ChatMessage>,
+        family_friendly: bool,
+    ) -> Result<(String, u32), AppError> {
+        let options = self.options(self.config.roast_temperature);
+        let request = messages
+            .last()
+            .map(|m| m.content.clone())
+            .ok_or_else(|| AppError::Internal("No message to reply to".into()))?;
+        let attempts = self.roast_guard.retries + 1;
+
+        for attempt in 1..=attempts {
+            let roast = self.backend.chat(&messages, &options).await?;
+
+            let Some(reason) = self.roast_rejection(&roast, family_friendly).await? else {
+                return Ok((roast, attempt));
+            };
+
+            tracing::warn!("Roast attempt {} rejected: {}", attempt, reason);
+            if let Some(last) = messages.last_mut() {
+                last.content = format!(
+                    "{}\n\nAn earlier roast was rejected as unsuitable for a public booth. \
+                    Write a different one that only teases the scene, clothes, pose or objects.",
+                    request
+                );
+            }
+        }
+
+        Err(AppError::UnsafeOutput(format!(
+            "Every roast was rejected by the safety checks ({} attempts)",
+            attempts
+        )))
+    }
+
+    /// Why a roast can't be used, or `None` if it passes every check
+    async fn roast_rejection(
+        &self,
+        roast: &str,
+        family_friendly: bool,
+    ) -> Result<Option<String>, AppError> {
+        if let Some(term) = self.roast_guard.blocked_term(roast) {
+            return Ok(Some(format!("blocked term \"{}\"", term)));
+        }
+        if !self.roast_guard.classifier {
+            return Ok(None);
+        }
+
+        let prompt = self.prompts.render(
+            PromptKind::RoastCheck,
+            &[
+                ("roast", roast),
+                (
+                    "family_friendly",
+                    family_friendly_instruction(family_friendly),
+                ),
+            ],
+        )?;
+        let verdict: SafetyVerdict = self
+            .chat_json(
+                vec![ChatMessage::user(prompt.text)],
+                self.config.extraction_temperature,
+            )
+            .await
+            .map_err(|e| e.context("Failed to check roast"))?;
+
+        Ok((!verdict.safe).then(|| format!("classifier: {}", verdict.reason)))
+    }
+
+    // ========================================================================
+    // Streaming
+    // ========================================================================
+
+    /// Stream a poem from a text theme, token by token. The form is asked
+    /// for but not checked, since the tokens are already sent.
+    pub async fn stream_poem_from_text(
+        &self,
+        prompt: &str,
+        form: &PoemOptions,
+    ) -> Result<Generated<TokenStream>, AppError> {
+        let prompt = self.poem_from_text_prompt(prompt, form)?;
+        let options = self.options(self.config.poem_temperature);
+        Ok(Generated {
+            output: self.backend.generate_stream(&prompt.text, &options).await?,
+            prompt_version: prompt.version,
+        })
+    }
+
+    /// Stream a poem inspired by an image, token by token
+    pub async fn stream_poem_from_image(
+        &self,
+        image_base64: &str,
+        custom_prompt: Option<&str>,
+    ) -> Result<Generated<TokenStream>, AppError> {
+        let prompt = self.poem_from_image_prompt(custom_prompt)?;
+        let message = ChatMessage::user_with_image(prompt.text, image_base64);
+        let options = self.options(self.config.poem_temperature);
+        Ok(Generated {
+            output: self.backend.chat_stream(&[message], &options).await?,
+            prompt_version: prompt.version,
+        })
+    }
+
+    /// Stream a roast of an image. The roast is generated and checked exactly
+    /// like `generate_roast_from_image`, regenerating rejected ones, so nothing
+    /// is sent until one passes; its words then go out as tokens. If every
+    /// attempt is rejected the stream ends with `UnsafeOutput`.
+    pub async fn stream_roast_from_image(
+        &self,
+        image_base64: &str,
+        intensity: RoastIntensity,
+        family_friendly: bool,
+    ) -> Result<Generated<TokenStream>, AppError> {
+        let prompt = self.roast_prompt(intensity, family_friendly)?;
+        let message = ChatMessage::user_with_image(prompt.text, image_base64);
+
+        let generator = self.clone();
+        let roast = stream::once(async move {
+            generator
+                .safe_roast(vec![message], family_friendly)
+                .await
+                .map(|(roast, _)| roast)
+        })
+        .flat_map(|roast| match roast {
+            Ok(roast) => {
+                let words: Vec<_> = roast
+                    .split_inclusive(char::is_whitespace)
+                    .map(|word| Ok(word.to_string()))
+                    .collect();
+                stream::iter(words).boxed()
+            }
+            Err(e) => stream::once(future::ready(Err(e))).boxed(),
+        });
+
+        Ok(Generated {
+            output: Box::pin(roast),
+            prompt_version: prompt.version,
+        })
+    }
+
+    // ========================================================================
+    // Embeddings
+    // ========================================================================
+
+    /// Generate embedding for a single text
+    pub async fn embed_text(&self, text: &str) -> Result<Vec<f32>, AppError> {
+        let embeddings = self.embed_texts(&[text.to_string()]).await?;
+        embeddings
+            .into_iter()
+            .next()
+            .ok_or_else(|| AppError::Upstream("No embedding returned".into()))
+    }
+
+    /// Generate embeddings for multiple texts
+    pub async fn embed_texts(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, AppError> {
+        self.backend
+            .embed(texts, &self.config.embedding_model)
+            .await
+    }
+
+    /// Create TextEmbeddings from multiple texts
+    pub async fn create_text_embeddings(
+        &self,
+        texts: &[String],
+    ) -> Result<Vec<TextEmbedding>, AppError> {
+        let embeddings = self.embed_texts(texts).await?;
+
+        Ok(texts
+            .iter()
+            .zip(embeddings)
+            .map(|(text, embedding)| TextEmbedding {
+                text: text.clone(),
+                embedding,
+            })
+            .collect())
+    }
+
+    /// Describe an image with one tag per prompt slot (object, emotion, style),
+    /// plus a few lower-weighted extra tags if the model offers them.
+    /// With `vocabulary_only` the model is told to pick from `word_library`
+    /// instead of merely being offered it.
+    pub async fn extract_tags_from_image(
+        &self,
+        image_base64: &str,
+        word_library: &[String],
+        vocabulary_only: bool,
+    ) -> Result<Generated<Vec<Tag>>, AppError> {
+        let word_list = word_library.join(", ");
+        let guidance = if vocabulary_only {
+            "Choose only from these words"
+        } else {
+            "Suggested words (use these if they fit, or choose your own)"
+        };
+        let prompt = self.prompts.render(
+            PromptKind::ExtractTags,
+            &[
+                ("guidance", guidance),
+                ("word_list", &word_list),
+                ("max_extra", &MAX_EXTRA_TAGS.to_string()),
+            ],
+        )?;
+        let message = ChatMessage::user_with_image(prompt.text, image_base64);
+
+        // Lower temperature for more consistent output
+        let extracted: ExtractedTags = self
+            .chat_json(vec![message], self.config.extraction_temperature)
+            .await?;
+
+        Ok(Generated {
+            output: extracted.into_tags(),
+            prompt_version: prompt.version,
+        })
+    }
+
+    pub fn poem_from_text_prompt(
+        &self,
+        theme: &str,
+        form: &PoemOptions,
+    ) -> Result<RenderedPrompt, AppError> {
+        self.prompts.render(
+            PromptKind::PoemText,
+            &[("theme", theme), ("form", &form.instructions())],
+        )
+    }
+
+    pub fn roast_prompt(
+        &self,
+        intensity: RoastIntensity,
+        family_friendly: bool,
+    ) -> Result<RenderedPrompt, AppError> {
+        self.prompts.render(
+            PromptKind::Roast,
+            &[
+                ("intensity", intensity_instruction(intensity)),
+                (
+                    "family_friendly",
+                    family_friendly_instruction(family_friendly),
+                ),
+            ],
+        )
+    }
+
+    /// The caller's own prompt, if any, replaces the template
+    pub fn poem_from_image_prompt(
+        &self,
+        custom_prompt: Option<&str>,
+    ) -> Result<RenderedPrompt, AppError> {
+        match custom_prompt {
+            Some(text) => Ok(RenderedPrompt {
+                text: text.into(),
+                version: CUSTOM_PROMPT_VERSION.into(),
+            }),
+            None => self.prompts.render(PromptKind::PoemImage, &[]),
+        }
+    }
+}
+
+/// Extra words the model may add after the three slots
+const MAX_EXTRA_TAGS: usize = 3;
+
+/// Extra words matter less than the slots the prompt asked for
+const EXTRA_TAG_WEIGHT: f32 = 0.5;
+
+/// The extraction prompt's answer: one word per slot, plus optional extras
+#[derive(Debug, Deserialize)]
+struct ExtractedTags {
+    object: String,
+    emotion: String,
+    style: String,
+    #[serde(default)]
+    extra: Vec<String>,
+}
+
+impl StructuredOutput for ExtractedTags {
+    fn schema() -> Value {
+        let word = json!({"type": "string"});
+        json!({
+            "type": "object",
+            "properties": {
+                "object": word,
+                "emotion": word,
+                "style": word,
+                "extra": {"type": "array", "items": word, "maxItems": MAX_EXTRA_TAGS},
+            },
+            "required": ["object", "emotion", "style"],
+        })
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (slot, word) in TagCategory::SLOTS.iter().zip(self.slot_words()) {
+            if word.trim().is_empty() {
+                return Err(format!("\"{}\" must not be empty", slot.as_str()));
+            }
+            if word.contains(RESERVED_CHARS) {
+                return Err(format!(
+                    "\"{}\" must be a single word without ';', ':' or '*'",
+                    slot.as_str()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ExtractedTags {
+    fn slot_words(&self) -> [&str; 3] {
+        [&self.object, &self.emotion, &self.style]
+    }
+
+    /// Lowercase tags: the three slots, then up to `MAX_EXTRA_TAGS` extras;
+    /// extras the library CSV could not hold are skipped
+    fn into_tags(self) -> Vec<Tag> {
+        let mut tags = Tag::slots(self.slot_words().map(|word| word.trim().to_lowercase()));
+        tags.extend(
+            self.extra
+                .iter()
+                .map(|word| word.trim().to_lowercase())
+                .filter(|word| !word.is_empty() && !word.contains(RESERVED_CHARS))
+                .take(MAX_EXTRA_TAGS)
+                .map(|word| Tag::new(word).with_weight(EXTRA_TAG_WEIGHT)),
+        );
+        tags
+    }
+}
+
+fn intensity_instruction(intensity: RoastIntensity) -> &'static str {
+    match intensity {
+        RoastIntensity::Wholesome => {
+            "Be playful and humorous like a comedy roast - gentle teasing, \
+            not mean-spirited. Keep it light-hearted and fun."
+        }
+        RoastIntensity::Spicy => {
+            "Give it some bite - cheeky and sharp, the kind of burn friends trade."
+        }
+        RoastIntensity::Savage => {
+            "Go all out - an over-the-top, merciless burn, but aimed at the scene, \
+            not at who someone is."
+        }
+    }
+}
+
+fn family_friendly_instruction(family_friendly: bool) -> &'static str {
+    if family_friendly {
+        "Keep it family friendly: no swearing, innuendo or adult themes."
+    } else {
+        ""
+    }
+}
+
+/// `prompt_version` of output from a caller-supplied prompt
+const CUSTOM_PROMPT_VERSION: &str = "custom";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::service::structured::parse_structured;
+
+    #[test]
+    fn test_extracted_tags() {
+        let extracted: ExtractedTags =
+            parse_structured(r#"{"object": " Cake", "emotion": "HAPPY", "style": "whimsical"}"#)
+                .unwrap();
+        assert_eq!(
+            extracted.into_tags(),
+            Tag::slots(["cake", "happy", "whimsical"])
+        );
+    }
+
+    #[test]
+    fn test_extracted_tags_keeps_extras_and_skips_blanks() {
+        let extracted: ExtractedTags = parse_structured(
+            r#"{"object": "cake", "emotion": "happy", "style": "cute",
+                "extra": ["party", " ", "hat", "a;b", "balloon", "more"]}"#,
+        )
+        .unwrap();
+        let tags = extracted.into_tags();
+        let words: Vec<&str> = tags.iter().map(|t| t.word.as_str()).collect();
+        assert_eq!(words, ["cake", "happy", "cute", "party", "hat", "balloon"]);
+        assert_eq!(tags[2].category, Some(TagCategory::Style));
+        assert_eq!(tags[3].category, None);
+        assert_eq!(tags[3].weight, EXTRA_TAG_WEIGHT);
+    }
+
+    #[test]
+    fn test_extracted_tags_require_every_slot() {
+        assert!(
+            parse_structured::<ExtractedTags>(r#"{"object": "cake", "emotion": "happy"}"#).is_err()
+        );
+        assert!(parse_structured::<ExtractedTags>(
+            r#"{"object": "cake", "emotion": " ", "style": "cute"}"#
+        )
+        .unwrap_err()
+        .contains("emotion"));
+        assert!(parse_structured::<ExtractedTags>(
+            r#"{"object": "cake", "emotion": "happy", "style": "cute:pastel"}"#
+        )
+        .unwrap_err()
+        .contains("style"));
+        assert!(parse_structured::<ExtractedTags>("cake, happy, cute").is_err());
+    }
+}
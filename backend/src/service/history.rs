@@ -0,0 +1,697 @@
+//! Every generation saved to an embedded SQLite database for `/history`.
+//!
+//! An entry keeps the mode, prompt, model, output, matched hamster, latency and
+//! a small JPEG thumbnail of the photo, plus an unguessable share id for its
+//! public `/r/{share_id}` page. Entries outside the retention policy are
+//! deleted whenever a new one is saved. Saving never fails a request; errors are
+//! only logged.
+
+use base64::{engine::general_purpose, Engine};
+use image::ImageFormat;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
+
+use crate::config::HistoryConfig;
+use crate::error::AppError;
+use crate::service::jobs::unix_now;
+
+/// Kept in `PRAGMA user_version`; bump it with each schema change
+const SCHEMA_VERSION: i64 = 2;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    created_at INTEGER NOT NULL,
+    mode TEXT NOT NULL,
+    prompt TEXT,
+    prompt_version TEXT,
+    model TEXT NOT NULL,
+    output TEXT NOT NULL,
+    matched_image_url TEXT,
+    latency_ms INTEGER NOT NULL,
+    thumbnail BLOB,
+    share_id TEXT
+);
+CREATE INDEX IF NOT EXISTS history_created_at ON history (created_at);
+CREATE INDEX IF NOT EXISTS history_mode ON history (mode);
+";
+
+const ENTRY_COLUMNS: &str = "id, created_at, mode, prompt, prompt_version, model, output, \
+    matched_image_url, latency_ms, thumbnail IS NOT NULL, share_id";
+
+/// Characters of a share id: Crockford's base32, which drops the easily
+/// confused i, l, o and u
+const SHARE_ID_ALPHABET: &[u8; 32] = b"0123456789abcdefghjkmnpqrstvwxyz";
+
+/// Length of a share id; 10 characters are 50 random bits
+const SHARE_ID_LEN: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryMode {
+    PoemText,
+    PoemImage,
+    Roast,
+    HamsterMatch,
+    /// A `/chat/session` reply
+    Chat,
+}
+
+impl HistoryMode {
+    const ALL: [HistoryMode; 5] = [
+        HistoryMode::PoemText,
+        HistoryMode::PoemImage,
+        HistoryMode::Roast,
+        HistoryMode::HamsterMatch,
+        HistoryMode::Chat,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HistoryMode::PoemText => "poem_text",
+            HistoryMode::PoemImage => "poem_image",
+            HistoryMode::Roast => "roast",
+            HistoryMode::HamsterMatch => "hamster_match",
+            HistoryMode::Chat => "chat",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.as_str() == name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryEntry {
+    pub id: i64,
+    /// Unix timestamp, in seconds
+    pub created_at: u64,
+    pub mode: HistoryMode,
+    /// The theme, custom prompt or chat message the caller sent, if any
+    pub prompt: Option<String>,
+    pub prompt_version: Option<String>,
+    pub model: String,
+    pub output: String,
+    pub matched_image_url: Option<String>,
+    /// From the request arriving to the output being ready
+    pub latency_ms: u64,
+    /// Where to fetch the photo thumbnail, if one was saved
+    pub thumbnail_url: Option<String>,
+    /// Public id of the entry's `/r/{share_id}` page
+    pub share_id: String,
+}
+
+/// A generation to save
+#[derive(Debug, Clone)]
+struct NewEntry {
+    mode: HistoryMode,
+    prompt: Option<String>,
+    prompt_version: Option<String>,
+    model: String,
+    image_base64: Option<String>,
+    matched_image_url: Option<String>,
+}
+
+/// Shared handle to the history database; cheap to clone. When history is
+/// disabled there is no database and saving does nothing.
+#[derive(Clone)]
+pub struct HistoryStore {
+    db: Option<Arc<Mutex<Connection>>>,
+    config: HistoryConfig,
+}
+
+impl HistoryStore {
+    /// Open or create the database, then apply the retention policy
+    pub fn open(config: &HistoryConfig) -> Result<Self, String> {
+        if !config.enabled {
+            return Ok(Self {
+                db: None,
+                config: config.clone(),
+            });
+        }
+
+        let path = config.db_path.display();
+        let conn = Connection::open(&config.db_path)
+            .map_err(|e| format!("Failed to open {}: {}", path, e))?;
+        migrate(&conn).map_err(|e| format!("Failed to set up {}: {}", path, e))?;
+        assign_share_ids(&conn).map_err(|e| format!("Failed to set up {}: {}", path, e))?;
+        prune(&conn, config).map_err(|e| format!("Failed to prune {}: {}", path, e))?;
+
+        Ok(Self {
+            db: Some(Arc::new(Mutex::new(conn))),
+            config: config.clone(),
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.db.is_some()
+    }
+
+    /// Whether entries have public share pages
+    pub fn shares(&self) -> bool {
+        self.enabled() && self.config.share
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Connection>, AppError> {
+        let db = self
+            .db
+            .as_ref()
+            .ok_or_else(|| AppError::NotFound("History is disabled".into()))?;
+        Ok(db.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Start timing a generation; `Recorder::save` stores it once it is done
+    pub fn recorder(&self, mode: HistoryMode, model: &str) -> Recorder {
+        Recorder {
+            store: self.clone(),
+            entry: NewEntry {
+                mode,
+                prompt: None,
+                prompt_version: None,
+                model: model.to_string(),
+                image_base64: None,
+                matched_image_url: None,
+            },
+            started: Instant::now(),
+        }
+    }
+
+    /// One page of entries, newest first, and how many there are in all
+    pub fn list(
+        &self,
+        page: usize,
+        per_page: usize,
+        mode: Option<HistoryMode>,
+    ) -> Result<(Vec<HistoryEntry>, u64), AppError> {
+        let conn = self.lock()?;
+        let mode = mode.map(HistoryMode::as_str);
+
+        let total: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM history WHERE ?1 IS NULL OR mode = ?1",
+                params![mode],
+                |row| row.get(0),
+            )
+            .map_err(db_error)?;
+
+        let mut statement = conn
+            .prepare(&format!(
+                "SELECT {} FROM history WHERE ?1 IS NULL OR mode = ?1 \
+                ORDER BY id DESC LIMIT ?2 OFFSET ?3",
+                ENTRY_COLUMNS
+            ))
+            .map_err(db_error)?;
+        let offset = page.saturating_sub(1).saturating_mul(per_page);
+        let entries = statement
+            .query_map(
+                params![mode, per_page as i64, offset as i64],
+                entry_from_row,
+            )
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(db_error)?;
+
+        Ok((entries, total as u64))
+    }
+
+    pub fn get(&self, id: i64) -> Result<HistoryEntry, AppError> {
+        let conn = self.lock()?;
+        find(&conn, id)
+    }
+
+    /// The entry with this share id
+    pub fn find_shared(&self, share_id: &str) -> Result<HistoryEntry, AppError> {
+        if !self.shares() {
+            return Err(AppError::NotFound("Share pages are disabled".into()));
+        }
+
+        let conn = self.lock()?;
+        conn.query_row(
+            &format!("SELECT {} FROM history WHERE share_id = ?1", ENTRY_COLUMNS),
+            params![share_id],
+            entry_from_row,
+        )
+        .optional()
+        .map_err(db_error)?
+        .ok_or_else(|| AppError::NotFound(format!("Shared result {} not found", share_id)))
+    }
+
+    /// The saved JPEG thumbnail of an entry's photo
+    pub fn thumbnail(&self, id: i64) -> Result<Vec<u8>, AppError> {
+        let conn = self.lock()?;
+        conn.query_row(
+            "SELECT thumbnail FROM history WHERE id = ?1",
+            params![id],
+            |row| row.get::<_, Option<Vec<u8>>>(0),
+        )
+        .optional()
+        .map_err(db_error)?
+        .flatten()
+        .ok_or_else(|| AppError::NotFound(format!("No thumbnail for history entry {}", id)))
+    }
+
+    /// Delete an entry, returning it as it was
+    pub fn delete(&self, id: i64) -> Result<HistoryEntry, AppError> {
+        let conn = self.lock()?;
+        let entry = find(&conn, id)?;
+        conn.execute("DELETE FROM history WHERE id = ?1", params![id])
+            .map_err(db_error)?;
+        Ok(entry)
+    }
+
+    fn insert(
+        &self,
+        entry: &NewEntry,
+        output: &str,
+        latency_ms: u64,
+        thumbnail: Option<Vec<u8>>,
+    ) -> Result<String, AppError> {
+        let share_id = new_share_id()?;
+        let conn = self.lock()?;
+        conn.execute(
+            "INSERT INTO history (created_at, mode, prompt, prompt_version, model, output, \
+            matched_image_url, latency_ms, thumbnail, share_id) \
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                unix_now() as i64,
+                entry.mode.as_str(),
+                entry.prompt,
+                entry.prompt_version,
+                entry.model,
+                output,
+                entry.matched_image_url,
+                latency_ms as i64,
+                thumbnail,
+                share_id,
+            ],
+        )
+        .map_err(db_error)?;
+        prune(&conn, &self.config).map_err(db_error)?;
+
+        Ok(share_id)
+    }
+}
+
+/// A generation being timed. Fill in what is known with the builder methods,
+/// then `save` the output.
+pub struct Recorder {
+    store: HistoryStore,
+    entry: NewEntry,
+    started: Instant,
+}
+
+impl Recorder {
+    pub fn prompt(mut self, prompt: &str) -> Self {
+        self.entry.prompt = Some(prompt.to_string());
+        self
+    }
+
+    pub fn prompt_version(mut self, version: &str) -> Self {
+        self.entry.prompt_version = Some(version.to_string());
+        self
+    }
+
+    /// The photo, saved as a thumbnail
+    pub fn image(mut self, image_base64: &str) -> Self {
+        self.entry.image_base64 = Some(image_base64.to_string());
+        self
+    }
+
+    pub fn matched_image(mut self, url: Option<String>) -> Self {
+        self.entry.matched_image_url = url;
+        self
+    }
+
+    /// Save the entry with its output and latency, returning its share id if
+    /// share pages are on. Errors are logged, never returned, so a full disk
+    /// doesn't cost a visitor their roast.
+    pub async fn save(self, output: &str) -> Option<String> {
+        if !self.store.enabled() {
+            return None;
+        }
+        let shares = self.store.shares();
+
+        let latency_ms = self.started.elapsed().as_millis() as u64;
+        let output = output.to_string();
+        let Recorder { store, entry, .. } = self;
+
+        // Decoding and resizing the photo is CPU-bound, and SQLite blocks
+        let saved = tokio::task::spawn_blocking(move || {
+            let thumbnail = entry
+                .image_base64
+                .as_deref()
+                .filter(|_| store.config.thumbnail_px > 0)
+                .and_then(
+                    |image| match make_thumbnail(image, store.config.thumbnail_px) {
+                        Ok(thumbnail) => Some(thumbnail),
+                        Err(e) => {
+                            tracing::debug!("No history thumbnail: {}", e);
+                            None
+                        }
+                    },
+                );
+            store.insert(&entry, &output, latency_ms, thumbnail)
+        })
+        .await;
+
+        match saved {
+            Ok(Ok(share_id)) => shares.then_some(share_id),
+            Ok(Err(e)) => {
+                tracing::warn!("Failed to save history entry: {}", e);
+                None
+            }
+            Err(e) => {
+                tracing::warn!("History task failed: {}", e);
+                None
+            }
+        }
+    }
+}
+
+fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version > SCHEMA_VERSION {
+        return Err(rusqlite::Error::InvalidParameterName(format!(
+            "schema version {} is newer than this build's {}",
+            version, SCHEMA_VERSION
+        )));
+    }
+
+    conn.execute_batch(SCHEMA)?;
+    if version == 1 {
+        conn.execute_batch("ALTER TABLE history ADD COLUMN share_id TEXT")?;
+    }
+    conn.execute_batch("CREATE UNIQUE INDEX IF NOT EXISTS history_share_id ON history (share_id)")?;
+    conn.pragma_update(None, "user_version", SCHEMA_VERSION)
+}
+
+/// Give entries saved before share ids existed one
+fn assign_share_ids(conn: &Connection) -> Result<(), AppError> {
+    let ids = conn
+        .prepare("SELECT id FROM history WHERE share_id IS NULL")
+        .and_then(|mut statement| {
+            statement
+                .query_map([], |row| row.get::<_, i64>(0))?
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .map_err(db_error)?;
+
+    for id in ids {
+        conn.execute(
+            "UPDATE history SET share_id = ?1 WHERE id = ?2",
+            params![new_share_id()?, id],
+        )
+        .map_err(db_error)?;
+    }
+    Ok(())
+}
+
+/// A random id short enough to type, too long to guess
+fn new_share_id() -> Result<String, AppError> {
+    let mut bytes = [0u8; SHARE_ID_LEN];
+    getrandom::fill(&mut bytes)
+        .map_err(|e| AppError::Internal(format!("Failed to generate share id: {}", e)))?;
+    // 256 is a multiple of 32, so every character is equally likely
+    Ok(bytes
+        .iter()
+        .map(|b| SHARE_ID_ALPHABET[*b as usize % SHARE_ID_ALPHABET.len()] as char)
+        .collect())
+}
+
+/// Delete entries past `retention_days`, then the oldest beyond `max_entries`
+fn prune(conn: &Connection, config: &HistoryConfig) -> rusqlite::Result<usize> {
+    let mut deleted = 0;
+    if config.retention_days > 0 {
+        let cutoff = unix_now().saturating_sub(config.retention_days * 24 * 60 * 60);
+        deleted += conn.execute(
+            "DELETE FROM history WHERE created_at < ?1",
+            params![cutoff as i64],
+        )?;
+    }
+    if config.max_entries > 0 {
+        deleted += conn.execute(
+            "DELETE FROM history WHERE id NOT IN \
+            (SELECT id FROM history ORDER BY id DESC LIMIT ?1)",
+            params![config.max_entries as i64],
+        )?;
+    }
+
+    Ok(deleted)
+}
+
+fn find(conn: &Connection, id: i64) -> Result<HistoryEntry, AppError> {
+    conn.query_row(
+        &format!("SELECT {} FROM history WHERE id = ?1", ENTRY_COLUMNS),
+        params![id],
+        entry_from_row,
+    )
+    .optional()
+    .map_err(db_error)?
+    .ok_or_else(|| AppError::NotFound(format!("History entry {} not found", id)))
+}
+
+fn entry_from_row(row: &Row) -> rusqlite::Result<HistoryEntry> {
+    let id: i64 = row.get(0)?;
+    let mode: String = row.get(2)?;
+    let has_thumbnail: bool = row.get(9)?;
+
+    Ok(HistoryEntry {
+        id,
+        created_at: row.get::<_, i64>(1)? as u64,
+        mode: HistoryMode::from_name(&mode).ok_or_else(|| {
+            rusqlite::Error::InvalidColumnType(2, mode, rusqlite::types::Type::Text)
+        })?,
+        prompt: row.get(3)?,
+        prompt_version: row.get(4)?,
+        model: row.get(5)?,
+        output: row.get(6)?,
+        matched_image_url: row.get(7)?,
+        latency_ms: row.get::<_, i64>(8)? as u64,
+        thumbnail_url: has_thumbnail.then(|| format!("/history/{}/thumbnail", id)),
+        share_id: row.get(10)?,
+    })
+}
+
+fn db_error(e: rusqlite::Error) -> AppError {
+    AppError::Io(format!("History database error: {}", e))
+}
+
+/// JPEG no larger than `max_px` on either side
+fn make_thumbnail(image_base64: &str, max_px: u32) -> Result<Vec<u8>, String> {
+    let bytes = general_purpose::STANDARD
+        .decode(image_base64.trim())
+        .map_err(|e| format!("invalid base64: {}", e))?;
+    let image = image::load_from_memory(&bytes).map_err(|e| format!("unreadable image: {}", e))?;
+
+    let mut jpeg = Vec::new();
+    image
+        .thumbnail(max_px, max_px)
+        .to_rgb8()
+        .write_to(&mut Cursor::new(&mut jpeg), ImageFormat::Jpeg)
+        .map_err(|e| format!("failed to encode thumbnail: {}", e))?;
+
+    Ok(jpeg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(dir: &tempfile::TempDir, max_entries: usize) -> HistoryStore {
+        HistoryStore::open(&HistoryConfig {
+            db_path: dir.path().join("history.db"),
+            max_entries,
+            ..HistoryConfig::default()
+        })
+        .unwrap()
+    }
+
+    fn png_base64(width: u32, height: u32) -> String {
+        let mut bytes = Vec::new();
+        image::RgbImage::new(width, height)
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        general_purpose::STANDARD.encode(bytes)
+    }
+
+    #[tokio::test]
+    async fn test_save_list_and_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store(&dir, 0);
+        store
+            .recorder(HistoryMode::PoemText, "test-model")
+            .prompt("spring")
+            .save("Blossoms fall")
+            .await;
+        store
+            .recorder(HistoryMode::Roast, "test-model")
+            .prompt_version("roast@12345678")
+            .save("Nice hat.")
+            .await;
+
+        let (entries, total) = store.list(1, 10, None).unwrap();
+        assert_eq!(total, 2);
+        assert_eq!(entries[0].output, "Nice hat.");
+        assert_eq!(entries[1].prompt.as_deref(), Some("spring"));
+        assert_eq!(entries[1].mode, HistoryMode::PoemText);
+
+        let (entries, total) = store.list(1, 10, Some(HistoryMode::PoemText)).unwrap();
+        assert_eq!((entries.len(), total), (1, 1));
+        let (entries, total) = store.list(2, 1, None).unwrap();
+        assert_eq!((entries[0].output.as_str(), total), ("Blossoms fall", 2));
+
+        let deleted = store.delete(entries[0].id).unwrap();
+        assert!(matches!(store.get(deleted.id), Err(AppError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_thumbnail_is_resized_jpeg() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store(&dir, 0);
+        store
+            .recorder(HistoryMode::Roast, "test-model")
+            .image(&png_base64(1024, 512))
+            .save("Nice hat.")
+            .await;
+        store
+            .recorder(HistoryMode::Roast, "test-model")
+            .image("aGVsbG8=")
+            .save("Not an image")
+            .await;
+
+        let (entries, _) = store.list(1, 10, None).unwrap();
+        assert!(entries[0].thumbnail_url.is_none());
+        let with_photo = &entries[1];
+        assert_eq!(
+            with_photo.thumbnail_url,
+            Some(format!("/history/{}/thumbnail", with_photo.id))
+        );
+
+        let thumbnail = store.thumbnail(with_photo.id).unwrap();
+        let image = image::load_from_memory_with_format(&thumbnail, ImageFormat::Jpeg).unwrap();
+        assert_eq!((image.width(), image.height()), (256, 128));
+    }
+
+    #[tokio::test]
+    async fn test_max_entries_drops_oldest() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store(&dir, 2);
+        for output in ["one", "two", "three"] {
+            store
+                .recorder(HistoryMode::Chat, "test-model")
+                .save(output)
+                .await;
+        }
+
+        let (entries, total) = store.list(1, 10, None).unwrap();
+        assert_eq!(total, 2);
+        let outputs: Vec<&str> = entries.iter().map(|e| e.output.as_str()).collect();
+        assert_eq!(outputs, ["three", "two"]);
+    }
+
+    #[test]
+    fn test_retention_and_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store(&dir, 0);
+        {
+            let conn = store.lock().unwrap();
+            conn.execute(
+                "INSERT INTO history (created_at, mode, model, output, latency_ms) \
+                VALUES (?1, 'roast', 'm', 'old', 1), (?2, 'roast', 'm', 'new', 1)",
+                params![(unix_now() - 31 * 24 * 60 * 60) as i64, unix_now() as i64],
+            )
+            .unwrap();
+        }
+        drop(store);
+
+        // Opening again applies the retention policy to what is already there
+        let store = self::store(&dir, 0);
+        let (entries, _) = store.list(1, 10, None).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].output, "new");
+    }
+
+    #[tokio::test]
+    async fn test_share_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store(&dir, 0);
+        let first = store
+            .recorder(HistoryMode::Roast, "test-model")
+            .save("Nice hat.")
+            .await
+            .unwrap();
+        let second = store
+            .recorder(HistoryMode::Roast, "test-model")
+            .save("Nice shoes.")
+            .await
+            .unwrap();
+
+        assert_ne!(first, second);
+        assert_eq!(first.len(), SHARE_ID_LEN);
+        assert!(first.bytes().all(|b| SHARE_ID_ALPHABET.contains(&b)));
+        assert_eq!(store.find_shared(&first).unwrap().output, "Nice hat.");
+        assert!(matches!(
+            store.find_shared("0000000000"),
+            Err(AppError::NotFound(_))
+        ));
+
+        let private = HistoryStore::open(&HistoryConfig {
+            db_path: dir.path().join("private.db"),
+            share: false,
+            ..HistoryConfig::default()
+        })
+        .unwrap();
+        let share_id = private
+            .recorder(HistoryMode::Roast, "test-model")
+            .save("Nice hat.")
+            .await;
+        assert_eq!(share_id, None);
+        assert_eq!(private.list(1, 10, None).unwrap().1, 1);
+    }
+
+    #[test]
+    fn test_version_1_database_gets_share_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let conn = Connection::open(dir.path().join("history.db")).unwrap();
+            conn.execute_batch(&SCHEMA.replace(",\n    share_id TEXT", ""))
+                .unwrap();
+            conn.execute(
+                "INSERT INTO history (created_at, mode, model, output, latency_ms) \
+                VALUES (?1, 'roast', 'm', 'old', 1)",
+                params![unix_now() as i64],
+            )
+            .unwrap();
+            conn.pragma_update(None, "user_version", 1).unwrap();
+        }
+
+        let store = store(&dir, 0);
+        let (entries, _) = store.list(1, 10, None).unwrap();
+        assert_eq!(entries[0].share_id.len(), SHARE_ID_LEN);
+        let version: i64 = store
+            .lock()
+            .unwrap()
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+    }
+
+    #[tokio::test]
+    async fn test_disabled_saves_nothing() {
+        let store = HistoryStore::open(&HistoryConfig {
+            enabled: false,
+            ..HistoryConfig::default()
+        })
+        .unwrap();
+        store
+            .recorder(HistoryMode::Roast, "test-model")
+            .save("Nice hat.")
+            .await;
+        assert!(matches!(
+            store.list(1, 10, None),
+            Err(AppError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,394 @@
+//! In-memory registry of background admin jobs, such as library generation.
+//!
+//! Jobs live only as long as the process; finished jobs beyond `MAX_FINISHED_JOBS`
+//! are forgotten oldest first.
+
+use serde::Serialize;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
+
+use crate::error::AppError;
+use crate::lib::tags::Tag;
+use crate::models::ErrorDetail;
+use crate::service::library_generator::GenerateMode;
+
+/// Finished jobs kept around for polling
+const MAX_FINISHED_JOBS: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_finished(self) -> bool {
+        matches!(
+            self,
+            JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageStatus {
+    Pending,
+    Processing,
+    Done,
+    Failed,
+    /// Same file as the library row already describes; kept as is
+    Unchanged,
+    /// The library row is locked against regeneration; kept as is
+    Locked,
+}
+
+/// Progress of one image within a job
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageProgress {
+    pub filename: String,
+    pub status: ImageStatus,
+    /// Extracted tags once done, or the library's tags if unchanged or locked
+    pub tags: Vec<Tag>,
+    /// SHA-256 of the file, once read
+    pub hash: Option<String>,
+    pub error: Option<ErrorDetail>,
+}
+
+impl ImageProgress {
+    pub fn pending(filename: String) -> Self {
+        Self {
+            filename,
+            status: ImageStatus::Pending,
+            tags: Vec::new(),
+            hash: None,
+            error: None,
+        }
+    }
+}
+
+/// A library generation run over a range of images
+#[derive(Debug, Clone, Serialize)]
+pub struct Job {
+    pub id: String,
+    pub status: JobStatus,
+    /// Unix timestamps, in seconds
+    pub created_at: u64,
+    pub started_at: Option<u64>,
+    pub finished_at: Option<u64>,
+    /// How many times the job has been run, including retries
+    pub attempts: u32,
+    pub mode: GenerateMode,
+    pub csv_path: String,
+    pub range: String,
+    pub total_images_in_folder: usize,
+    pub processed_images: usize,
+    pub skipped_images: usize,
+    pub unchanged_images: usize,
+    pub locked_images: usize,
+    pub images: Vec<ImageProgress>,
+    /// Why the whole job failed, if it did
+    pub error: Option<ErrorDetail>,
+}
+
+impl Job {
+    pub fn new(
+        mode: GenerateMode,
+        csv_path: String,
+        range: String,
+        total_images_in_folder: usize,
+        filenames: Vec<String>,
+    ) -> Self {
+        Self {
+            id: String::new(),
+            status: JobStatus::Queued,
+            created_at: unix_now(),
+            started_at: None,
+            finished_at: None,
+            attempts: 1,
+            mode,
+            csv_path,
+            range,
+            total_images_in_folder,
+            processed_images: 0,
+            skipped_images: 0,
+            unchanged_images: 0,
+            locked_images: 0,
+            images: filenames.into_iter().map(ImageProgress::pending).collect(),
+            error: None,
+        }
+    }
+
+    /// Recompute the processed/skipped counters from per-image status
+    pub fn refresh_counts(&mut self) {
+        self.processed_images = self.count(ImageStatus::Done);
+        self.skipped_images = self.count(ImageStatus::Failed);
+        self.unchanged_images = self.count(ImageStatus::Unchanged);
+        self.locked_images = self.count(ImageStatus::Locked);
+    }
+
+    pub fn finish(&mut self, status: JobStatus, error: Option<ErrorDetail>) {
+        self.status = status;
+        self.error = error;
+        self.finished_at = Some(unix_now());
+        self.refresh_counts();
+    }
+
+    pub fn count(&self, status: ImageStatus) -> usize {
+        self.images.iter().filter(|i| i.status == status).count()
+    }
+}
+
+struct JobEntry {
+    job: Job,
+    cancel: watch::Sender<bool>,
+}
+
+#[derive(Default)]
+struct Jobs {
+    entries: Vec<JobEntry>,
+    next_id: u64,
+}
+
+/// Shared handle to the job registry; cheap to clone
+#[derive(Clone, Default)]
+pub struct JobStore {
+    inner: Arc<Mutex<Jobs>>,
+}
+
+impl JobStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Jobs> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Register a queued job and return it with its id.
+    /// Only one job may be queued or running at a time, since they all write the same CSV.
+    pub fn insert(&self, mut job: Job) -> Result<Job, AppError> {
+        let mut jobs = self.lock();
+        ensure_idle(&jobs)?;
+
+        jobs.next_id += 1;
+        job.id = format!("job-{}-{}", job.created_at, jobs.next_id);
+
+        let (cancel, _) = watch::channel(false);
+        jobs.entries.push(JobEntry {
+            job: job.clone(),
+            cancel,
+        });
+        prune(&mut jobs);
+
+        Ok(job)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Job, AppError> {
+        let jobs = self.lock();
+        find(&jobs, id).map(|entry| entry.job.clone())
+    }
+
+    /// All known jobs, newest first
+    pub fn list(&self) -> Vec<Job> {
+        let jobs = self.lock();
+        jobs.entries.iter().rev().map(|e| e.job.clone()).collect()
+    }
+
+    /// Apply `f` to a job under the lock and return its result
+    pub fn update<T>(&self, id: &str, f: impl FnOnce(&mut Job) -> T) -> Result<T, AppError> {
+        let mut jobs = self.lock();
+        let entry = find_mut(&mut jobs, id)?;
+        Ok(f(&mut entry.job))
+    }
+
+    /// Receiver that flips to `true` when the job is cancelled
+    pub fn cancellation(&self, id: &str) -> Result<watch::Receiver<bool>, AppError> {
+        let jobs = self.lock();
+        find(&jobs, id).map(|entry| entry.cancel.subscribe())
+    }
+
+    /// Ask a queued or running job to stop. The worker marks it cancelled once
+    /// the image in flight is abandoned.
+    pub fn cancel(&self, id: &str) -> Result<Job, AppError> {
+        let mut jobs = self.lock();
+        let entry = find_mut(&mut jobs, id)?;
+
+        if entry.job.status.is_finished() {
+            return Err(AppError::Conflict(format!(
+                "Job {} has already finished",
+                id
+            )));
+        }
+
+        entry.cancel.send_replace(true);
+        Ok(entry.job.clone())
+    }
+
+    /// Re-queue the failed and never-processed images of a finished job.
+    /// Images that already succeeded, were unchanged or locked keep their tags.
+    pub fn retry(&self, id: &str) -> Result<Job, AppError> {
+        let mut jobs = self.lock();
+        ensure_idle(&jobs)?;
+
+        let entry = find_mut(&mut jobs, id)?;
+        let job = &mut entry.job;
+
+        let mut retried = 0;
+        for image in &mut job.images {
+            if matches!(
+                image.status,
+                ImageStatus::Pending | ImageStatus::Processing | ImageStatus::Failed
+            ) {
+                *image = ImageProgress::pending(std::mem::take(&mut image.filename));
+                retried += 1;
+            }
+        }
+        if retried == 0 {
+            return Err(AppError::Validation(format!(
+                "Job {} has no failed images to retry",
+                id
+            )));
+        }
+
+        job.status = JobStatus::Queued;
+        job.attempts += 1;
+        job.started_at = None;
+        job.finished_at = None;
+        job.error = None;
+        job.refresh_counts();
+        entry.cancel = watch::channel(false).0;
+
+        Ok(entry.job.clone())
+    }
+}
+
+fn find<'a>(jobs: &'a Jobs, id: &str) -> Result<&'a JobEntry, AppError> {
+    jobs.entries
+        .iter()
+        .find(|e| e.job.id == id)
+        .ok_or_else(|| AppError::NotFound(format!("Job {} not found", id)))
+}
+
+fn find_mut<'a>(jobs: &'a mut Jobs, id: &str) -> Result<&'a mut JobEntry, AppError> {
+    jobs.entries
+        .iter_mut()
+        .find(|e| e.job.id == id)
+        .ok_or_else(|| AppError::NotFound(format!("Job {} not found", id)))
+}
+
+fn ensure_idle(jobs: &Jobs) -> Result<(), AppError> {
+    match jobs.entries.iter().find(|e| !e.job.status.is_finished()) {
+        Some(active) => Err(AppError::Conflict(format!(
+            "Job {} is already {}",
+            active.job.id,
+            if active.job.status == JobStatus::Queued {
+                "queued"
+            } else {
+                "running"
+            }
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Forget the oldest finished jobs beyond the retention limit
+fn prune(jobs: &mut Jobs) {
+    let finished = jobs
+        .entries
+        .iter()
+        .filter(|e| e.job.status.is_finished())
+        .count();
+    let mut excess = finished.saturating_sub(MAX_FINISHED_JOBS);
+
+    jobs.entries.retain(|e| {
+        if excess > 0 && e.job.status.is_finished() {
+            excess -= 1;
+            false
+        } else {
+            true
+        }
+    });
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(files: &[&str]) -> Job {
+        Job::new(
+            GenerateMode::Merge,
+            "library.csv".into(),
+            "0-1".into(),
+            files.len(),
+            files.iter().map(|f| f.to_string()).collect(),
+        )
+    }
+
+    #[test]
+    fn test_only_one_active_job() {
+        let store = JobStore::new();
+        let first = store.insert(job(&["a.png"])).unwrap();
+
+        let err = store.insert(job(&["b.png"])).unwrap_err();
+        assert_eq!(err.code(), "conflict");
+
+        store
+            .update(&first.id, |job| job.finish(JobStatus::Completed, None))
+            .unwrap();
+        assert!(store.insert(job(&["b.png"])).is_ok());
+        assert_eq!(store.list().len(), 2);
+    }
+
+    #[test]
+    fn test_retry_requeues_unfinished_images() {
+        let store = JobStore::new();
+        let id = store.insert(job(&["a.png", "b.png", "c.png"])).unwrap().id;
+
+        assert_eq!(store.retry(&id).unwrap_err().code(), "conflict");
+
+        store
+            .update(&id, |job| {
+                job.images[0].status = ImageStatus::Done;
+                job.images[0].tags = Tag::slots(["a", "b", "c"]);
+                job.images[1].status = ImageStatus::Failed;
+                job.finish(JobStatus::Cancelled, None);
+            })
+            .unwrap();
+
+        let retried = store.retry(&id).unwrap();
+        assert_eq!(retried.status, JobStatus::Queued);
+        assert_eq!(retried.attempts, 2);
+        assert_eq!(retried.processed_images, 1);
+        assert_eq!(retried.skipped_images, 0);
+        assert_eq!(retried.images[0].tags.len(), 3);
+        assert!(retried.images[1..]
+            .iter()
+            .all(|i| i.status == ImageStatus::Pending));
+        assert!(!*store.cancellation(&id).unwrap().borrow());
+    }
+
+    #[test]
+    fn test_cancel_and_unknown_jobs() {
+        let store = JobStore::new();
+        let id = store.insert(job(&["a.png"])).unwrap().id;
+        let cancelled = store.cancellation(&id).unwrap();
+
+        store.cancel(&id).unwrap();
+        assert!(*cancelled.borrow());
+
+        assert_eq!(store.get("job-0-0").unwrap_err().code(), "not_found");
+    }
+}
@@ -0,0 +1,301 @@
+//! Background worker behind `/admin/generate-library`: tags each image with the
+//! vision model and writes the library CSV once the run is over.
+
+use base64::{engine::general_purpose, Engine};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::error::AppError;
+use crate::lib::image_library::{
+    get_word_library, image_hash, lock_library, read_library_rows, write_library_rows,
+    ImageLibraryRow, IMAGE_URL_PREFIX,
+};
+use crate::service::jobs::{unix_now, ImageProgress, ImageStatus, Job, JobStatus, JobStore};
+use crate::service::{GenerationService, LocalEmbeddingService};
+
+/// How a generation run combines its results with the existing library CSV
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GenerateMode {
+    /// Only process images that are new or whose file changed, and upsert their
+    /// rows; every other row is kept
+    #[default]
+    Merge,
+    /// Process every image in the range and replace the library with the results
+    Replace,
+}
+
+/// Everything a generation job needs, detached from the request that queued it
+#[derive(Clone)]
+pub struct LibraryGenerator {
+    pub jobs: JobStore,
+    pub generator: GenerationService,
+    pub images_dir: PathBuf,
+    pub csv_path: PathBuf,
+}
+
+impl LibraryGenerator {
+    /// Run a queued job on a background task
+    pub fn spawn(&self, id: String) {
+        let worker = self.clone();
+        tokio::spawn(async move { worker.run(&id).await });
+    }
+
+    /// Process every pending image of the job, then write the words of every
+    /// described image, including ones done by earlier attempts, to the CSV.
+    pub async fn run(&self, id: &str) {
+        let jobs = &self.jobs;
+        let Ok(mut cancel) = jobs.cancellation(id) else {
+            return;
+        };
+        let Ok((count, mode)) = jobs.update(id, |job| {
+            job.status = JobStatus::Running;
+            job.started_at = Some(unix_now());
+            (job.images.len(), job.mode)
+        }) else {
+            return;
+        };
+
+        let word_library = get_word_library();
+        let existing = match self.existing_rows(mode) {
+            Ok(rows) => rows,
+            Err(e) => {
+                tracing::error!("[{}] Library generation failed: {}", id, e);
+                let _ = jobs.update(id, |job| job.finish(JobStatus::Failed, Some(e.detail())));
+                return;
+            }
+        };
+        let mut last_error = None;
+        let mut cancelled = false;
+
+        for index in 0..count {
+            if *cancel.borrow() {
+                cancelled = true;
+                break;
+            }
+
+            let filename = jobs.update(id, |job| {
+                let image = &mut job.images[index];
+                (image.status == ImageStatus::Pending).then(|| {
+                    image.status = ImageStatus::Processing;
+                    image.filename.clone()
+                })
+            });
+            let Ok(Some(filename)) = filename else {
+                continue;
+            };
+
+            tracing::info!(
+                "[{}] Processing image {}/{}: {}",
+                id,
+                index + 1,
+                count,
+                filename
+            );
+
+            let result = tokio::select! {
+                result = self.process(&filename, existing.get(&filename), &word_library) => result,
+                _ = cancel.wait_for(|cancelled| *cancelled) => {
+                    // Abandon the model call; the image can be retried later
+                    let _ = jobs.update(id, |job| job.images[index].status = ImageStatus::Pending);
+                    cancelled = true;
+                    break;
+                }
+            };
+
+            let _ = jobs.update(id, |job| {
+                let image = &mut job.images[index];
+                match result {
+                    Ok(processed) => {
+                        tracing::info!(
+                            "✓ {:?} {}: {:?}",
+                            processed.status,
+                            filename,
+                            processed.tags
+                        );
+                        *image = processed;
+                    }
+                    Err(e) => {
+                        tracing::error!("Failed to process {}: {}", filename, e);
+                        image.status = ImageStatus::Failed;
+                        image.error = Some(e.detail());
+                        last_error = Some(e);
+                    }
+                }
+                job.refresh_counts();
+            });
+        }
+
+        if cancelled {
+            tracing::info!("[{}] Library generation cancelled", id);
+            let _ = jobs.update(id, |job| job.finish(JobStatus::Cancelled, None));
+            return;
+        }
+
+        let Ok(job) = jobs.get(id) else {
+            return;
+        };
+        let done = job.count(ImageStatus::Done);
+
+        // Report why the last image failed, e.g. an unreachable or confused model
+        if done == 0 && job.skipped_images > 0 {
+            let context = format!(
+                "No images were successfully processed in the given range ({} skipped)",
+                job.skipped_images
+            );
+            let error = last_error
+                .map(|e| e.context(&context))
+                .unwrap_or(AppError::Internal(context));
+            tracing::error!("[{}] Library generation failed: {}", id, error);
+            let _ = jobs.update(id, |job| {
+                job.finish(JobStatus::Failed, Some(error.detail()))
+            });
+            return;
+        }
+
+        if done > 0 {
+            let rows = match self.merged_rows(&job) {
+                Ok(rows) => rows,
+                Err(e) => {
+                    tracing::error!("[{}] Library generation failed: {}", id, e);
+                    let _ = jobs.update(id, |job| job.finish(JobStatus::Failed, Some(e.detail())));
+                    return;
+                }
+            };
+
+            tracing::info!(
+                "✓ Generated library with {} images ({} new or changed, {} skipped)",
+                rows.len(),
+                done,
+                job.skipped_images
+            );
+
+            // Serve the new library without a restart
+            if let Err(e) = LocalEmbeddingService::new().start_reload() {
+                tracing::error!("Failed to start library reload: {}", e);
+            }
+        } else {
+            tracing::info!("[{}] Library is already up to date", id);
+        }
+
+        let _ = jobs.update(id, |job| job.finish(JobStatus::Completed, None));
+    }
+
+    /// Current library rows by file name, consulted to skip unchanged and locked
+    /// images. Replace mode only honours locks.
+    fn existing_rows(
+        &self,
+        mode: GenerateMode,
+    ) -> Result<HashMap<String, ImageLibraryRow>, AppError> {
+        Ok(self
+            .library_rows(mode)?
+            .into_iter()
+            .filter(|row| mode == GenerateMode::Merge || row.locked)
+            .filter_map(|row| Some((row.filename()?.to_string(), row)))
+            .collect())
+    }
+
+    /// Rows of the library on disk. A replace run can start over from an unreadable file.
+    fn library_rows(&self, mode: GenerateMode) -> Result<Vec<ImageLibraryRow>, AppError> {
+        match read_library_rows(&self.csv_path) {
+            Ok(rows) => Ok(rows),
+            Err(e) if mode == GenerateMode::Replace => {
+                tracing::warn!("Replacing unreadable library: {}", e);
+                Ok(Vec::new())
+            }
+            Err(e) => Err(AppError::Io(e)),
+        }
+    }
+
+    /// Read, hash and, unless the library already has it, describe one image
+    async fn process(
+        &self,
+        filename: &str,
+        existing: Option<&ImageLibraryRow>,
+        word_library: &[String],
+    ) -> Result<ImageProgress, AppError> {
+        let image_data = tokio::fs::read(self.images_dir.join(filename))
+            .await
+            .map_err(|e| AppError::Io(format!("Failed to read {}: {}", filename, e)))?;
+        let hash = image_hash(&image_data);
+
+        let mut progress = ImageProgress::pending(filename.to_string());
+        progress.hash = Some(hash.clone());
+
+        if let Some(row) = existing {
+            let kept = if row.locked {
+                Some(ImageStatus::Locked)
+            } else if row.hash == hash {
+                Some(ImageStatus::Unchanged)
+            } else {
+                None
+            };
+            if let Some(status) = kept {
+                progress.status = status;
+                progress.tags = row.tags.clone();
+                return Ok(progress);
+            }
+        }
+
+        let image_base64 = general_purpose::STANDARD.encode(&image_data);
+        progress.tags = self
+            .generator
+            .extract_tags_from_image(&image_base64, word_library, false)
+            .await
+            .map_err(|e| e.context(filename))?
+            .output;
+        progress.status = ImageStatus::Done;
+        Ok(progress)
+    }
+
+    /// The library after this job: freshly described images upserted into the
+    /// rows on disk (or replacing them), locked rows untouched, rows for images
+    /// that no longer exist dropped. Rows keep their order in the file, so hand
+    /// edits and diffs stay readable, and new images go at the end. Written
+    /// atomically.
+    fn merged_rows(&self, job: &Job) -> Result<Vec<ImageLibraryRow>, AppError> {
+        // Re-read under the lock so edits made while the job ran are not lost
+        let _guard = lock_library();
+        let mut rows: Vec<ImageLibraryRow> = self
+            .library_rows(job.mode)?
+            .into_iter()
+            .filter(|row| job.mode == GenerateMode::Merge || row.locked)
+            .collect();
+
+        for image in &job.images {
+            if image.status != ImageStatus::Done {
+                continue;
+            }
+            let image_url = format!("{}{}", IMAGE_URL_PREFIX, image.filename);
+            let described = ImageLibraryRow {
+                image_url,
+                tags: image.tags.clone(),
+                hash: image.hash.clone().unwrap_or_default(),
+                locked: false,
+            };
+            match rows
+                .iter_mut()
+                .find(|row| row.image_url == described.image_url)
+            {
+                Some(row) if row.locked => {}
+                Some(row) => *row = described,
+                None => rows.push(described),
+            }
+        }
+
+        rows.retain(|row| {
+            let exists = row
+                .image_url
+                .strip_prefix(IMAGE_URL_PREFIX)
+                .is_some_and(|filename| self.images_dir.join(filename).is_file());
+            if !exists {
+                tracing::warn!("Dropping library row for missing image {}", row.image_url);
+            }
+            exists
+        });
+
+        write_library_rows(&self.csv_path, &rows).map_err(AppError::Io)?;
+        Ok(rows)
+    }
+}
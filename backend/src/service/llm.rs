@@ -0,0 +1,173 @@
+use async_trait::async_trait;
+use futures_util::{Stream, StreamExt};
+use reqwest::{Response, StatusCode};
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::config::{Config, LlmBackendKind};
+use crate::error::AppError;
+use crate::service::{OllamaService, OpenAiService};
+
+/// Generated text tokens as they arrive from a streaming LLM call
+pub type TokenStream = Pin<Box<dyn Stream<Item = Result<String, AppError>> + Send>>;
+
+/// A chat message in backend-neutral form
+#[derive(Debug, Clone)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+    /// Base64-encoded images attached to this message
+    pub images: Vec<String>,
+}
+
+impl ChatMessage {
+    pub fn user(content: impl Into<String>) -> Self {
+        Self {
+            role: "user".into(),
+            content: content.into(),
+            images: Vec::new(),
+        }
+    }
+
+    pub fn user_with_image(content: impl Into<String>, image_base64: &str) -> Self {
+        Self {
+            images: vec![image_base64.into()],
+            ..Self::user(content)
+        }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self {
+            role: "assistant".into(),
+            ..Self::user(content)
+        }
+    }
+}
+
+/// Sampling settings for a single call
+#[derive(Debug, Clone)]
+pub struct GenerateOptions {
+    pub model: String,
+    pub temperature: f32,
+    /// JSON schema the reply must follow; `None` for free text
+    pub format: Option<serde_json::Value>,
+}
+
+/// A local LLM runtime able to generate text, chat about images and embed text
+#[async_trait]
+pub trait LlmBackend: Send + Sync {
+    /// Short name used in logs
+    fn name(&self) -> &'static str;
+
+    /// Complete a single text prompt
+    async fn generate(&self, prompt: &str, options: &GenerateOptions) -> Result<String, AppError>;
+
+    /// Complete a single text prompt, token by token
+    async fn generate_stream(
+        &self,
+        prompt: &str,
+        options: &GenerateOptions,
+    ) -> Result<TokenStream, AppError>;
+
+    /// Reply to a conversation, which may include images
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        options: &GenerateOptions,
+    ) -> Result<String, AppError>;
+
+    /// Reply to a conversation, token by token
+    async fn chat_stream(
+        &self,
+        messages: &[ChatMessage],
+        options: &GenerateOptions,
+    ) -> Result<TokenStream, AppError>;
+
+    /// Embed texts with the given embedding model
+    async fn embed(&self, texts: &[String], model: &str) -> Result<Vec<Vec<f32>>, AppError>;
+}
+
+/// Construct the backend selected in the config
+pub fn build_backend(config: &Config) -> Arc<dyn LlmBackend> {
+    match config.llm.backend {
+        LlmBackendKind::Ollama => Arc::new(OllamaService::new(&config.ollama, &config.llm)),
+        LlmBackendKind::Openai => Arc::new(OpenAiService::new(&config.openai, &config.llm)),
+    }
+}
+
+/// Turn a streaming HTTP response into text tokens.
+///
+/// Lines may be split across network chunks, so bytes are buffered until a newline.
+/// `decode` maps each non-empty line to `(token, is_last)`; an error ends the stream.
+pub fn line_token_stream<F>(response: Response, decode: F) -> TokenStream
+where
+    F: FnMut(&str) -> Result<(String, bool), AppError> + Send + 'static,
+{
+    let bytes = response.bytes_stream().boxed();
+
+    let tokens = futures_util::stream::unfold(
+        (bytes, Vec::<u8>::new(), decode, false),
+        |(mut bytes, mut buf, mut decode, finished)| async move {
+            if finished {
+                return None;
+            }
+
+            loop {
+                if let Some(pos) = buf.iter().position(|b| *b == b'\n') {
+                    let line: Vec<u8> = buf.drain(..=pos).collect();
+                    let line = String::from_utf8_lossy(&line);
+                    let line = line.trim();
+                    if line.is_empty() {
+                        continue;
+                    }
+
+                    return match decode(line) {
+                        Ok((token, last)) => Some((Ok(token), (bytes, buf, decode, last))),
+                        Err(e) => Some((Err(e), (bytes, buf, decode, true))),
+                    };
+                }
+
+                match bytes.next().await {
+                    Some(Ok(chunk)) => buf.extend_from_slice(&chunk),
+                    Some(Err(e)) => {
+                        return Some((
+                            Err(request_error("Stream interrupted", e)),
+                            (bytes, buf, decode, true),
+                        ));
+                    }
+                    // Flush a trailing line without a newline, otherwise we're done
+                    None if buf.iter().any(|b| !b.is_ascii_whitespace()) => buf.push(b'\n'),
+                    None => return None,
+                }
+            }
+        },
+    );
+
+    Box::pin(tokens)
+}
+
+/// Classify a failed HTTP exchange with the LLM server
+pub fn request_error(context: &str, e: reqwest::Error) -> AppError {
+    let message = format!("{}: {}", context, e);
+    if e.is_timeout() {
+        AppError::UpstreamTimeout(message)
+    } else if e.is_connect() {
+        AppError::UpstreamUnavailable(message)
+    } else {
+        AppError::Upstream(message)
+    }
+}
+
+/// Classify a non-2xx reply from the LLM server
+pub fn status_error(server: &str, status: StatusCode, body: &str) -> AppError {
+    let message = format!("{} error ({}): {}", server, status, body);
+    match status {
+        StatusCode::SERVICE_UNAVAILABLE | StatusCode::TOO_MANY_REQUESTS => {
+            AppError::UpstreamUnavailable(message)
+        }
+        StatusCode::GATEWAY_TIMEOUT | StatusCode::REQUEST_TIMEOUT => {
+            AppError::UpstreamTimeout(message)
+        }
+        _ => AppError::Upstream(message),
+    }
+}
@@ -1,64 +1,216 @@
 use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
-use once_cell::sync::Lazy;
-use std::sync::Mutex;
+use once_cell::sync::OnceCell;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
 
-use crate::lib::image_library::get_image_library;
-use crate::service::similarity::{average_embeddings, cosine_similarity};
+use crate::config::{MatchMode, SlotWeights};
+use crate::error::AppError;
+use crate::lib::image_library::LibrarySource;
+use crate::lib::tags::{Tag, TagCategory};
+use crate::models::{ImageMatchCandidate, SlotScore, WordSimilarity};
+use crate::service::similarity::{
+    average_embeddings, cosine_similarity, weighted_average_embeddings,
+};
 
+#[derive(Clone)]
 pub struct ImageEntryWithEmbedding {
     pub image_url: String,
-    pub words: [String; 3],
+    pub tags: Vec<Tag>,
+    /// Embedding of each tag's word, in the same order as `tags`
+    pub word_embeddings: Vec<Vec<f32>>,
+    /// Average of `word_embeddings`, weighted by tag weight
     pub embedding: Vec<f32>,
 }
 
+/// Pre-computed library; replaced wholesale on reload so readers never see a partial rebuild
+pub type LibrarySnapshot = Arc<Vec<ImageEntryWithEmbedding>>;
+
 struct EmbeddingCache {
-    model: TextEmbedding,
-    library: Vec<ImageEntryWithEmbedding>,
+    model: Mutex<TextEmbedding>,
+    library: RwLock<LibrarySnapshot>,
+    source: LibrarySource,
+    reloading: AtomicBool,
+    /// A reload was asked for while one was running, and runs after it
+    reload_queued: AtomicBool,
+    /// Bumped by every single-entry change, so a reload can tell it raced one
+    generation: AtomicU64,
+    /// Embeddings of vocabulary words seen so far, for snapping
+    vocabulary: Mutex<HashMap<String, Vec<f32>>>,
 }
 
-static EMBEDDING_CACHE: Lazy<Mutex<EmbeddingCache>> = Lazy::new(|| {
-    tracing::info!("Initializing local embedding model...");
+static EMBEDDING_CACHE: OnceCell<EmbeddingCache> = OnceCell::new();
 
-    let mut options = InitOptions::default();
-    options.model_name = EmbeddingModel::AllMiniLML6V2;
-    options.show_download_progress = true;
+/// Builds a reload may throw away for racing single-entry changes before giving up
+const MAX_RELOAD_ATTEMPTS: usize = 3;
 
-    let model = TextEmbedding::try_new(options)
-        .expect("Failed to initialize embedding model");
-    tracing::info!("Pre-computing image library embeddings...");
+fn cache() -> Result<&'static EmbeddingCache, AppError> {
+    EMBEDDING_CACHE
+        .get()
+        .ok_or_else(|| AppError::LibraryEmpty("Embedding cache is not initialized".into()))
+}
 
-    let image_library = get_image_library();
-    let mut library_with_embeddings = Vec::with_capacity(image_library.len());
+impl EmbeddingCache {
+    fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, String> {
+        let model = self
+            .model
+            .lock()
+            .map_err(|e| format!("Failed to acquire lock: {}", e))?;
+
+        model.embed(texts, None).map_err(|e| e.to_string())
+    }
+
+    fn snapshot(&self) -> Result<LibrarySnapshot, String> {
+        self.library
+            .read()
+            .map(|library| Arc::clone(&library))
+            .map_err(|e| format!("Failed to acquire lock: {}", e))
+    }
 
-    for entry in image_library {
-        let words_vec: Vec<String> = entry.words.to_vec();
+    /// Embed one entry's tags and their weighted average
+    fn embed_entry(
+        &self,
+        image_url: String,
+        tags: Vec<Tag>,
+    ) -> Result<ImageEntryWithEmbedding, String> {
+        let embeddings = self.embed(tags.iter().map(|t| t.word.clone()).collect())?;
+        let weights: Vec<f32> = tags.iter().map(|t| t.weight).collect();
+        let avg = weighted_average_embeddings(&embeddings, &weights)
+            .ok_or_else(|| format!("No embeddings returned for {}", image_url))?;
 
-        match model.embed(words_vec.clone(), None) {
-            Ok(embeddings) => {
-                if let Some(avg) = average_embeddings(&embeddings) {
-                    library_with_embeddings.push(ImageEntryWithEmbedding {
-                        image_url: entry.image_url,
-                        words: entry.words,
-                        embedding: avg,
-                    });
+        Ok(ImageEntryWithEmbedding {
+            image_url,
+            tags,
+            word_embeddings: embeddings,
+            embedding: avg,
+        })
+    }
+
+    /// Load the library from its source and embed every entry
+    fn build_library(&self) -> Result<Vec<ImageEntryWithEmbedding>, String> {
+        let image_library = self.source.load()?;
+        let mut library_with_embeddings = Vec::with_capacity(image_library.len());
+
+        for entry in image_library {
+            match self.embed_entry(entry.image_url.clone(), entry.tags) {
+                Ok(embedded) => library_with_embeddings.push(embedded),
+                Err(e) => {
+                    tracing::warn!("Failed to embed words for {}: {}", entry.image_url, e);
                 }
             }
-            Err(e) => {
-                tracing::warn!("Failed to embed words for {}: {}", entry.image_url, e);
+        }
+
+        if library_with_embeddings.is_empty() {
+            return Err("No library entries could be embedded".into());
+        }
+
+        Ok(library_with_embeddings)
+    }
+
+    /// Rebuild the library and swap it in. The old snapshot keeps serving until the swap.
+    /// A build that an entry was changed during may have read the files before
+    /// the change, so it is thrown away and the library is built again.
+    fn reload(&self) -> Result<usize, String> {
+        for _ in 0..MAX_RELOAD_ATTEMPTS {
+            let generation = self.generation.load(Ordering::SeqCst);
+            let library = self.build_library()?;
+            let count = library.len();
+
+            let mut current = self
+                .library
+                .write()
+                .map_err(|e| format!("Failed to acquire lock: {}", e))?;
+            if self.generation.load(Ordering::SeqCst) != generation {
+                tracing::info!("Library entry changed during reload, rebuilding");
+                continue;
             }
+            *current = Arc::new(library);
+
+            return Ok(count);
         }
+
+        Err(format!(
+            "Library entries kept changing during {} reload attempts",
+            MAX_RELOAD_ATTEMPTS
+        ))
     }
 
-    tracing::info!(
-        "Pre-computed embeddings for {} images",
-        library_with_embeddings.len()
-    );
+    /// Embeddings of the vocabulary words, in order, embedding only words not seen before
+    fn vocabulary_embeddings(&self, vocabulary: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        let mut known = self
+            .vocabulary
+            .lock()
+            .map_err(|e| format!("Failed to acquire lock: {}", e))?;
+
+        let missing: Vec<String> = vocabulary
+            .iter()
+            .filter(|word| !known.contains_key(*word))
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            let embeddings = self.embed(missing.clone())?;
+            known.extend(missing.into_iter().zip(embeddings));
+        }
 
-    Mutex::new(EmbeddingCache {
-        model,
-        library: library_with_embeddings,
-    })
-});
+        vocabulary
+            .iter()
+            .map(|word| {
+                known
+                    .get(word)
+                    .cloned()
+                    .ok_or_else(|| format!("No embedding returned for {}", word))
+            })
+            .collect()
+    }
+
+    /// Reload until no reload is queued, for the caller that claimed
+    /// `reloading`, then release it. Returns the last reload's result.
+    fn run_reloads(&self) -> Result<usize, String> {
+        loop {
+            self.reload_queued.store(false, Ordering::SeqCst);
+            let result = self.reload();
+            match &result {
+                Ok(count) => tracing::info!("Reloaded image library with {} images", count),
+                Err(e) => tracing::error!("Library reload failed, keeping previous library: {}", e),
+            }
+
+            self.reloading.store(false, Ordering::SeqCst);
+            // A request queued just before the release would otherwise be lost;
+            // one that came after it started its own reload
+            if !self.reload_queued.load(Ordering::SeqCst)
+                || self.reloading.swap(true, Ordering::SeqCst)
+            {
+                return result;
+            }
+        }
+    }
+
+    /// Swap in a copy of the current snapshot with `change` applied
+    fn modify(&self, change: impl FnOnce(&mut Vec<ImageEntryWithEmbedding>)) -> Result<(), String> {
+        let mut current = self
+            .library
+            .write()
+            .map_err(|e| format!("Failed to acquire lock: {}", e))?;
+
+        let mut library = current.as_ref().clone();
+        change(&mut library);
+        *current = Arc::new(library);
+        self.generation.fetch_add(1, Ordering::SeqCst);
+
+        Ok(())
+    }
+}
+
+/// Outcome of asking for a library reload
+pub enum ReloadStatus {
+    /// A reload was started in the background
+    Started,
+    /// A reload is already in progress and may have read the files before
+    /// this request; another one runs as soon as it finishes
+    Queued,
+}
 
 pub struct LocalEmbeddingService;
 
@@ -68,49 +220,147 @@ impl LocalEmbeddingService {
     }
 
     /// Embed multiple texts using the local model
-    pub fn embed_texts(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
-        let cache = EMBEDDING_CACHE
-            .lock()
-            .map_err(|e| format!("Failed to acquire lock: {}", e))?;
+    pub fn embed_texts(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, AppError> {
+        cache()?
+            .embed(texts.to_vec())
+            .map_err(|e| AppError::Internal(format!("Embedding failed: {}", e)))
+    }
 
-        cache
-            .model
-            .embed(texts.to_vec(), None)
-            .map_err(|e| format!("Embedding failed: {}", e))
+    /// Current library snapshot
+    pub fn library(&self) -> Result<LibrarySnapshot, AppError> {
+        cache()?.snapshot().map_err(AppError::Internal)
     }
 
-    /// Find the best matching image from the pre-computed library
-    pub fn find_best_match(&self, query_words: &[String]) -> Result<(String, f32), String> {
-        let cache = EMBEDDING_CACHE
-            .lock()
-            .map_err(|e| format!("Failed to acquire lock: {}", e))?;
+    /// Rank library images against the query words, best first.
+    /// Returns at most `top_k` candidates scoring at least `min_score`.
+    /// In `slots` mode the query words are the object, emotion and style, in order.
+    pub fn find_matches(
+        &self,
+        query_words: &[String],
+        top_k: usize,
+        min_score: Option<f32>,
+        mode: MatchMode,
+        slot_weights: &SlotWeights,
+    ) -> Result<Vec<ImageMatchCandidate>, AppError> {
+        let cache = cache()?;
+        let library = cache.snapshot().map_err(AppError::Internal)?;
 
         // Embed the query words
         let query_embeddings = cache
-            .model
-            .embed(query_words.to_vec(), None)
-            .map_err(|e| format!("Failed to embed query: {}", e))?;
+            .embed(query_words.to_vec())
+            .map_err(|e| AppError::Internal(format!("Failed to embed query: {}", e)))?;
 
         let query_avg = average_embeddings(&query_embeddings)
-            .ok_or_else(|| "Failed to calculate average embedding".to_string())?;
+            .ok_or_else(|| AppError::Internal("Failed to calculate average embedding".into()))?;
+
+        if library.is_empty() {
+            return Err(AppError::LibraryEmpty("The image library is empty".into()));
+        }
 
-        // Find best match from pre-computed library
-        let mut best_match: Option<(String, f32)> = None;
+        let scoring = match mode {
+            MatchMode::Average => Scoring::Average(&query_avg),
+            MatchMode::Slots => Scoring::Slots(slot_weights),
+        };
 
-        for entry in &cache.library {
-            let similarity = cosine_similarity(&query_avg, &entry.embedding);
+        Ok(rank_library(
+            &library,
+            query_words,
+            &query_embeddings,
+            &scoring,
+            top_k,
+            min_score,
+        ))
+    }
 
-            match &best_match {
-                None => best_match = Some((entry.image_url.clone(), similarity)),
-                Some((_, best_score)) => {
-                    if similarity > *best_score {
-                        best_match = Some((entry.image_url.clone(), similarity));
-                    }
-                }
-            }
+    /// Replace each word with the closest vocabulary word, returned with their
+    /// cosine similarity. Words already in the vocabulary are kept with a score of 1.
+    pub fn snap_to_vocabulary(
+        &self,
+        words: &[String],
+        vocabulary: &[String],
+    ) -> Result<Vec<(String, f32)>, AppError> {
+        let unknown: Vec<String> = words
+            .iter()
+            .filter(|word| !vocabulary.contains(word))
+            .cloned()
+            .collect();
+        if unknown.is_empty() {
+            return Ok(words.iter().map(|word| (word.clone(), 1.0)).collect());
+        }
+
+        let cache = cache()?;
+        let vocabulary_embeddings = cache
+            .vocabulary_embeddings(vocabulary)
+            .map_err(|e| AppError::Internal(format!("Failed to embed vocabulary: {}", e)))?;
+        let unknown_embeddings = cache
+            .embed(unknown.clone())
+            .map_err(|e| AppError::Internal(format!("Failed to embed words: {}", e)))?;
+        let embedded: HashMap<&String, &Vec<f32>> =
+            unknown.iter().zip(&unknown_embeddings).collect();
+
+        Ok(words
+            .iter()
+            .map(|word| match embedded.get(word) {
+                Some(embedding) => closest_word(embedding, vocabulary, &vocabulary_embeddings),
+                None => (word.clone(), 1.0),
+            })
+            .collect())
+    }
+
+    /// Embed a single entry and add it to the served library, replacing any entry
+    /// with the same URL. The rest of the library is not re-embedded.
+    pub fn upsert_entry(&self, image_url: &str, tags: Vec<Tag>) -> Result<(), AppError> {
+        let cache = cache()?;
+        let entry = cache
+            .embed_entry(image_url.to_string(), tags)
+            .map_err(|e| AppError::Internal(format!("Failed to embed {}: {}", image_url, e)))?;
+
+        cache
+            .modify(|library| match library.iter_mut().find(|e| e.image_url == image_url) {
+                Some(existing) => *existing = entry,
+                None => library.push(entry),
+            })
+            .map_err(AppError::Internal)
+    }
+
+    /// Drop an entry from the served library
+    pub fn remove_entry(&self, image_url: &str) -> Result<(), AppError> {
+        cache()?
+            .modify(|library| library.retain(|e| e.image_url != image_url))
+            .map_err(AppError::Internal)
+    }
+
+    /// Rebuild the library on the current thread, blocking until done.
+    /// Returns the number of entries in the new snapshot.
+    pub fn reload_library(&self) -> Result<usize, AppError> {
+        let cache = cache()?;
+
+        if cache.reloading.swap(true, Ordering::SeqCst) {
+            return Err(AppError::Conflict(
+                "A library reload is already in progress".into(),
+            ));
+        }
+
+        cache.run_reloads().map_err(AppError::Internal)
+    }
+
+    /// Rebuild the library on a blocking worker thread without waiting for it.
+    /// If a reload is already running, one more is queued behind it.
+    pub fn start_reload(&self) -> Result<ReloadStatus, AppError> {
+        let cache = cache()?;
+
+        // Queue first, so a running reload that is just finishing sees it
+        cache.reload_queued.store(true, Ordering::SeqCst);
+        if cache.reloading.swap(true, Ordering::SeqCst) {
+            return Ok(ReloadStatus::Queued);
         }
 
-        best_match.ok_or_else(|| "No matching images found".to_string())
+        // This call holds `reloading` now; the task releases it when done
+        tokio::task::spawn_blocking(move || {
+            let _ = cache.run_reloads();
+        });
+
+        Ok(ReloadStatus::Started)
     }
 }
 
@@ -120,7 +370,309 @@ impl Default for LocalEmbeddingService {
     }
 }
 
-/// Initialize the embedding cache (call this at startup to pre-load)
-pub fn init_embeddings() {
-    let _ = &*EMBEDDING_CACHE;
+/// How `rank_library` scores an entry
+enum Scoring<'a> {
+    /// Against the query's averaged embedding
+    Average(&'a [f32]),
+    /// Slot by slot, with these weights
+    Slots(&'a SlotWeights),
+}
+
+/// Score every entry against the query, keeping the best `top_k` at or above `min_score`
+fn rank_library(
+    library: &[ImageEntryWithEmbedding],
+    query_words: &[String],
+    query_embeddings: &[Vec<f32>],
+    scoring: &Scoring,
+    top_k: usize,
+    min_score: Option<f32>,
+) -> Vec<ImageMatchCandidate> {
+    let mut scored: Vec<(f32, Vec<SlotScore>, &ImageEntryWithEmbedding)> = library
+        .iter()
+        .map(|entry| match scoring {
+            Scoring::Average(query_avg) => {
+                (cosine_similarity(query_avg, &entry.embedding), Vec::new(), entry)
+            }
+            Scoring::Slots(weights) => {
+                let slots = slot_breakdown(entry, query_words, query_embeddings, weights);
+                (weighted_slot_score(&slots), slots, entry)
+            }
+        })
+        .filter(|(score, _, _)| min_score.is_none_or(|min| *score >= min))
+        .collect();
+
+    scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+    scored
+        .into_iter()
+        .take(top_k)
+        .map(|(score, slot_scores, entry)| ImageMatchCandidate {
+            image_url: entry.image_url.clone(),
+            words: entry.tags.iter().map(|t| t.word.clone()).collect(),
+            tags: entry.tags.clone(),
+            score,
+            word_scores: word_breakdown(entry, query_words, query_embeddings),
+            slot_scores,
+        })
+        .collect()
+}
+
+/// Compare the query's first three words with the entry's object, emotion and
+/// style tags. Entries without a tag for a slot are compared on their
+/// uncategorised tags instead.
+fn slot_breakdown(
+    entry: &ImageEntryWithEmbedding,
+    query_words: &[String],
+    query_embeddings: &[Vec<f32>],
+    weights: &SlotWeights,
+) -> Vec<SlotScore> {
+    query_words
+        .iter()
+        .zip(query_embeddings)
+        .zip(TagCategory::SLOTS)
+        .map(|((query_word, query_embedding), slot)| {
+            let in_slot = |category: Option<TagCategory>| {
+                entry
+                    .tags
+                    .iter()
+                    .zip(&entry.word_embeddings)
+                    .filter(move |(tag, _)| tag.category == category)
+            };
+            let candidates: Vec<_> = if in_slot(Some(slot)).next().is_some() {
+                in_slot(Some(slot)).collect()
+            } else {
+                in_slot(None).collect()
+            };
+
+            let best = candidates
+                .into_iter()
+                .map(|(tag, embedding)| (tag, cosine_similarity(query_embedding, embedding)))
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+
+            SlotScore {
+                slot,
+                query_word: query_word.clone(),
+                library_word: best.map(|(tag, _)| tag.word.clone()),
+                score: best.map_or(0.0, |(_, score)| score),
+                weight: weights.get(slot),
+            }
+        })
+        .collect()
+}
+
+/// Weighted mean of the slot scores; 0 if no slot carries weight
+fn weighted_slot_score(slots: &[SlotScore]) -> f32 {
+    let total: f32 = slots.iter().map(|s| s.weight).sum();
+    if total <= 0.0 {
+        return 0.0;
+    }
+    slots.iter().map(|s| s.score * s.weight).sum::<f32>() / total
+}
+
+/// For each query word, the library tag it is closest to
+fn word_breakdown(
+    entry: &ImageEntryWithEmbedding,
+    query_words: &[String],
+    query_embeddings: &[Vec<f32>],
+) -> Vec<WordSimilarity> {
+    query_words
+        .iter()
+        .zip(query_embeddings)
+        .filter_map(|(query_word, query_embedding)| {
+            entry
+                .tags
+                .iter()
+                .zip(&entry.word_embeddings)
+                .map(|(tag, library_embedding)| WordSimilarity {
+                    query_word: query_word.clone(),
+                    library_word: tag.word.clone(),
+                    score: cosine_similarity(query_embedding, library_embedding),
+                })
+                .max_by(|a, b| a.score.partial_cmp(&b.score).unwrap_or(std::cmp::Ordering::Equal))
+        })
+        .collect()
+}
+
+/// The vocabulary word whose embedding is closest, with its similarity
+fn closest_word(
+    embedding: &[f32],
+    vocabulary: &[String],
+    vocabulary_embeddings: &[Vec<f32>],
+) -> (String, f32) {
+    vocabulary
+        .iter()
+        .zip(vocabulary_embeddings)
+        .map(|(word, candidate)| (word, cosine_similarity(embedding, candidate)))
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(word, score)| (word.clone(), score))
+        .unwrap_or_default()
+}
+
+/// Load the image library and pre-compute its embeddings (call once at startup)
+pub fn init_embeddings(source: &LibrarySource) -> Result<(), String> {
+    tracing::info!("Initializing local embedding model...");
+
+    let mut options = InitOptions::default();
+    options.model_name = EmbeddingModel::AllMiniLML6V2;
+    options.show_download_progress = true;
+
+    let model = TextEmbedding::try_new(options)
+        .map_err(|e| format!("Failed to initialize embedding model: {}", e))?;
+
+    let cache = EmbeddingCache {
+        model: Mutex::new(model),
+        library: RwLock::new(Arc::new(Vec::new())),
+        source: source.clone(),
+        reloading: AtomicBool::new(false),
+        reload_queued: AtomicBool::new(false),
+        generation: AtomicU64::new(0),
+        vocabulary: Mutex::new(HashMap::new()),
+    };
+
+    tracing::info!("Pre-computing image library embeddings...");
+    let count = cache.reload()?;
+    tracing::info!("Pre-computed embeddings for {} images", count);
+
+    EMBEDDING_CACHE
+        .set(cache)
+        .map_err(|_| "Embedding cache is already initialized".to_string())
+}
+
+/// Poll the library CSV and images directory and reload when either changes
+pub fn spawn_library_watcher(source: LibrarySource, interval: Duration) {
+    tokio::spawn(async move {
+        let mut last_seen = library_mtime(&source);
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+
+        loop {
+            ticker.tick().await;
+
+            let current = library_mtime(&source);
+            if current == last_seen {
+                continue;
+            }
+            last_seen = current;
+
+            tracing::info!("Image library changed on disk, reloading...");
+            if let Err(e) = LocalEmbeddingService::new().start_reload() {
+                tracing::error!("Failed to start library reload: {}", e);
+            }
+        }
+    });
+}
+
+/// Latest modification time across the CSV, the images directory and its files
+fn library_mtime(source: &LibrarySource) -> Option<SystemTime> {
+    let mtime = |path: &Path| path.metadata().and_then(|m| m.modified()).ok();
+
+    let mut latest = mtime(&source.csv_path).max(mtime(&source.images_dir));
+
+    if let Ok(entries) = std::fs::read_dir(&source.images_dir) {
+        for entry in entries.flatten() {
+            latest = latest.max(mtime(&entry.path()));
+        }
+    }
+
+    latest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(url: &str, words: [&str; 3], word_embeddings: Vec<Vec<f32>>) -> ImageEntryWithEmbedding {
+        let embedding = average_embeddings(&word_embeddings).unwrap();
+        ImageEntryWithEmbedding {
+            image_url: url.into(),
+            tags: Tag::slots(words),
+            word_embeddings,
+            embedding,
+        }
+    }
+
+    #[test]
+    fn test_rank_library_orders_and_filters() {
+        let library = vec![
+            entry("/images/a.jpg", ["x", "x", "x"], vec![vec![1.0, 0.0]; 3]),
+            entry("/images/b.jpg", ["y", "y", "y"], vec![vec![0.0, 1.0]; 3]),
+            entry("/images/c.jpg", ["x", "y", "y"], vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![0.0, 1.0]]),
+        ];
+        let query_words = vec!["q".to_string()];
+        let query_embeddings = vec![vec![1.0, 0.0]];
+
+        let scoring = Scoring::Average(&[1.0, 0.0]);
+
+        let ranked = rank_library(&library, &query_words, &query_embeddings, &scoring, 3, None);
+        let urls: Vec<&str> = ranked.iter().map(|c| c.image_url.as_str()).collect();
+        assert_eq!(urls, ["/images/a.jpg", "/images/c.jpg", "/images/b.jpg"]);
+        assert!(ranked[0].slot_scores.is_empty());
+
+        let ranked = rank_library(&library, &query_words, &query_embeddings, &scoring, 3, Some(0.1));
+        assert_eq!(ranked.len(), 2);
+
+        let ranked = rank_library(&library, &query_words, &query_embeddings, &scoring, 1, None);
+        assert_eq!(ranked.len(), 1);
+    }
+
+    #[test]
+    fn test_slots_compare_like_with_like() {
+        let hungry = vec![1.0, 0.0];
+        let other = vec![0.0, 1.0];
+        let library = vec![
+            // "hungry" as the object
+            entry("/images/object.jpg", ["hungry", "calm", "plain"], vec![hungry.clone(), other.clone(), other.clone()]),
+            // "hungry" as the emotion
+            entry("/images/emotion.jpg", ["box", "hungry", "plain"], vec![other.clone(), hungry.clone(), other.clone()]),
+        ];
+        let query_words: Vec<String> = ["food", "hungry", "plain"].map(String::from).to_vec();
+        let query_embeddings = vec![other.clone(), hungry.clone(), other.clone()];
+
+        // Averaged, both entries look the same
+        let average = average_embeddings(&query_embeddings).unwrap();
+        let ranked = rank_library(&library, &query_words, &query_embeddings, &Scoring::Average(&average), 2, None);
+        assert!((ranked[0].score - ranked[1].score).abs() < 1e-6);
+
+        let weights = SlotWeights::default();
+        let ranked = rank_library(&library, &query_words, &query_embeddings, &Scoring::Slots(&weights), 2, None);
+        assert_eq!(ranked[0].image_url, "/images/emotion.jpg");
+        assert!((ranked[0].score - 1.0).abs() < 1e-6);
+        assert_eq!(ranked[0].slot_scores.len(), 3);
+        assert_eq!(ranked[0].slot_scores[1].slot, TagCategory::Emotion);
+        assert_eq!(ranked[0].slot_scores[1].library_word.as_deref(), Some("hungry"));
+        assert!(ranked[1].score < ranked[0].score);
+    }
+
+    #[test]
+    fn test_slots_fall_back_to_uncategorised_tags() {
+        let mut e = entry("/images/a.jpg", ["cake", "sad", "cute"], vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![0.7, 0.7]]);
+        e.tags[0].category = None;
+        let weights = SlotWeights::default();
+
+        let slots = slot_breakdown(&e, &["pie".to_string()], &[vec![1.0, 0.0]], &weights);
+        assert_eq!(slots.len(), 1);
+        assert_eq!(slots[0].library_word.as_deref(), Some("cake"));
+
+        e.tags.iter_mut().for_each(|t| t.category = Some(TagCategory::Style));
+        let slots = slot_breakdown(&e, &["pie".to_string()], &[vec![1.0, 0.0]], &weights);
+        assert_eq!(slots[0].library_word, None);
+        assert_eq!(weighted_slot_score(&slots), 0.0);
+    }
+
+    #[test]
+    fn test_closest_word() {
+        let vocabulary = ["happy", "sad"].map(String::from);
+        let embeddings = vec![vec![1.0, 0.0], vec![0.0, 1.0]];
+        let (word, score) = closest_word(&[0.2, 0.9], &vocabulary, &embeddings);
+        assert_eq!(word, "sad");
+        assert!(score > 0.9);
+    }
+
+    #[test]
+    fn test_word_breakdown_picks_closest_library_word() {
+        let e = entry("/images/c.jpg", ["cake", "sad", "cute"], vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![0.7, 0.7]]);
+        let breakdown = word_breakdown(&e, &["sweet".to_string()], &[vec![0.1, 1.0]]);
+        assert_eq!(breakdown.len(), 1);
+        assert_eq!(breakdown[0].library_word, "sad");
+    }
 }
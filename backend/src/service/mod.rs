@@ -1,6 +1,27 @@
+mod generation;
+pub mod history;
+pub mod jobs;
+mod library_generator;
+pub mod llm;
 mod ollama;
+mod openai;
+pub mod photo_embeddings;
+pub mod prompts;
+pub mod roast_guard;
+pub mod sessions;
 pub mod similarity;
+pub mod structured;
 pub mod local_embeddings;
 
+pub use generation::{Generated, GenerationService};
+pub use history::HistoryStore;
+pub use jobs::JobStore;
+pub use library_generator::{GenerateMode, LibraryGenerator};
+pub use llm::{build_backend, TokenStream};
 pub use ollama::OllamaService;
-pub use local_embeddings::{LocalEmbeddingService, init_embeddings};
+pub use openai::OpenAiService;
+pub use local_embeddings::{LocalEmbeddingService, init_embeddings, spawn_library_watcher};
+pub use photo_embeddings::{PhotoEmbeddingService, init_photo_embeddings};
+pub use prompts::{PromptRegistry, spawn_prompt_watcher};
+pub use roast_guard::RoastGuard;
+pub use sessions::SessionStore;
@@ -1,173 +1,181 @@
-use reqwest::Client;
+use async_trait::async_trait;
+use reqwest::{Client, Response};
+use serde::Serialize;
 use std::time::Duration;
 
+use crate::config::{LlmConfig, OllamaConfig};
+use crate::error::AppError;
 use crate::models::{
     EmbeddingInput, OllamaChatMessage, OllamaChatRequest, OllamaChatResponse,
-    OllamaEmbeddingRequest, OllamaEmbeddingResponse, OllamaGenerateRequest, OllamaGenerateResponse,
-    OllamaOptions, TextEmbedding,
+    OllamaChatStreamChunk, OllamaEmbeddingRequest, OllamaEmbeddingResponse, OllamaGenerateRequest,
+    OllamaGenerateResponse, OllamaGenerateStreamChunk, OllamaOptions,
+};
+use crate::service::llm::{
+    line_token_stream, request_error, status_error, ChatMessage, GenerateOptions, LlmBackend,
+    TokenStream,
 };
 
-const EMBEDDING_MODEL: &str = "nomic-embed-text";
-// Timeout for Ollama requests (5 minutes for slow devices like Raspberry Pi)
-const OLLAMA_TIMEOUT_SECS: u64 = 300;
-
+/// Ollama's native `/api/generate`, `/api/chat` and `/api/embed` endpoints
+#[derive(Clone)]
 pub struct OllamaService {
     client: Client,
     base_url: String,
 }
 
 impl OllamaService {
-    pub fn new() -> Self {
-        let base_url =
-            std::env::var("OLLAMA_BASE_URL").unwrap_or_else(|_| "http://localhost:11434".into());
-
+    pub fn new(config: &OllamaConfig, llm: &LlmConfig) -> Self {
         let client = Client::builder()
-            .timeout(Duration::from_secs(OLLAMA_TIMEOUT_SECS))
+            .timeout(Duration::from_secs(llm.timeout_secs))
             .build()
             .unwrap_or_else(|_| Client::new());
 
-        Self { client, base_url }
+        Self {
+            client,
+            base_url: config.base_url.clone(),
+        }
     }
 
-    pub async fn generate_poem_from_text(&self, prompt: &str) -> Result<String, String> {
-        let full_prompt = format!(
-            "Write a creative, evocative poem based on the following theme or idea. \
-            Output ONLY the poem, no explanations or titles.\n\nTheme: {}",
-            prompt
-        );
-
-        let request = OllamaGenerateRequest {
-            model: "gemma3:4b".into(),
-            prompt: full_prompt,
-            stream: false,
-            options: OllamaOptions { temperature: 0.7 },
-        };
-
+    /// POST a JSON body to Ollama and fail on non-2xx status
+    async fn send<T: Serialize>(&self, path: &str, body: &T) -> Result<Response, AppError> {
         let response = self
             .client
-            .post(format!("{}/api/generate", self.base_url))
-            .json(&request)
+            .post(format!("{}{}", self.base_url, path))
+            .json(body)
             .send()
             .await
-            .map_err(|e| format!("Failed to connect to Ollama: {}", e))?;
+            .map_err(|e| request_error("Failed to connect to Ollama", e))?;
 
         if !response.status().is_success() {
             let status = response.status();
             let text = response.text().await.unwrap_or_default();
-            return Err(format!("Ollama error ({}): {}", status, text));
+            return Err(status_error("Ollama", status, &text));
         }
 
-        let result: OllamaGenerateResponse = response
-            .json()
-            .await
-            .map_err(|e| format!("Failed to parse Ollama response: {}", e))?;
+        Ok(response)
+    }
 
-        Ok(result.response)
+    fn generate_request(
+        prompt: &str,
+        options: &GenerateOptions,
+        stream: bool,
+    ) -> OllamaGenerateRequest {
+        OllamaGenerateRequest {
+            model: options.model.clone(),
+            prompt: prompt.into(),
+            stream,
+            options: OllamaOptions {
+                temperature: options.temperature,
+            },
+            format: options.format.clone(),
+        }
     }
 
-    pub async fn generate_poem_from_image(
-        &self,
-        image_base64: &str,
-        custom_prompt: Option<&str>,
-    ) -> Result<String, String> {
-        let prompt = custom_prompt.unwrap_or(
-            "Look at this image carefully. Write a creative, evocative poem inspired by what you see. \
-            Output ONLY the poem, no explanations or titles."
-        );
+    fn chat_request(
+        messages: &[ChatMessage],
+        options: &GenerateOptions,
+        stream: bool,
+    ) -> OllamaChatRequest {
+        let messages = messages
+            .iter()
+            .map(|m| OllamaChatMessage {
+                role: m.role.clone(),
+                content: m.content.clone(),
+                images: (!m.images.is_empty()).then(|| m.images.clone()),
+            })
+            .collect();
 
-        let message = OllamaChatMessage {
-            role: "user".into(),
-            content: prompt.into(),
-            images: Some(vec![image_base64.into()]),
-        };
+        OllamaChatRequest {
+            model: options.model.clone(),
+            messages,
+            stream,
+            options: OllamaOptions {
+                temperature: options.temperature,
+            },
+            format: options.format.clone(),
+        }
+    }
+}
 
-        let request = OllamaChatRequest {
-            model: "gemma3:4b".into(),
-            messages: vec![message],
-            stream: false,
-            options: OllamaOptions { temperature: 0.7 },
-        };
+#[async_trait]
+impl LlmBackend for OllamaService {
+    fn name(&self) -> &'static str {
+        "ollama"
+    }
 
-        let response = self
-            .client
-            .post(format!("{}/api/chat", self.base_url))
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| format!("Failed to connect to Ollama: {}", e))?;
+    async fn generate(&self, prompt: &str, options: &GenerateOptions) -> Result<String, AppError> {
+        let request = Self::generate_request(prompt, options, false);
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            return Err(format!("Ollama error ({}): {}", status, text));
-        }
+        let response = self.send("/api/generate", &request).await?;
 
-        let result: OllamaChatResponse = response
+        let result: OllamaGenerateResponse = response
             .json()
             .await
-            .map_err(|e| format!("Failed to parse Ollama response: {}", e))?;
+            .map_err(|e| request_error("Failed to parse Ollama response", e))?;
 
-        Ok(result.message.content)
+        Ok(result.response)
     }
 
-    pub async fn generate_roast_from_image(&self, image_base64: &str) -> Result<String, String> {
-        let prompt = "Look at this image carefully. Write a short, funny roast or comedic insult about what you see. \
-            Be playful and humorous like a comedy roast - gentle teasing, not mean-spirited. \
-            Keep it light-hearted and fun. Output ONLY the roast, no explanations or commentary. \
-            Make it punchy and memorable, 2-4 sentences max.";
-
-        let message = OllamaChatMessage {
-            role: "user".into(),
-            content: prompt.into(),
-            images: Some(vec![image_base64.into()]),
-        };
-
-        let request = OllamaChatRequest {
-            model: "gemma3:4b".into(),
-            messages: vec![message],
-            stream: false,
-            options: OllamaOptions { temperature: 0.9 },
-        };
+    async fn generate_stream(
+        &self,
+        prompt: &str,
+        options: &GenerateOptions,
+    ) -> Result<TokenStream, AppError> {
+        let request = Self::generate_request(prompt, options, true);
+        let response = self.send("/api/generate", &request).await?;
+
+        Ok(line_token_stream(response, |line| {
+            let chunk: OllamaGenerateStreamChunk = serde_json::from_str(line).map_err(|e| {
+                AppError::Upstream(format!("Failed to parse Ollama stream chunk: {}", e))
+            })?;
+            match chunk.error {
+                Some(error) => Err(AppError::Upstream(format!("Ollama error: {}", error))),
+                None => Ok((chunk.response, chunk.done)),
+            }
+        }))
+    }
 
-        let response = self
-            .client
-            .post(format!("{}/api/chat", self.base_url))
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| format!("Failed to connect to Ollama: {}", e))?;
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        options: &GenerateOptions,
+    ) -> Result<String, AppError> {
+        let request = Self::chat_request(messages, options, false);
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            return Err(format!("Ollama error ({}): {}", status, text));
-        }
+        let response = self.send("/api/chat", &request).await?;
 
         let result: OllamaChatResponse = response
             .json()
             .await
-            .map_err(|e| format!("Failed to parse Ollama response: {}", e))?;
+            .map_err(|e| request_error("Failed to parse Ollama response", e))?;
 
         Ok(result.message.content)
     }
 
-    // ========================================================================
-    // Embeddings
-    // ========================================================================
-
-    /// Generate embedding for a single text
-    pub async fn embed_text(&self, text: &str) -> Result<Vec<f32>, String> {
-        let embeddings = self.embed_texts(&[text.to_string()]).await?;
-        embeddings
-            .into_iter()
-            .next()
-            .ok_or_else(|| "No embedding returned".to_string())
+    async fn chat_stream(
+        &self,
+        messages: &[ChatMessage],
+        options: &GenerateOptions,
+    ) -> Result<TokenStream, AppError> {
+        let request = Self::chat_request(messages, options, true);
+        let response = self.send("/api/chat", &request).await?;
+
+        Ok(line_token_stream(response, |line| {
+            let chunk: OllamaChatStreamChunk = serde_json::from_str(line).map_err(|e| {
+                AppError::Upstream(format!("Failed to parse Ollama stream chunk: {}", e))
+            })?;
+            match chunk.error {
+                Some(error) => Err(AppError::Upstream(format!("Ollama error: {}", error))),
+                None => {
+                    let token = chunk.message.map(|m| m.content).unwrap_or_default();
+                    Ok((token, chunk.done))
+                }
+            }
+        }))
     }
 
-    /// Generate embeddings for multiple texts
-    pub async fn embed_texts(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+    async fn embed(&self, texts: &[String], model: &str) -> Result<Vec<Vec<f32>>, AppError> {
         let request = OllamaEmbeddingRequest {
-            model: EMBEDDING_MODEL.into(),
+            model: model.into(),
             input: EmbeddingInput::Multiple(texts.to_vec()),
         };
 
@@ -177,118 +185,19 @@ impl OllamaService {
             .json(&request)
             .send()
             .await
-            .map_err(|e| format!("Failed to connect to Ollama: {}", e))?;
+            .map_err(|e| request_error("Failed to connect to Ollama", e))?;
 
         if !response.status().is_success() {
             let status = response.status();
             let text = response.text().await.unwrap_or_default();
-            return Err(format!("Ollama embedding error ({}): {}", status, text));
+            return Err(status_error("Ollama embedding", status, &text));
         }
 
         let result: OllamaEmbeddingResponse = response
             .json()
             .await
-            .map_err(|e| format!("Failed to parse embedding response: {}", e))?;
+            .map_err(|e| request_error("Failed to parse embedding response", e))?;
 
         Ok(result.embeddings)
     }
-
-    /// Create a TextEmbedding from text
-    pub async fn create_text_embedding(&self, text: &str) -> Result<TextEmbedding, String> {
-        let embedding = self.embed_text(text).await?;
-        Ok(TextEmbedding {
-            text: text.to_string(),
-            embedding,
-        })
-    }
-
-    /// Create TextEmbeddings from multiple texts
-    pub async fn create_text_embeddings(
-        &self,
-        texts: &[String],
-    ) -> Result<Vec<TextEmbedding>, String> {
-        let embeddings = self.embed_texts(texts).await?;
-
-        Ok(texts
-            .iter()
-            .zip(embeddings)
-            .map(|(text, embedding)| TextEmbedding {
-                text: text.clone(),
-                embedding,
-            })
-            .collect())
-    }
-
-    pub async fn extract_words_from_image(
-        &self,
-        image_base64: &str,
-        word_library: &[String],
-    ) -> Result<Vec<String>, String> {
-        let word_list = word_library.join(", ");
-
-        let prompt = format!(
-            "Look at this image.\n\n\\n\n\
-        What is the SINGLE BEST word that describes:\n\
-        1. The main visible object/item or profession\n\
-        2. The character's emotion or state\n\
-        3. The overall style or theme\n\n\
-        Suggested words (use these if they fit, or choose your own): {}
-        Answer with exactly 3 words separated by commas.",
-            word_list
-        );
-        let message = OllamaChatMessage {
-            role: "user".into(),
-            content: prompt,
-            images: Some(vec![image_base64.into()]),
-        };
-
-        let request = OllamaChatRequest {
-            model: "gemma3:4b".into(),
-            messages: vec![message],
-            stream: false,
-            options: OllamaOptions { temperature: 0.3 }, // Lower temperature for more consistent output
-        };
-
-        let response = self
-            .client
-            .post(format!("{}/api/chat", self.base_url))
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| format!("Failed to connect to Ollama: {}", e))?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            return Err(format!("Ollama error ({}): {}", status, text));
-        }
-
-        let result: OllamaChatResponse = response
-            .json()
-            .await
-            .map_err(|e| format!("Failed to parse Ollama response: {}", e))?;
-
-        // Parse the response to extract the 3 words
-        let words: Vec<String> = result
-            .message
-            .content
-            .split(',')
-            .map(|s| s.trim().to_lowercase())
-            .filter(|s| !s.is_empty())
-            .take(3)
-            .map(|s| s.to_string())
-            .collect();
-
-        if words.len() != 3 {
-            return Err(format!("Expected 3 words but got {}", words.len()));
-        }
-
-        Ok(words)
-    }
-}
-
-impl Default for OllamaService {
-    fn default() -> Self {
-        Self::new()
-    }
 }
@@ -0,0 +1,270 @@
+use async_trait::async_trait;
+use base64::{engine::general_purpose, Engine};
+use reqwest::{Client, RequestBuilder, Response};
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::config::{LlmConfig, OpenAiConfig};
+use crate::error::AppError;
+use crate::models::{
+    OpenAiChatMessage, OpenAiChatRequest, OpenAiChatResponse, OpenAiChatStreamChunk, OpenAiContent,
+    OpenAiContentPart, OpenAiEmbeddingRequest, OpenAiEmbeddingResponse, OpenAiImageUrl,
+    OpenAiJsonSchema, OpenAiResponseFormat,
+};
+use crate::service::llm::{
+    line_token_stream, request_error, status_error, ChatMessage, GenerateOptions, LlmBackend,
+    TokenStream,
+};
+
+/// OpenAI-compatible `/v1/chat/completions` and `/v1/embeddings` servers,
+/// e.g. llama.cpp server, vLLM or LM Studio
+#[derive(Clone)]
+pub struct OpenAiService {
+    client: Client,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl OpenAiService {
+    pub fn new(config: &OpenAiConfig, llm: &LlmConfig) -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(llm.timeout_secs))
+            .build()
+            .unwrap_or_else(|_| Client::new());
+
+        Self {
+            client,
+            base_url: config.base_url.trim_end_matches('/').to_string(),
+            api_key: config.api_key.clone(),
+        }
+    }
+
+    fn post(&self, path: &str) -> RequestBuilder {
+        let request = self.client.post(format!("{}{}", self.base_url, path));
+        match &self.api_key {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        }
+    }
+
+    /// POST a JSON body and fail on non-2xx status
+    async fn send<T: Serialize>(&self, path: &str, body: &T) -> Result<Response, AppError> {
+        let response = self
+            .post(path)
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| request_error("Failed to connect to LLM server", e))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(status_error("LLM server", status, &text));
+        }
+
+        Ok(response)
+    }
+
+    fn chat_request(
+        messages: &[ChatMessage],
+        options: &GenerateOptions,
+        stream: bool,
+    ) -> OpenAiChatRequest {
+        let messages = messages
+            .iter()
+            .map(|m| {
+                let content = if m.images.is_empty() {
+                    OpenAiContent::Text(m.content.clone())
+                } else {
+                    let mut parts = vec![OpenAiContentPart::Text {
+                        text: m.content.clone(),
+                    }];
+                    parts.extend(m.images.iter().map(|image| OpenAiContentPart::ImageUrl {
+                        image_url: OpenAiImageUrl {
+                            url: format!("data:{};base64,{}", image_mime_type(image), image),
+                        },
+                    }));
+                    OpenAiContent::Parts(parts)
+                };
+
+                OpenAiChatMessage {
+                    role: m.role.clone(),
+                    content,
+                }
+            })
+            .collect();
+
+        OpenAiChatRequest {
+            model: options.model.clone(),
+            messages,
+            temperature: options.temperature,
+            stream,
+            response_format: options.format.clone().map(|schema| {
+                OpenAiResponseFormat::JsonSchema {
+                    json_schema: OpenAiJsonSchema {
+                        name: "response".into(),
+                        schema,
+                    },
+                }
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl LlmBackend for OpenAiService {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    async fn generate(&self, prompt: &str, options: &GenerateOptions) -> Result<String, AppError> {
+        self.chat(&[ChatMessage::user(prompt)], options).await
+    }
+
+    async fn generate_stream(
+        &self,
+        prompt: &str,
+        options: &GenerateOptions,
+    ) -> Result<TokenStream, AppError> {
+        self.chat_stream(&[ChatMessage::user(prompt)], options)
+            .await
+    }
+
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        options: &GenerateOptions,
+    ) -> Result<String, AppError> {
+        let request = Self::chat_request(messages, options, false);
+
+        let response = self.send("/chat/completions", &request).await?;
+
+        let result: OpenAiChatResponse = response
+            .json()
+            .await
+            .map_err(|e| request_error("Failed to parse LLM server response", e))?;
+
+        result
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|choice| choice.message.content)
+            .ok_or_else(|| AppError::Upstream("LLM server returned no content".into()))
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[ChatMessage],
+        options: &GenerateOptions,
+    ) -> Result<TokenStream, AppError> {
+        let request = Self::chat_request(messages, options, true);
+        let response = self.send("/chat/completions", &request).await?;
+
+        // Server-Sent Events: `data: {...}` lines, terminated by `data: [DONE]`
+        Ok(line_token_stream(response, |line| {
+            let Some(data) = line.strip_prefix("data:") else {
+                return Ok((String::new(), false));
+            };
+            let data = data.trim();
+            if data == "[DONE]" {
+                return Ok((String::new(), true));
+            }
+
+            let chunk: OpenAiChatStreamChunk = serde_json::from_str(data).map_err(|e| {
+                AppError::Upstream(format!("Failed to parse LLM stream chunk: {}", e))
+            })?;
+
+            Ok(chunk
+                .choices
+                .into_iter()
+                .next()
+                .map(|choice| {
+                    let done = choice.finish_reason.is_some();
+                    (choice.delta.content.unwrap_or_default(), done)
+                })
+                .unwrap_or_default())
+        }))
+    }
+
+    async fn embed(&self, texts: &[String], model: &str) -> Result<Vec<Vec<f32>>, AppError> {
+        let request = OpenAiEmbeddingRequest {
+            model: model.into(),
+            input: texts.to_vec(),
+        };
+
+        let response = self.send("/embeddings", &request).await?;
+
+        let mut result: OpenAiEmbeddingResponse = response
+            .json()
+            .await
+            .map_err(|e| request_error("Failed to parse embedding response", e))?;
+
+        result.data.sort_by_key(|d| d.index);
+        Ok(result.data.into_iter().map(|d| d.embedding).collect())
+    }
+}
+
+/// Guess an image's MIME type from its leading bytes; defaults to JPEG
+fn image_mime_type(image_base64: &str) -> &'static str {
+    // 16 base64 chars decode to the first 12 bytes, enough for every signature below
+    let head: String = image_base64.chars().take(16).collect();
+    let bytes = general_purpose::STANDARD.decode(head).unwrap_or_default();
+
+    match bytes.as_slice() {
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [b'G', b'I', b'F', b'8', ..] => "image/gif",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        _ => "image/jpeg",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_mime_type() {
+        let png = general_purpose::STANDARD.encode(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR");
+        let webp = general_purpose::STANDARD.encode(b"RIFF\0\0\0\0WEBPVP8 ");
+        let jpeg = general_purpose::STANDARD.encode(b"\xff\xd8\xff\xe0\0\x10JFIF\0\x01");
+        assert_eq!(image_mime_type(&png), "image/png");
+        assert_eq!(image_mime_type(&webp), "image/webp");
+        assert_eq!(image_mime_type(&jpeg), "image/jpeg");
+    }
+
+    #[test]
+    fn test_image_message_becomes_content_parts() {
+        let options = GenerateOptions {
+            model: "llava".into(),
+            temperature: 0.5,
+            format: None,
+        };
+        let request = OpenAiService::chat_request(
+            &[ChatMessage::user_with_image("describe", "aGVsbG8=")],
+            &options,
+            false,
+        );
+        let json = serde_json::to_value(&request).unwrap();
+        let parts = &json["messages"][0]["content"];
+        assert_eq!(parts[0]["type"], "text");
+        assert_eq!(parts[1]["type"], "image_url");
+        assert!(parts[1]["image_url"]["url"]
+            .as_str()
+            .unwrap()
+            .starts_with("data:image/jpeg;base64,"));
+        assert!(json.get("response_format").is_none());
+    }
+
+    #[test]
+    fn test_format_becomes_json_schema_response_format() {
+        let options = GenerateOptions {
+            model: "llava".into(),
+            temperature: 0.5,
+            format: Some(serde_json::json!({"type": "object"})),
+        };
+        let request = OpenAiService::chat_request(&[ChatMessage::user("hi")], &options, false);
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["response_format"]["type"], "json_schema");
+        assert_eq!(json["response_format"]["json_schema"]["schema"]["type"], "object");
+    }
+}
@@ -0,0 +1,335 @@
+//! CLIP image embeddings of every file in the images directory, so a captured
+//! photo can be matched by how it looks rather than only by words.
+//!
+//! Files are embedded at startup. Before each match the directory is checked
+//! again, and only new or modified files are embedded.
+
+use fastembed::{ImageEmbedding, ImageEmbeddingModel, ImageInitOptions};
+use once_cell::sync::OnceCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::SystemTime;
+
+use crate::error::AppError;
+use crate::lib::image_library::{is_image_file, IMAGE_URL_PREFIX};
+use crate::models::PhotoMatchCandidate;
+use crate::service::similarity::cosine_similarity;
+
+/// A file seen in the images directory, with the metadata it had when embedded
+#[derive(Clone)]
+struct PhotoEntry {
+    modified: Option<SystemTime>,
+    len: u64,
+    /// `None` if the file could not be decoded or embedded; it is not tried
+    /// again until it changes
+    embedding: Option<Vec<f32>>,
+}
+
+/// Embeddings by file name; replaced wholesale so readers never see a partial refresh
+type PhotoSnapshot = Arc<HashMap<String, PhotoEntry>>;
+
+struct PhotoCache {
+    model: Mutex<ImageEmbedding>,
+    images_dir: PathBuf,
+    entries: RwLock<PhotoSnapshot>,
+    /// Held while refreshing so concurrent requests don't embed the same file twice
+    refreshing: Mutex<()>,
+}
+
+static PHOTO_CACHE: OnceCell<PhotoCache> = OnceCell::new();
+
+fn cache() -> Result<&'static PhotoCache, AppError> {
+    PHOTO_CACHE.get().ok_or_else(|| {
+        AppError::LibraryEmpty(
+            "Photo embeddings are not enabled; set [matching] photo_embeddings or PHOTO_EMBEDDINGS"
+                .into(),
+        )
+    })
+}
+
+/// How well one library image matches the photo
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhotoSimilarity {
+    pub image_url: String,
+    /// Cosine similarity of the two image embeddings
+    pub score: f32,
+}
+
+impl PhotoCache {
+    fn embed(&self, images: Vec<image::DynamicImage>) -> Result<Vec<Vec<f32>>, String> {
+        let model = self
+            .model
+            .lock()
+            .map_err(|e| format!("Failed to acquire lock: {}", e))?;
+
+        model.embed_images(images).map_err(|e| e.to_string())
+    }
+
+    fn snapshot(&self) -> Result<PhotoSnapshot, String> {
+        self.entries
+            .read()
+            .map(|entries| Arc::clone(&entries))
+            .map_err(|e| format!("Failed to acquire lock: {}", e))
+    }
+
+    /// Bring the embeddings in line with the images directory: embed new and
+    /// modified files, forget deleted ones. Unreadable files are remembered as
+    /// failed, so they are skipped until they change.
+    fn refresh(&self) -> Result<PhotoSnapshot, String> {
+        let _guard = self
+            .refreshing
+            .lock()
+            .map_err(|e| format!("Failed to acquire lock: {}", e))?;
+        let current = self.snapshot()?;
+        let files = list_images(&self.images_dir)?;
+
+        let unchanged = files.len() == current.len()
+            && files.iter().all(|(filename, modified, len)| {
+                current
+                    .get(filename)
+                    .is_some_and(|entry| entry.modified == *modified && entry.len == *len)
+            });
+        if unchanged {
+            return Ok(current);
+        }
+
+        let mut entries = HashMap::with_capacity(files.len());
+        for (filename, modified, len) in files {
+            if let Some(entry) = current.get(&filename) {
+                if entry.modified == modified && entry.len == len {
+                    entries.insert(filename, entry.clone());
+                    continue;
+                }
+            }
+
+            let embedded = image::open(self.images_dir.join(&filename))
+                .map_err(|e| e.to_string())
+                .and_then(|image| self.embed(vec![image]))
+                .and_then(|embeddings| {
+                    embeddings
+                        .into_iter()
+                        .next()
+                        .ok_or_else(|| "no embedding returned".to_string())
+                });
+            if let Err(e) = &embedded {
+                tracing::warn!("Failed to embed image {}: {}", filename, e);
+            }
+            entries.insert(
+                filename,
+                PhotoEntry {
+                    modified,
+                    len,
+                    embedding: embedded.ok(),
+                },
+            );
+        }
+
+        let snapshot = Arc::new(entries);
+        *self
+            .entries
+            .write()
+            .map_err(|e| format!("Failed to acquire lock: {}", e))? = Arc::clone(&snapshot);
+
+        Ok(snapshot)
+    }
+}
+
+pub struct PhotoEmbeddingService;
+
+impl PhotoEmbeddingService {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Score every library image against the photo, best first.
+    /// CPU-bound; call from a blocking task.
+    pub fn rank(&self, photo: &[u8]) -> Result<Vec<PhotoSimilarity>, AppError> {
+        let cache = cache()?;
+        let photo = image::load_from_memory(photo)
+            .map_err(|e| AppError::Validation(format!("Could not decode the image: {}", e)))?;
+
+        let entries = cache.refresh().map_err(AppError::Internal)?;
+        if embedded_count(&entries) == 0 {
+            return Err(AppError::LibraryEmpty(
+                "No library images could be embedded".into(),
+            ));
+        }
+
+        let query = cache
+            .embed(vec![photo])
+            .map_err(|e| AppError::Internal(format!("Failed to embed photo: {}", e)))?
+            .into_iter()
+            .next()
+            .ok_or_else(|| AppError::Internal("No embedding returned for photo".into()))?;
+
+        let mut scored: Vec<PhotoSimilarity> = entries
+            .iter()
+            .filter_map(|(filename, entry)| {
+                Some(PhotoSimilarity {
+                    image_url: format!("{}{}", IMAGE_URL_PREFIX, filename),
+                    score: cosine_similarity(&query, entry.embedding.as_ref()?),
+                })
+            })
+            .collect();
+        scored.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        Ok(scored)
+    }
+}
+
+impl Default for PhotoEmbeddingService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Combine photo scores with word scores by image URL:
+/// `(1 - word_weight) * photo + word_weight * words`.
+/// Without word scores the photo score stands alone. With them, only images
+/// that have both are kept, since the others have no tags in the library.
+pub fn fuse_scores(
+    photo: Vec<PhotoSimilarity>,
+    word_scores: Option<&HashMap<String, f32>>,
+    word_weight: f32,
+    top_k: usize,
+    min_score: Option<f32>,
+) -> Vec<PhotoMatchCandidate> {
+    let mut fused: Vec<PhotoMatchCandidate> = photo
+        .into_iter()
+        .filter_map(|photo| {
+            let word_score = match word_scores {
+                Some(scores) => Some(*scores.get(&photo.image_url)?),
+                None => None,
+            };
+            let score = match word_score {
+                Some(word_score) => (1.0 - word_weight) * photo.score + word_weight * word_score,
+                None => photo.score,
+            };
+            Some(PhotoMatchCandidate {
+                image_url: photo.image_url,
+                score,
+                photo_score: photo.score,
+                word_score,
+            })
+        })
+        .filter(|candidate| min_score.is_none_or(|min| candidate.score >= min))
+        .collect();
+
+    fused.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    fused.truncate(top_k);
+    fused
+}
+
+/// Files in the snapshot that have an embedding
+fn embedded_count(entries: &PhotoSnapshot) -> usize {
+    entries
+        .values()
+        .filter(|entry| entry.embedding.is_some())
+        .count()
+}
+
+/// Image files in the directory with their modification time and size
+fn list_images(images_dir: &Path) -> Result<Vec<(String, Option<SystemTime>, u64)>, String> {
+    let entries = std::fs::read_dir(images_dir)
+        .map_err(|e| format!("Failed to read {}: {}", images_dir.display(), e))?;
+
+    Ok(entries
+        .flatten()
+        .filter_map(|entry| {
+            let filename = entry.file_name().to_str()?.to_string();
+            let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+            is_image_file(&filename).then(|| (filename, metadata.modified().ok(), metadata.len()))
+        })
+        .collect())
+}
+
+/// Load the CLIP image model and embed every library image (call once at startup)
+pub fn init_photo_embeddings(images_dir: &Path) -> Result<(), String> {
+    tracing::info!("Initializing image embedding model...");
+
+    let options =
+        ImageInitOptions::new(ImageEmbeddingModel::ClipVitB32).with_show_download_progress(true);
+    let model = ImageEmbedding::try_new(options)
+        .map_err(|e| format!("Failed to initialize image embedding model: {}", e))?;
+
+    let cache = PhotoCache {
+        model: Mutex::new(model),
+        images_dir: images_dir.to_path_buf(),
+        entries: RwLock::new(Arc::new(HashMap::new())),
+        refreshing: Mutex::new(()),
+    };
+
+    tracing::info!("Pre-computing image embeddings...");
+    let count = embedded_count(&cache.refresh()?);
+    tracing::info!("Pre-computed embeddings for {} images", count);
+
+    PHOTO_CACHE
+        .set(cache)
+        .map_err(|_| "Photo embedding cache is already initialized".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn photo(image_url: &str, score: f32) -> PhotoSimilarity {
+        PhotoSimilarity {
+            image_url: image_url.into(),
+            score,
+        }
+    }
+
+    #[test]
+    fn test_fuse_scores_weights_words() {
+        let photos = vec![photo("/images/a.png", 0.9), photo("/images/b.png", 0.8)];
+        let words = HashMap::from([
+            ("/images/a.png".to_string(), 0.0),
+            ("/images/b.png".to_string(), 1.0),
+        ]);
+
+        let fused = fuse_scores(photos.clone(), Some(&words), 0.5, 5, None);
+        assert_eq!(fused[0].image_url, "/images/b.png");
+        assert!((fused[0].score - 0.9).abs() < 1e-6);
+        assert_eq!(fused[0].photo_score, 0.8);
+        assert_eq!(fused[0].word_score, Some(1.0));
+
+        let fused = fuse_scores(photos, None, 0.5, 1, None);
+        assert_eq!(fused.len(), 1);
+        assert_eq!(fused[0].image_url, "/images/a.png");
+        assert_eq!(fused[0].word_score, None);
+    }
+
+    #[test]
+    fn test_fuse_scores_drops_untagged_and_low_scores() {
+        let photos = vec![photo("/images/a.png", 0.9), photo("/images/b.png", 0.2)];
+        let words = HashMap::from([("/images/b.png".to_string(), 0.4)]);
+
+        let fused = fuse_scores(photos.clone(), Some(&words), 0.5, 5, None);
+        assert_eq!(fused.len(), 1);
+        assert_eq!(fused[0].image_url, "/images/b.png");
+
+        assert!(fuse_scores(photos, Some(&words), 0.5, 5, Some(0.5)).is_empty());
+    }
+
+    #[test]
+    fn test_list_images_skips_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.png"), b"png").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), b"text").unwrap();
+        std::fs::create_dir(dir.path().join("nested.png")).unwrap();
+
+        let files = list_images(dir.path()).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].0, "a.png");
+        assert_eq!(files[0].2, 3);
+    }
+}
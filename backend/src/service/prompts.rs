@@ -0,0 +1,340 @@
+//! Prompt templates for poems, roasts, roast checks and tag extraction.
+//!
+//! Each template is a text file in the prompts directory, e.g. `roast.txt`,
+//! with `{variable}` placeholders; `{{` and `}}` stand for literal braces.
+//! A missing file falls back to the built-in text. Templates are checked when
+//! loaded, and a reload that finds a broken one keeps the previous set.
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use crate::error::AppError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PromptKind {
+    PoemText,
+    PoemImage,
+    Roast,
+    RoastCheck,
+    ExtractTags,
+}
+
+impl PromptKind {
+    pub const ALL: [PromptKind; 5] = [
+        PromptKind::PoemText,
+        PromptKind::PoemImage,
+        PromptKind::Roast,
+        PromptKind::RoastCheck,
+        PromptKind::ExtractTags,
+    ];
+
+    /// Template name; the file is `<name>.txt`
+    pub fn name(self) -> &'static str {
+        match self {
+            PromptKind::PoemText => "poem_text",
+            PromptKind::PoemImage => "poem_image",
+            PromptKind::Roast => "roast",
+            PromptKind::RoastCheck => "roast_check",
+            PromptKind::ExtractTags => "extract_tags",
+        }
+    }
+
+    /// Placeholders the template may use
+    fn variables(self) -> &'static [&'static str] {
+        match self {
+            PromptKind::PoemText => &["theme", "form"],
+            PromptKind::PoemImage => &[],
+            PromptKind::Roast => &["intensity", "family_friendly"],
+            PromptKind::RoastCheck => &["roast", "family_friendly"],
+            PromptKind::ExtractTags => &["guidance", "word_list", "max_extra"],
+        }
+    }
+
+    fn builtin(self) -> &'static str {
+        match self {
+            PromptKind::PoemText => include_str!("../../prompts/poem_text.txt"),
+            PromptKind::PoemImage => include_str!("../../prompts/poem_image.txt"),
+            PromptKind::Roast => include_str!("../../prompts/roast.txt"),
+            PromptKind::RoastCheck => include_str!("../../prompts/roast_check.txt"),
+            PromptKind::ExtractTags => include_str!("../../prompts/extract_tags.txt"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PromptSource {
+    /// Read from the prompts directory
+    File,
+    /// Compiled in, as no file overrides it
+    Builtin,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PromptTemplate {
+    pub name: &'static str,
+    /// `<name>@` and the first 8 hex digits of the text's SHA-256
+    pub version: String,
+    pub source: PromptSource,
+    pub variables: &'static [&'static str],
+    pub text: String,
+}
+
+/// A filled-in template and the version it came from
+#[derive(Debug, Clone)]
+pub struct RenderedPrompt {
+    pub text: String,
+    pub version: String,
+}
+
+type PromptSet = Arc<HashMap<PromptKind, PromptTemplate>>;
+
+pub struct PromptRegistry {
+    dir: PathBuf,
+    templates: RwLock<PromptSet>,
+}
+
+impl PromptRegistry {
+    /// Load every template from `dir`, falling back to the built-in ones
+    pub fn load(dir: &Path) -> Result<Self, AppError> {
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            templates: RwLock::new(Arc::new(load_templates(dir)?)),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Read the templates again; on error the current ones stay in use
+    pub fn reload(&self) -> Result<Vec<PromptTemplate>, AppError> {
+        let templates = Arc::new(load_templates(&self.dir)?);
+        *self
+            .templates
+            .write()
+            .map_err(|e| AppError::Internal(format!("Failed to acquire lock: {}", e)))? =
+            Arc::clone(&templates);
+
+        Ok(sorted(&templates))
+    }
+
+    pub fn list(&self) -> Result<Vec<PromptTemplate>, AppError> {
+        let templates = self.snapshot()?;
+        Ok(sorted(&templates))
+    }
+
+    pub fn render(
+        &self,
+        kind: PromptKind,
+        vars: &[(&str, &str)],
+    ) -> Result<RenderedPrompt, AppError> {
+        let templates = self.snapshot()?;
+        let template = templates
+            .get(&kind)
+            .ok_or_else(|| AppError::Internal(format!("No {} prompt loaded", kind.name())))?;
+        let text = render_template(&template.text, vars).map_err(|e| {
+            AppError::Internal(format!("Failed to render {} prompt: {}", kind.name(), e))
+        })?;
+
+        Ok(RenderedPrompt {
+            text,
+            version: template.version.clone(),
+        })
+    }
+
+    fn snapshot(&self) -> Result<PromptSet, AppError> {
+        self.templates
+            .read()
+            .map(|templates| Arc::clone(&templates))
+            .map_err(|e| AppError::Internal(format!("Failed to acquire lock: {}", e)))
+    }
+}
+
+fn sorted(templates: &HashMap<PromptKind, PromptTemplate>) -> Vec<PromptTemplate> {
+    PromptKind::ALL
+        .iter()
+        .filter_map(|kind| templates.get(kind).cloned())
+        .collect()
+}
+
+fn load_templates(dir: &Path) -> Result<HashMap<PromptKind, PromptTemplate>, AppError> {
+    let mut templates = HashMap::new();
+    for kind in PromptKind::ALL {
+        let path = dir.join(format!("{}.txt", kind.name()));
+        let (text, source) = match std::fs::read_to_string(&path) {
+            Ok(text) => (text, PromptSource::File),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                (kind.builtin().to_string(), PromptSource::Builtin)
+            }
+            Err(e) => {
+                return Err(AppError::Io(format!(
+                    "Failed to read {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        };
+        let text = text.trim_end().to_string();
+
+        let blanks: Vec<(&str, &str)> = kind.variables().iter().map(|v| (*v, "")).collect();
+        render_template(&text, &blanks).map_err(|e| {
+            AppError::Validation(format!(
+                "{}: {} (variables: {})",
+                path.display(),
+                e,
+                kind.variables().join(", ")
+            ))
+        })?;
+
+        let digest = format!("{:x}", Sha256::digest(text.as_bytes()));
+        templates.insert(
+            kind,
+            PromptTemplate {
+                name: kind.name(),
+                version: format!("{}@{}", kind.name(), &digest[..8]),
+                source,
+                variables: kind.variables(),
+                text,
+            },
+        );
+    }
+
+    Ok(templates)
+}
+
+/// Fill `{name}` placeholders from `vars`; `{{` and `}}` are literal braces
+fn render_template(template: &str, vars: &[(&str, &str)]) -> Result<String, String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(pos) = rest.find(['{', '}']) {
+        out.push_str(&rest[..pos]);
+        let tail = &rest[pos..];
+
+        if let Some(after) = tail.strip_prefix("{{") {
+            out.push('{');
+            rest = after;
+        } else if let Some(after) = tail.strip_prefix("}}") {
+            out.push('}');
+            rest = after;
+        } else if tail.starts_with('}') {
+            return Err("unmatched '}'; write '}}' for a literal brace".into());
+        } else {
+            let end = tail
+                .find('}')
+                .ok_or("unclosed '{'; write '{{' for a literal brace")?;
+            let name = tail[1..end].trim();
+            let value = vars
+                .iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| *value)
+                .ok_or_else(|| format!("unknown variable {{{}}}", name))?;
+            out.push_str(value);
+            rest = &tail[end + 1..];
+        }
+    }
+    out.push_str(rest);
+
+    Ok(out)
+}
+
+/// Poll the prompts directory every `interval` and reload when it changes.
+/// A broken template is logged and the previous templates stay in use.
+pub fn spawn_prompt_watcher(registry: Arc<PromptRegistry>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut last_seen = prompts_mtime(registry.dir());
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+
+        loop {
+            ticker.tick().await;
+
+            let current = prompts_mtime(registry.dir());
+            if current == last_seen {
+                continue;
+            }
+            last_seen = current;
+
+            match registry.reload() {
+                Ok(templates) => {
+                    let versions: Vec<&str> =
+                        templates.iter().map(|t| t.version.as_str()).collect();
+                    tracing::info!("Reloaded prompts: {}", versions.join(", "));
+                }
+                Err(e) => tracing::error!("Failed to reload prompts, keeping the old ones: {}", e),
+            }
+        }
+    });
+}
+
+/// Latest modification time of the directory and its files
+fn prompts_mtime(dir: &Path) -> Option<SystemTime> {
+    let mtime = |path: &Path| path.metadata().and_then(|m| m.modified()).ok();
+
+    let mut latest = mtime(dir);
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            latest = latest.max(mtime(&entry.path()));
+        }
+    }
+
+    latest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_template() {
+        let vars = [("theme", "spring")];
+        assert_eq!(
+            render_template("Theme: {theme} {{literal}}", &vars).unwrap(),
+            "Theme: spring {literal}"
+        );
+        assert_eq!(
+            render_template("{theme}", &[]).unwrap_err(),
+            "unknown variable {theme}"
+        );
+        assert!(render_template("{theme", &vars).is_err());
+        assert!(render_template("theme}", &vars).is_err());
+    }
+
+    #[test]
+    fn test_builtin_templates_are_valid() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = PromptRegistry::load(dir.path()).unwrap();
+        let templates = registry.list().unwrap();
+        assert_eq!(templates.len(), PromptKind::ALL.len());
+        assert!(templates.iter().all(|t| t.source == PromptSource::Builtin));
+        assert!(templates[2].version.starts_with("roast@"));
+    }
+
+    #[test]
+    fn test_files_override_and_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("roast.txt"), "Roast gently.\n").unwrap();
+        let registry = PromptRegistry::load(dir.path()).unwrap();
+        let first = registry.render(PromptKind::Roast, &[]).unwrap();
+        assert_eq!(first.text, "Roast gently.");
+
+        std::fs::write(dir.path().join("roast.txt"), "Roast {target}.").unwrap();
+        let error = registry.reload().unwrap_err();
+        assert!(matches!(error, AppError::Validation(_)), "{:?}", error);
+        assert_eq!(
+            registry.render(PromptKind::Roast, &[]).unwrap().text,
+            "Roast gently."
+        );
+
+        std::fs::write(dir.path().join("roast.txt"), "Roast harder.").unwrap();
+        registry.reload().unwrap();
+        let second = registry.render(PromptKind::Roast, &[]).unwrap();
+        assert_eq!(second.text, "Roast harder.");
+        assert_ne!(first.version, second.version);
+    }
+}
@@ -0,0 +1,157 @@
+//! Safety checks a roast must pass before it is returned or printed: a
+//! blocklist of body-shaming and protected-attribute words, and optionally a
+//! second pass where the model reviews the roast itself.
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::config::RoastConfig;
+use crate::lib::blocklist::Blocklist;
+use crate::service::structured::StructuredOutput;
+
+/// Words no roast at the booth should use. Slurs are left to the operator's
+/// `blocklist_path` file rather than spelled out here, and words with common
+/// harmless senses ("race", "short") to the classifier.
+const BUILTIN_BLOCKLIST: &[&str] = &[
+    // Body shaming
+    "fat",
+    "obese",
+    "chubby",
+    "plump",
+    "skinny",
+    "anorexic",
+    "ugly",
+    "hideous",
+    "double chin",
+    "beer belly",
+    "muffin top",
+    "love handles",
+    "bald",
+    "midget",
+    "pimple",
+    "acne",
+    "wrinkle",
+    "big nose",
+    // Protected attributes
+    "racial",
+    "skin colour",
+    "skin color",
+    "ethnic",
+    "ethnicity",
+    "religion",
+    "religious",
+    "gay",
+    "lesbian",
+    "transgender",
+    "disabled",
+    "disability",
+    "retard",
+    "retarded",
+    "cripple",
+    "crippled",
+    "autistic",
+    "wheelchair",
+    "immigrant",
+];
+
+/// The blocklist and classifier settings, loaded once at startup
+#[derive(Debug, Clone)]
+pub struct RoastGuard {
+    blocklist: Blocklist,
+    /// Whether the model reviews each roast
+    pub classifier: bool,
+    /// Extra generations when a roast is rejected
+    pub retries: u32,
+}
+
+impl RoastGuard {
+    /// The built-in blocklist plus the operator's file, if configured; a
+    /// configured file that can't be read is an error
+    pub fn load(config: &RoastConfig) -> Result<Self, String> {
+        let mut blocklist = Blocklist::new(BUILTIN_BLOCKLIST);
+        if let Some(path) = &config.blocklist_path {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            blocklist.extend(Blocklist::parse(&text));
+        }
+
+        Ok(Self {
+            blocklist,
+            classifier: config.classifier,
+            retries: config.retries,
+        })
+    }
+
+    /// The blocked term `roast` uses, if any
+    pub fn blocked_term(&self, roast: &str) -> Option<String> {
+        self.blocklist.find(roast)
+    }
+}
+
+/// The classifier's answer about one roast
+#[derive(Debug, Deserialize)]
+pub struct SafetyVerdict {
+    pub safe: bool,
+    #[serde(default)]
+    pub reason: String,
+}
+
+impl StructuredOutput for SafetyVerdict {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "safe": {"type": "boolean"},
+                "reason": {"type": "string"},
+            },
+            "required": ["safe"],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::structured::parse_structured;
+
+    #[test]
+    fn test_builtin_blocklist() {
+        let guard = RoastGuard::load(&RoastConfig::default()).unwrap();
+        assert_eq!(
+            guard.blocked_term("Those love-handles deserve a hat."),
+            Some("love handles".into())
+        );
+        assert_eq!(guard.blocked_term("That hat has seen things."), None);
+    }
+
+    #[test]
+    fn test_load_extends_with_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("blocklist.txt");
+        std::fs::write(&path, "# booth extras\nsocks\n").unwrap();
+        let config = RoastConfig {
+            blocklist_path: Some(path),
+            ..RoastConfig::default()
+        };
+        let guard = RoastGuard::load(&config).unwrap();
+        assert_eq!(guard.blocked_term("Nice socks."), Some("socks".into()));
+        assert_eq!(guard.blocked_term("So fat."), Some("fat".into()));
+
+        let missing = RoastConfig {
+            blocklist_path: Some(dir.path().join("missing.txt")),
+            ..RoastConfig::default()
+        };
+        assert!(RoastGuard::load(&missing).is_err());
+    }
+
+    #[test]
+    fn test_safety_verdict() {
+        let verdict: SafetyVerdict =
+            parse_structured(r#"{"safe": false, "reason": "weight"}"#).unwrap();
+        assert!(!verdict.safe);
+        assert_eq!(verdict.reason, "weight");
+        let verdict: SafetyVerdict = parse_structured(r#"{"safe": true}"#).unwrap();
+        assert!(verdict.safe);
+        assert!(parse_structured::<SafetyVerdict>(r#"{"reason": "x"}"#).is_err());
+    }
+}
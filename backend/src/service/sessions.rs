@@ -0,0 +1,452 @@
+//! In-memory conversations for `/chat/session`.
+//!
+//! A session keeps its messages server-side; each image is stored once and
+//! messages refer to it by id. The history is bounded: the opening exchange is
+//! always kept, and the oldest follow-ups are dropped to fit
+//! `max_history_chars`. Sessions idle for `ttl_secs` are forgotten.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::config::{RoastIntensity, SessionsConfig};
+use crate::error::AppError;
+use crate::service::jobs::unix_now;
+use crate::service::llm::ChatMessage;
+
+/// What an attached image counts for against `max_history_chars`
+const IMAGE_COST_CHARS: usize = 2_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionKind {
+    /// Free conversation
+    #[default]
+    Chat,
+    /// Opens with a poem about an image or a theme
+    Poem,
+    /// Opens with a roast of an image; every reply passes the roast safety checks
+    Roast,
+}
+
+/// How a session's replies are generated, fixed when it is created
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct SessionSettings {
+    pub kind: SessionKind,
+    /// Only for roast sessions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub intensity: Option<RoastIntensity>,
+    pub family_friendly: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+/// One message as stored
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionMessage {
+    pub role: Role,
+    pub content: String,
+    /// Id of the attached image, e.g. `image-1`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+}
+
+/// A session as shown to clients, without image data
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionView {
+    pub id: String,
+    #[serde(flatten)]
+    pub settings: SessionSettings,
+    /// Unix timestamps, in seconds
+    pub created_at: u64,
+    pub last_active_at: u64,
+    pub expires_at: u64,
+    pub messages: Vec<SessionMessage>,
+    /// Earlier messages dropped to keep the history within bounds
+    pub dropped_messages: usize,
+}
+
+struct Session {
+    id: String,
+    settings: SessionSettings,
+    created_at: u64,
+    last_active_at: u64,
+    messages: Vec<SessionMessage>,
+    /// Base64 image data by attachment id
+    images: HashMap<String, String>,
+    next_image: usize,
+    dropped_messages: usize,
+    /// A reply is being generated; a second message must wait
+    busy: bool,
+}
+
+impl Session {
+    fn view(&self, ttl_secs: u64) -> SessionView {
+        SessionView {
+            id: self.id.clone(),
+            settings: self.settings,
+            created_at: self.created_at,
+            last_active_at: self.last_active_at,
+            expires_at: self.last_active_at + ttl_secs,
+            messages: self.messages.clone(),
+            dropped_messages: self.dropped_messages,
+        }
+    }
+}
+
+/// Shared handle to the sessions; cheap to clone
+#[derive(Clone)]
+pub struct SessionStore {
+    inner: Arc<Mutex<HashMap<String, Session>>>,
+    config: SessionsConfig,
+}
+
+impl SessionStore {
+    pub fn new(config: &SessionsConfig) -> Self {
+        Self {
+            inner: Arc::default(),
+            config: config.clone(),
+        }
+    }
+
+    /// Sessions still alive; expired ones are dropped on every access
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Session>> {
+        let mut sessions = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        prune_expired(&mut sessions, unix_now(), self.config.ttl_secs);
+        sessions
+    }
+
+    /// Start an empty session. Live sessions are never dropped to make room,
+    /// so a full store refuses new ones until some expire or are deleted.
+    pub fn create(&self, settings: SessionSettings) -> Result<SessionView, AppError> {
+        let mut sessions = self.lock();
+        if sessions.len() >= self.config.max_sessions {
+            return Err(AppError::Unavailable(
+                "Too many chat sessions are open, try again later".into(),
+            ));
+        }
+
+        let now = unix_now();
+        let session = Session {
+            id: new_session_id()?,
+            settings,
+            created_at: now,
+            last_active_at: now,
+            messages: Vec::new(),
+            images: HashMap::new(),
+            next_image: 1,
+            dropped_messages: 0,
+            busy: false,
+        };
+        let view = session.view(self.config.ttl_secs);
+        sessions.insert(session.id.clone(), session);
+
+        Ok(view)
+    }
+
+    pub fn get(&self, id: &str) -> Result<SessionView, AppError> {
+        let sessions = self.lock();
+        find(&sessions, id).map(|s| s.view(self.config.ttl_secs))
+    }
+
+    /// Forget a session and its images, returning it as it was
+    pub fn delete(&self, id: &str) -> Result<SessionView, AppError> {
+        self.lock()
+            .remove(id)
+            .map(|s| s.view(self.config.ttl_secs))
+            .ok_or_else(|| not_found(id))
+    }
+
+    /// Claim the session for one user message and build the conversation to
+    /// send to the model. Only one message per session is answered at a time.
+    pub fn begin_turn(
+        &self,
+        id: &str,
+        message: String,
+        image_base64: Option<String>,
+    ) -> Result<Turn, AppError> {
+        let mut sessions = self.lock();
+        let session = sessions.get_mut(id).ok_or_else(|| not_found(id))?;
+        if session.busy {
+            return Err(AppError::Conflict(format!(
+                "Session {} is still answering the previous message",
+                id
+            )));
+        }
+
+        let pending = SessionMessage {
+            role: Role::User,
+            content: message,
+            image: None,
+        };
+        let mut history = session.messages.clone();
+        history.push(pending.clone());
+        trim(&mut history, self.config.max_history_chars);
+
+        let last = history.len() - 1;
+        let messages = history
+            .iter()
+            .enumerate()
+            .map(|(i, message)| {
+                let image = if i == last {
+                    image_base64.clone()
+                } else {
+                    message
+                        .image
+                        .as_ref()
+                        .and_then(|image| session.images.get(image).cloned())
+                };
+                to_chat_message(message, image)
+            })
+            .collect();
+
+        session.busy = true;
+        session.last_active_at = unix_now();
+
+        Ok(Turn {
+            store: self.clone(),
+            id: id.to_string(),
+            settings: session.settings,
+            messages,
+            pending,
+            image_base64,
+            finished: false,
+        })
+    }
+}
+
+/// One user message being answered. Dropping it without `finish`, e.g. when
+/// generation fails, leaves the history as it was.
+pub struct Turn {
+    store: SessionStore,
+    id: String,
+    pub settings: SessionSettings,
+    /// The bounded history plus the new message, for the model
+    pub messages: Vec<ChatMessage>,
+    pending: SessionMessage,
+    image_base64: Option<String>,
+    finished: bool,
+}
+
+impl Turn {
+    /// Record the message and its reply
+    pub fn finish(mut self, reply: String) -> Result<SessionView, AppError> {
+        self.finished = true;
+        let mut sessions = self.store.lock();
+        let session = sessions
+            .get_mut(&self.id)
+            .ok_or_else(|| not_found(&self.id))?;
+
+        let mut message = self.pending.clone();
+        if let Some(image) = self.image_base64.take() {
+            let image_id = format!("image-{}", session.next_image);
+            session.next_image += 1;
+            session.images.insert(image_id.clone(), image);
+            message.image = Some(image_id);
+        }
+        session.messages.push(message);
+        session.messages.push(SessionMessage {
+            role: Role::Assistant,
+            content: reply,
+            image: None,
+        });
+
+        session.dropped_messages +=
+            trim(&mut session.messages, self.store.config.max_history_chars);
+        let messages = &session.messages;
+        session
+            .images
+            .retain(|id, _| messages.iter().any(|m| m.image.as_ref() == Some(id)));
+        session.busy = false;
+        session.last_active_at = unix_now();
+
+        Ok(session.view(self.store.config.ttl_secs))
+    }
+}
+
+impl Drop for Turn {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        if let Some(session) = self.store.lock().get_mut(&self.id) {
+            session.busy = false;
+        }
+    }
+}
+
+fn find<'a>(sessions: &'a HashMap<String, Session>, id: &str) -> Result<&'a Session, AppError> {
+    sessions.get(id).ok_or_else(|| not_found(id))
+}
+
+fn not_found(id: &str) -> AppError {
+    AppError::NotFound(format!("Session {} not found or expired", id))
+}
+
+fn prune_expired(sessions: &mut HashMap<String, Session>, now: u64, ttl_secs: u64) {
+    sessions.retain(|_, s| s.busy || s.last_active_at + ttl_secs > now);
+}
+
+/// Drop the oldest follow-up exchanges until the history fits `max_chars`.
+/// The opening exchange and the latest message, with its reply if it has one,
+/// are always kept. Returns how many messages were dropped.
+fn trim(messages: &mut Vec<SessionMessage>, max_chars: usize) -> usize {
+    let tail = match messages.last() {
+        Some(message) if message.role == Role::Assistant => 2,
+        _ => 1,
+    };
+
+    let mut dropped = 0;
+    while messages.iter().map(cost).sum::<usize>() > max_chars && messages.len() >= 4 + tail {
+        messages.drain(2..4);
+        dropped += 2;
+    }
+
+    dropped
+}
+
+fn cost(message: &SessionMessage) -> usize {
+    message.content.chars().count() + message.image.as_ref().map_or(0, |_| IMAGE_COST_CHARS)
+}
+
+fn to_chat_message(message: &SessionMessage, image_base64: Option<String>) -> ChatMessage {
+    let chat = match message.role {
+        Role::User => ChatMessage::user(message.content.clone()),
+        Role::Assistant => ChatMessage::assistant(message.content.clone()),
+    };
+    ChatMessage {
+        images: image_base64.into_iter().collect(),
+        ..chat
+    }
+}
+
+/// 128 random bits as hex; ids are the only key to a session's photos
+fn new_session_id() -> Result<String, AppError> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes)
+        .map_err(|e| AppError::Internal(format!("Failed to generate session id: {}", e)))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(max_history_chars: usize) -> SessionStore {
+        SessionStore::new(&SessionsConfig {
+            max_sessions: 2,
+            max_history_chars,
+            ..SessionsConfig::default()
+        })
+    }
+
+    fn chat() -> SessionSettings {
+        SessionSettings {
+            kind: SessionKind::Chat,
+            intensity: None,
+            family_friendly: true,
+        }
+    }
+
+    fn say(store: &SessionStore, id: &str, message: &str, reply: &str) -> SessionView {
+        let turn = store.begin_turn(id, message.into(), None).unwrap();
+        turn.finish(reply.into()).unwrap()
+    }
+
+    #[test]
+    fn test_turns_build_the_conversation() {
+        let store = store(10_000);
+        let id = store.create(chat()).unwrap().id;
+        assert_eq!(id.len(), 32);
+
+        let turn = store
+            .begin_turn(&id, "Roast this".into(), Some("aGVsbG8=".into()))
+            .unwrap();
+        assert_eq!(turn.messages.len(), 1);
+        assert_eq!(turn.messages[0].images, ["aGVsbG8="]);
+        let view = turn.finish("Nice hat.".into()).unwrap();
+        assert_eq!(view.messages[0].image.as_deref(), Some("image-1"));
+
+        let turn = store.begin_turn(&id, "Funnier".into(), None).unwrap();
+        let roles: Vec<&str> = turn.messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["user", "assistant", "user"]);
+        assert_eq!(turn.messages[0].images, ["aGVsbG8="]);
+        assert!(turn.messages[2].images.is_empty());
+    }
+
+    #[test]
+    fn test_one_turn_at_a_time() {
+        let store = store(10_000);
+        let id = store.create(chat()).unwrap().id;
+
+        let turn = store.begin_turn(&id, "Hi".into(), None).unwrap();
+        let error = store.begin_turn(&id, "Hello?".into(), None).err().unwrap();
+        assert!(matches!(error, AppError::Conflict(_)), "{:?}", error);
+
+        // A failed turn leaves the history untouched
+        drop(turn);
+        let turn = store.begin_turn(&id, "Hi".into(), None).unwrap();
+        assert_eq!(turn.messages.len(), 1);
+    }
+
+    #[test]
+    fn test_history_keeps_opening_and_latest() {
+        let store = store(30);
+        let id = store.create(chat()).unwrap().id;
+        say(&store, &id, "poem", "roses");
+        say(&store, &id, "funnier", "clown roses");
+        let view = say(&store, &id, "shorter", "rose");
+
+        let contents: Vec<&str> = view.messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["poem", "roses", "shorter", "rose"]);
+        assert_eq!(view.dropped_messages, 2);
+    }
+
+    #[test]
+    fn test_trimmed_images_are_forgotten() {
+        let store = store(IMAGE_COST_CHARS);
+        let id = store.create(chat()).unwrap().id;
+        say(&store, &id, "poem", "roses");
+        store
+            .begin_turn(&id, "this one".into(), Some("aGVsbG8=".into()))
+            .unwrap()
+            .finish("ok".into())
+            .unwrap();
+        say(&store, &id, "again", "fine");
+
+        let sessions = store.lock();
+        assert!(sessions[&id].images.is_empty());
+    }
+
+    #[test]
+    fn test_full_store_and_expiry() {
+        let store = store(10_000);
+        let first = store.create(chat()).unwrap().id;
+        let second = store.create(chat()).unwrap().id;
+
+        // Full: no one else's session is dropped to make room
+        assert!(matches!(
+            store.create(chat()),
+            Err(AppError::Unavailable(_))
+        ));
+        assert!(store.get(&first).is_ok());
+        assert!(store.get(&second).is_ok());
+
+        // An expired session frees its slot
+        store
+            .inner
+            .lock()
+            .unwrap()
+            .get_mut(&first)
+            .unwrap()
+            .last_active_at -= 3600;
+        assert!(store.create(chat()).is_ok());
+        assert!(matches!(store.get(&first), Err(AppError::NotFound(_))));
+        assert!(store.get(&second).is_ok());
+    }
+}
@@ -101,6 +101,25 @@ pub fn average_embeddings(embeddings: &[Vec<f32>]) -> Option<Vec<f32>> {
     Some(avg)
 }
 
+/// Calculate the weighted average of multiple embeddings
+/// Weights are relative and need not sum to 1
+pub fn weighted_average_embeddings(embeddings: &[Vec<f32>], weights: &[f32]) -> Option<Vec<f32>> {
+    let total: f32 = weights.iter().take(embeddings.len()).sum();
+    if embeddings.is_empty() || embeddings.len() != weights.len() || total <= 0.0 {
+        return None;
+    }
+
+    let dim = embeddings[0].len();
+    let mut avg = vec![0.0; dim];
+    for (embedding, weight) in embeddings.iter().zip(weights) {
+        for (i, val) in embedding.iter().enumerate() {
+            avg[i] += val * weight / total;
+        }
+    }
+
+    Some(avg)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -136,4 +155,14 @@ mod tests {
         assert!((n[0] - 0.6).abs() < 1e-6);
         assert!((n[1] - 0.8).abs() < 1e-6);
     }
+
+    #[test]
+    fn test_weighted_average_embeddings() {
+        let embeddings = vec![vec![1.0, 0.0], vec![0.0, 1.0]];
+        let avg = weighted_average_embeddings(&embeddings, &[3.0, 1.0]).unwrap();
+        assert!((avg[0] - 0.75).abs() < 1e-6);
+        assert!((avg[1] - 0.25).abs() < 1e-6);
+        assert!(weighted_average_embeddings(&embeddings, &[1.0]).is_none());
+        assert!(weighted_average_embeddings(&[], &[]).is_none());
+    }
 }
@@ -0,0 +1,125 @@
+//! Structured JSON replies. The backend is handed the reply's JSON schema, and
+//! a reply that still fails to parse is sent back with the error for another try.
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::error::AppError;
+use crate::service::llm::{ChatMessage, GenerateOptions, LlmBackend};
+
+/// A reply the model is asked to give as JSON
+pub trait StructuredOutput: DeserializeOwned {
+    /// JSON schema sent to the backend as the reply format
+    fn schema() -> Value;
+
+    /// Checks the schema can't express; the error is shown to the model on retry
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Chat until the reply parses as `T`. After a bad reply the conversation is
+/// continued with the parse error, up to `retries` times, before giving up
+/// with `AppError::ModelParse`. Backend errors are returned as they are.
+pub async fn chat_structured<T: StructuredOutput>(
+    backend: &dyn LlmBackend,
+    mut messages: Vec<ChatMessage>,
+    options: &GenerateOptions,
+    retries: u32,
+) -> Result<T, AppError> {
+    let schema = T::schema();
+    let options = GenerateOptions {
+        format: Some(schema.clone()),
+        ..options.clone()
+    };
+
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let content = backend.chat(&messages, &options).await?;
+
+        let error = match parse_structured::<T>(&content) {
+            Ok(value) => return Ok(value),
+            Err(error) => error,
+        };
+        if attempts > retries {
+            return Err(AppError::ModelParse(format!(
+                "Reply did not match the expected format after {} attempts: {}",
+                attempts, error
+            )));
+        }
+
+        tracing::warn!(
+            "Rejected structured reply from {} (attempt {}): {}",
+            backend.name(),
+            attempts,
+            error
+        );
+        messages.push(ChatMessage::assistant(content));
+        messages.push(ChatMessage::user(format!(
+            "That reply could not be used: {}. \
+            Answer again with only a JSON object matching this schema: {}",
+            error, schema
+        )));
+    }
+}
+
+/// Parse and validate a reply, tolerating a markdown code fence around the JSON
+pub fn parse_structured<T: StructuredOutput>(content: &str) -> Result<T, String> {
+    let value: T = serde_json::from_str(strip_code_fence(content)).map_err(|e| e.to_string())?;
+    value.validate()?;
+    Ok(value)
+}
+
+fn strip_code_fence(content: &str) -> &str {
+    let content = content.trim();
+    content
+        .strip_prefix("```")
+        .and_then(|rest| rest.strip_suffix("```"))
+        .map(|inner| inner.strip_prefix("json").unwrap_or(inner).trim())
+        .unwrap_or(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    struct Word {
+        word: String,
+    }
+
+    impl StructuredOutput for Word {
+        fn schema() -> Value {
+            serde_json::json!({"type": "object"})
+        }
+
+        fn validate(&self) -> Result<(), String> {
+            if self.word.is_empty() {
+                return Err("word must not be empty".into());
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_parse_structured_strips_code_fence() {
+        let word: Word = parse_structured("```json\n{\"word\": \"cake\"}\n```\n").unwrap();
+        assert_eq!(word.word, "cake");
+        let word: Word = parse_structured("{\"word\": \"cake\"}").unwrap();
+        assert_eq!(word.word, "cake");
+    }
+
+    #[test]
+    fn test_parse_structured_reports_errors() {
+        assert!(parse_structured::<Word>("cake, happy, cute").is_err());
+        assert!(parse_structured::<Word>("{\"other\": 1}")
+            .unwrap_err()
+            .contains("word"));
+        assert_eq!(
+            parse_structured::<Word>("{\"word\": \"\"}").unwrap_err(),
+            "word must not be empty"
+        );
+    }
+}
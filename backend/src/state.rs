@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::service::{
+    build_backend, GenerationService, HistoryStore, JobStore, LibraryGenerator, PromptRegistry,
+    RoastGuard, SessionStore,
+};
+
+/// Shared state handed to every handler through axum `State`
+#[derive(Clone)]
+pub struct AppState {
+    pub config: Arc<Config>,
+    pub generator: GenerationService,
+    /// Background admin jobs, e.g. library generation
+    pub jobs: JobStore,
+    /// Conversations for `/chat/session`
+    pub sessions: SessionStore,
+    /// Saved generations for `/history`
+    pub history: HistoryStore,
+}
+
+impl AppState {
+    /// Fails if a prompt template in the prompts directory can't be used, the
+    /// roast blocklist file can't be read or the history database can't be opened
+    pub fn new(config: Config) -> Result<Self, String> {
+        let prompts = PromptRegistry::load(&config.prompts.dir)
+            .map_err(|e| format!("Failed to load prompts: {}", e))?;
+        let roast_guard = RoastGuard::load(&config.roast)
+            .map_err(|e| format!("Failed to load roast blocklist: {}", e))?;
+        let history = HistoryStore::open(&config.history)
+            .map_err(|e| format!("Failed to open history: {}", e))?;
+        let generator = GenerationService::new(
+            build_backend(&config),
+            &config.llm,
+            Arc::new(prompts),
+            Arc::new(roast_guard),
+        );
+        Ok(Self {
+            sessions: SessionStore::new(&config.sessions),
+            history,
+            config: Arc::new(config),
+            generator,
+            jobs: JobStore::new(),
+        })
+    }
+
+    /// Worker for library generation jobs, bound to this state's model and paths
+    pub fn library_generator(&self) -> LibraryGenerator {
+        LibraryGenerator {
+            jobs: self.jobs.clone(),
+            generator: self.generator.clone(),
+            images_dir: self.config.library.images_dir.clone(),
+            csv_path: self.config.library.csv_path.clone(),
+        }
+    }
+}
@@ -0,0 +1,2285 @@
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use serde_json::json;
+use std::time::Duration;
+
+use super::mock_ollama::Reply;
+use super::{sse_events, TestApp, ADMIN_TOKEN};
+use crate::lib::image_library::{image_hash, read_library_rows};
+
+/// "hello" in base64, a stand-in for real image bytes
+const IMAGE: &str = "aGVsbG8=";
+
+/// `/api/chat` reply in the extraction prompt's JSON format: object, emotion
+/// and style, then any extra words
+fn tags_reply(words: &[&str]) -> Reply {
+    Reply::chat(
+        &json!({
+            "object": words[0],
+            "emotion": words[1],
+            "style": words[2],
+            "extra": words[3..],
+        })
+        .to_string(),
+    )
+}
+
+// ============================================================================
+// Health
+// ============================================================================
+
+#[tokio::test]
+async fn test_health() {
+    let app = TestApp::start().await;
+
+    let (status, body) = app.get("/health").await;
+    assert_eq!(status, StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["status"], "ok");
+
+    let (status, body) = app.get("/").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, b"Hack and Roll Snap API");
+}
+
+// ============================================================================
+// Poems
+// ============================================================================
+
+#[tokio::test]
+async fn test_poem_from_text() {
+    let app = TestApp::start().await;
+    app.ollama
+        .on("/api/generate", Reply::generate("Roses are red"));
+
+    let (status, body) = app.post("/poem/text", json!({ "prompt": "spring" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["success"], true);
+    assert_eq!(body["poem"], "Roses are red");
+    assert!(body.get("attempts").is_none());
+
+    let sent = app.ollama.requests("/api/generate");
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0]["model"], "test-model");
+    assert_eq!(sent[0]["stream"], false);
+    assert!(sent[0]["prompt"]
+        .as_str()
+        .unwrap()
+        .contains("Theme: spring"));
+}
+
+#[tokio::test]
+async fn test_poem_from_text_regenerates_until_form_fits() {
+    let app = TestApp::start().await;
+    app.ollama.on(
+        "/api/generate",
+        Reply::sequence(vec![
+            Reply::generate("A hamster\nsleeps all day\nin his nest\nof hay"),
+            Reply::generate(
+                "Tiny paws scurry\nthrough the sawdust in the night\nthe wheel never stops",
+            ),
+        ]),
+    );
+
+    let (status, body) = app
+        .post(
+            "/poem/text",
+            json!({ "prompt": "hamster", "style": "haiku", "tone": "playful" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["attempts"], 2);
+    assert!(body["poem"].as_str().unwrap().starts_with("Tiny paws"));
+    assert_eq!(body["constraints"][0]["constraint"], "line_count");
+    assert_eq!(body["constraints"][0]["satisfied"], true);
+    assert_eq!(body["constraints"][1]["constraint"], "syllables");
+    assert_eq!(body["constraints"][1]["satisfied"], true);
+
+    let sent = app.ollama.requests("/api/generate");
+    assert_eq!(sent.len(), 2);
+    let first = sent[0]["prompt"].as_str().unwrap();
+    assert!(first.contains("Write it as a haiku"), "{}", first);
+    assert!(first.contains("Make the tone playful."), "{}", first);
+    let second = sent[1]["prompt"].as_str().unwrap();
+    assert!(
+        second.contains("did not fit (expected 3 lines, got 4"),
+        "{}",
+        second
+    );
+}
+
+#[tokio::test]
+async fn test_poem_from_text_returns_best_attempt() {
+    let app = TestApp::start_with(|config| config.llm.poem_retries = 1).await;
+    app.ollama.on(
+        "/api/generate",
+        Reply::sequence(vec![
+            Reply::generate("one\ntwo\nthree"),
+            Reply::generate("one\ntwo\nthree\nfour"),
+        ]),
+    );
+
+    let (status, body) = app
+        .post("/poem/text", json!({ "prompt": "spring", "max_lines": 2 }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["poem"], "one\ntwo\nthree");
+    assert_eq!(body["attempts"], 2);
+    assert_eq!(body["constraints"][0]["satisfied"], false);
+    assert_eq!(
+        body["constraints"][0]["detail"],
+        "expected at most 2 lines, got 3"
+    );
+}
+
+#[tokio::test]
+async fn test_poem_from_text_rejects_conflicting_form() {
+    let app = TestApp::start().await;
+
+    let (status, body) = app
+        .post(
+            "/poem/text",
+            json!({ "prompt": "spring", "style": "sonnet", "max_lines": 10 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        body["error"]["message"],
+        "max_lines must be at least 14 for this style"
+    );
+
+    let (status, _) = app
+        .post("/poem/text", json!({ "prompt": "spring", "style": "ode" }))
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(app.ollama.requests("/api/generate").is_empty());
+}
+
+#[tokio::test]
+async fn test_poem_from_text_rejects_empty_prompt() {
+    let app = TestApp::start().await;
+
+    let (status, body) = app.post("/poem/text", json!({ "prompt": "  " })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "validation_failed");
+    assert_eq!(body["success"], false);
+    assert_eq!(body["error"]["message"], "Prompt cannot be empty");
+    assert!(app.ollama.requests("/api/generate").is_empty());
+}
+
+#[tokio::test]
+async fn test_poem_from_text_rejects_malformed_body() {
+    let app = TestApp::start().await;
+
+    let (status, body) = app.post("/poem/text", json!({})).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["success"], false);
+    assert_eq!(body["error"]["code"], "invalid_body");
+
+    let (status, body) = app.post_raw("/poem/text", "not json".into()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["error"]["code"], "validation_failed");
+}
+
+#[tokio::test]
+async fn test_poem_from_text_upstream_error() {
+    let app = TestApp::start().await;
+    app.ollama.on(
+        "/api/generate",
+        Reply::error(StatusCode::NOT_FOUND, "model 'test-model' not found"),
+    );
+
+    let (status, body) = app.post("/poem/text", json!({ "prompt": "spring" })).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(body["success"], false);
+    assert_eq!(body["error"]["code"], "upstream_error");
+    assert!(body["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("not found"));
+}
+
+#[tokio::test]
+async fn test_poem_from_text_upstream_timeout() {
+    let app = TestApp::start_with(|config| config.llm.timeout_secs = 1).await;
+    app.ollama.on(
+        "/api/generate",
+        Reply::generate("too late").delayed(Duration::from_secs(3)),
+    );
+
+    let (status, body) = app.post("/poem/text", json!({ "prompt": "spring" })).await;
+    assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(body["error"]["code"], "upstream_timeout");
+}
+
+#[tokio::test]
+async fn test_poem_from_text_unreachable_ollama() {
+    let app =
+        TestApp::start_with(|config| config.ollama.base_url = "http://127.0.0.1:1".into()).await;
+
+    let (status, body) = app.post("/poem/text", json!({ "prompt": "spring" })).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(body["error"]["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("Failed to connect to Ollama"));
+}
+
+#[tokio::test]
+async fn test_poem_from_image() {
+    let app = TestApp::start().await;
+    app.ollama.on("/api/chat", Reply::chat("A hamster dreams"));
+
+    let (status, body) = app
+        .post(
+            "/poem/image",
+            json!({ "image_base64": IMAGE, "prompt": "Be brief" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["poem"], "A hamster dreams");
+
+    let sent = app.ollama.requests("/api/chat");
+    assert_eq!(sent[0]["messages"][0]["content"], "Be brief");
+    assert_eq!(sent[0]["messages"][0]["images"][0], IMAGE);
+}
+
+#[tokio::test]
+async fn test_poem_from_image_rejects_bad_image() {
+    let app = TestApp::start().await;
+
+    let (status, body) = app.post("/poem/image", json!({ "image_base64": "" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["message"], "Image data cannot be empty");
+
+    let (status, body) = app
+        .post("/poem/image", json!({ "image_base64": "not base64!" }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["message"], "Invalid base64 image data");
+}
+
+#[tokio::test]
+async fn test_poem_from_image_upstream_error() {
+    let app = TestApp::start().await;
+    app.ollama.on(
+        "/api/chat",
+        Reply::error(StatusCode::INTERNAL_SERVER_ERROR, "out of memory"),
+    );
+
+    let (status, body) = app
+        .post("/poem/image", json!({ "image_base64": IMAGE }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert!(body["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("out of memory"));
+}
+
+// ============================================================================
+// Roasts
+// ============================================================================
+
+#[tokio::test]
+async fn test_roast_from_image() {
+    let app = TestApp::start().await;
+    app.ollama.on("/api/chat", Reply::chat("Nice hat."));
+
+    let (status, body) = app
+        .post("/roast/image", json!({ "image_base64": IMAGE }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["roast"], "Nice hat.");
+    assert_eq!(body["intensity"], "wholesome");
+    assert_eq!(body["attempts"], 1);
+    assert!(body["prompt_version"]
+        .as_str()
+        .unwrap()
+        .starts_with("roast@"));
+
+    let sent = app.ollama.requests("/api/chat");
+    assert_eq!(
+        sent[0]["options"]["temperature"].as_f64().unwrap() as f32,
+        0.9
+    );
+    let prompt = sent[0]["messages"][0]["content"].as_str().unwrap();
+    assert!(prompt.contains("gentle teasing"));
+    assert!(prompt.contains("family friendly"));
+}
+
+#[tokio::test]
+async fn test_roast_intensity_and_family_friendly() {
+    let app = TestApp::start().await;
+    app.ollama.on("/api/chat", Reply::chat("Nice hat."));
+
+    let (status, body) = app
+        .post(
+            "/roast/image",
+            json!({ "image_base64": IMAGE, "intensity": "savage", "family_friendly": false }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["intensity"], "savage");
+    let sent = app.ollama.requests("/api/chat");
+    let prompt = sent[0]["messages"][0]["content"].as_str().unwrap();
+    assert!(prompt.contains("merciless burn"));
+    assert!(!prompt.contains("family friendly"));
+
+    let (status, _) = app
+        .post(
+            "/roast/image",
+            json!({ "image_base64": IMAGE, "intensity": "brutal" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn test_roast_blocklist_regenerates() {
+    let blocklist = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(
+        blocklist.path(),
+        "# booth extras
+socks
+",
+    )
+    .unwrap();
+    let path = blocklist.path().to_path_buf();
+    let app = TestApp::start_with(|config| config.roast.blocklist_path = Some(path)).await;
+    app.ollama.on(
+        "/api/chat",
+        Reply::sequence(vec![
+            Reply::chat("What fat cheeks."),
+            Reply::chat("Those socks are a crime."),
+            Reply::chat("Nice hat."),
+        ]),
+    );
+
+    let (status, body) = app
+        .post("/roast/image", json!({ "image_base64": IMAGE }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["roast"], "Nice hat.");
+    assert_eq!(body["attempts"], 3);
+
+    let sent = app.ollama.requests("/api/chat");
+    assert_eq!(sent.len(), 3);
+    assert!(sent[1]["messages"][0]["content"]
+        .as_str()
+        .unwrap()
+        .contains("An earlier roast was rejected"));
+}
+
+#[tokio::test]
+async fn test_roast_rejected_every_attempt() {
+    let app = TestApp::start_with(|config| config.roast.retries = 0).await;
+    app.ollama.on("/api/chat", Reply::chat("So ugly."));
+
+    let (status, body) = app
+        .post("/roast/image", json!({ "image_base64": IMAGE }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(body["error"]["code"], "unsafe_output");
+    assert_eq!(app.ollama.requests("/api/chat").len(), 1);
+}
+
+#[tokio::test]
+async fn test_roast_classifier() {
+    let app = TestApp::start_with(|config| config.roast.classifier = true).await;
+    app.ollama.on(
+        "/api/chat",
+        Reply::sequence(vec![
+            Reply::chat("Nice accent."),
+            Reply::chat(r#"{"safe": false, "reason": "nationality"}"#),
+            Reply::chat("Nice hat."),
+            Reply::chat(r#"{"safe": true}"#),
+        ]),
+    );
+
+    let (status, body) = app
+        .post("/roast/image", json!({ "image_base64": IMAGE }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["roast"], "Nice hat.");
+    assert_eq!(body["attempts"], 2);
+
+    let sent = app.ollama.requests("/api/chat");
+    assert_eq!(sent.len(), 4);
+    assert!(sent[1]["messages"][0]["content"]
+        .as_str()
+        .unwrap()
+        .contains("Joke: Nice accent."));
+    assert_eq!(sent[1]["format"]["required"], json!(["safe"]));
+    assert!(sent[2].get("format").is_none());
+}
+
+#[tokio::test]
+async fn test_roast_from_image_validation_and_upstream_error() {
+    let app = TestApp::start().await;
+
+    let (status, _) = app
+        .post("/roast/image", json!({ "image_base64": " " }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    app.ollama.on(
+        "/api/chat",
+        Reply::error(StatusCode::SERVICE_UNAVAILABLE, "busy"),
+    );
+    let (status, body) = app
+        .post("/roast/image", json!({ "image_base64": IMAGE }))
+        .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"]["code"], "upstream_unavailable");
+}
+
+// ============================================================================
+// Streaming
+// ============================================================================
+
+#[tokio::test]
+async fn test_stream_poem_from_text() {
+    let app = TestApp::start().await;
+    app.ollama.on(
+        "/api/generate",
+        Reply::generate_stream(&["Roses", " are", " red"]),
+    );
+
+    let (status, body) = app
+        .post_raw(
+            "/poem/text/stream",
+            json!({ "prompt": "spring" }).to_string(),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let events = sse_events(&body);
+    let tokens: Vec<_> = events
+        .iter()
+        .filter(|(name, _)| name == "token")
+        .map(|(_, data)| data["token"].as_str().unwrap())
+        .collect();
+    assert_eq!(tokens, ["Roses", " are", " red"]);
+
+    let (name, done) = events.last().unwrap();
+    assert_eq!(name, "done");
+    assert_eq!(done["text"], "Roses are red");
+    assert!(done["prompt_version"]
+        .as_str()
+        .unwrap()
+        .starts_with("poem_text@"));
+
+    assert_eq!(app.ollama.requests("/api/generate")[0]["stream"], true);
+}
+
+#[tokio::test]
+async fn test_stream_poem_from_image() {
+    let app = TestApp::start().await;
+    app.ollama
+        .on("/api/chat", Reply::chat_stream(&["Tiny", " paws"]));
+
+    let (status, body) = app
+        .post_raw(
+            "/poem/image/stream",
+            json!({ "image_base64": IMAGE }).to_string(),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (name, done) = sse_events(&body).pop().unwrap();
+    assert_eq!(name, "done");
+    assert_eq!(done["text"], "Tiny paws");
+    // Saved before `done` went out, so it can already be shared
+    let share_id = done["share_id"].as_str().unwrap();
+    let (status, _) = app.get(&format!("/r/{}", share_id)).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_stream_roast_is_checked_before_sending() {
+    let app = TestApp::start().await;
+    app.ollama.on("/api/chat", Reply::chat("What fat cheeks."));
+
+    let (status, body) = app
+        .post_raw(
+            "/roast/image/stream",
+            json!({ "image_base64": IMAGE }).to_string(),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let events = sse_events(&body);
+    assert_eq!(events.len(), 1);
+    let (name, error) = &events[0];
+    assert_eq!(name, "error");
+    assert_eq!(error["error"]["code"], "unsafe_output");
+    // Regenerated like /roast/image before giving up
+    assert_eq!(app.ollama.requests("/api/chat").len(), 3);
+
+    let (_, history) = app.get_json("/history").await;
+    assert_eq!(history["total"], 0);
+}
+
+#[tokio::test]
+async fn test_stream_roast_regenerates_rejected_roast() {
+    let app = TestApp::start().await;
+    app.ollama.on(
+        "/api/chat",
+        Reply::sequence(vec![
+            Reply::chat("What fat cheeks."),
+            Reply::chat("Nice hat."),
+        ]),
+    );
+
+    let (_, body) = app
+        .post_raw(
+            "/roast/image/stream",
+            json!({ "image_base64": IMAGE }).to_string(),
+        )
+        .await;
+
+    let events = sse_events(&body);
+    let tokens: Vec<_> = events
+        .iter()
+        .filter(|(name, _)| name == "token")
+        .map(|(_, data)| data["token"].as_str().unwrap())
+        .collect();
+    assert_eq!(tokens, ["Nice ", "hat."]);
+    let (name, done) = events.last().unwrap();
+    assert_eq!(name, "done");
+    assert_eq!(done["text"], "Nice hat.");
+    assert!(done["prompt_version"]
+        .as_str()
+        .unwrap()
+        .starts_with("roast@"));
+}
+
+#[tokio::test]
+async fn test_stream_error_midway() {
+    let app = TestApp::start().await;
+    app.ollama.on(
+        "/api/chat",
+        Reply::Lines(vec![
+            json!({ "message": { "role": "assistant", "content": "Tiny" }, "done": false }),
+            json!({ "error": "model crashed" }),
+        ]),
+    );
+
+    let (status, body) = app
+        .post_raw(
+            "/poem/image/stream",
+            json!({ "image_base64": IMAGE }).to_string(),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let events = sse_events(&body);
+    assert_eq!(events[0].0, "token");
+    let (name, error) = events.last().unwrap();
+    assert_eq!(name, "error");
+    assert_eq!(error["error"]["code"], "upstream_error");
+    assert!(error["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("model crashed"));
+    assert!(!events.iter().any(|(name, _)| name == "done"));
+}
+
+#[tokio::test]
+async fn test_stream_validation_and_upstream_error() {
+    let app = TestApp::start().await;
+
+    let (status, _) = app
+        .post_raw("/poem/text/stream", json!({ "prompt": "" }).to_string())
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = app
+        .post_raw(
+            "/roast/image/stream",
+            json!({ "image_base64": "%%%" }).to_string(),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Nothing scripted: the mock answers 404 before any token is sent
+    let (status, body) = app
+        .post("/poem/image/stream", json!({ "image_base64": IMAGE }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(body["success"], false);
+}
+
+#[tokio::test]
+async fn test_prompts_list_and_reload() {
+    let app = TestApp::start().await;
+    app.ollama.on("/api/chat", Reply::chat("Nice hat."));
+
+    let (status, body) = app.get_json("/admin/prompts").await;
+    assert_eq!(status, StatusCode::OK);
+    let names: Vec<&str> = body["prompts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "poem_text",
+            "poem_image",
+            "roast",
+            "roast_check",
+            "extract_tags"
+        ]
+    );
+    assert_eq!(body["prompts"][2]["source"], "builtin");
+    assert_eq!(
+        body["prompts"][4]["variables"],
+        json!(["guidance", "word_list", "max_extra"])
+    );
+    let builtin_version = body["prompts"][2]["version"].clone();
+
+    let dir = app.dir.path().join("prompts");
+    std::fs::create_dir(&dir).unwrap();
+    std::fs::write(dir.join("roast.txt"), "Roast it like a pirate.\n").unwrap();
+    let (status, body) = app.post("/admin/prompts/reload", json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["prompts"][2]["source"], "file");
+    assert_ne!(body["prompts"][2]["version"], builtin_version);
+    let file_version = body["prompts"][2]["version"].clone();
+
+    let (_, body) = app
+        .post("/roast/image", json!({ "image_base64": IMAGE }))
+        .await;
+    assert_eq!(body["prompt_version"], file_version);
+    let sent = app.ollama.requests("/api/chat");
+    assert_eq!(sent[0]["messages"][0]["content"], "Roast it like a pirate.");
+
+    // A broken template is rejected and the previous one stays in use
+    std::fs::write(dir.join("roast.txt"), "Roast {name}.").unwrap();
+    let (status, body) = app.post("/admin/prompts/reload", json!({})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("unknown variable {name}"));
+    let (_, body) = app.get_json("/admin/prompts").await;
+    assert_eq!(body["prompts"][2]["version"], file_version);
+}
+
+// ============================================================================
+// Chat sessions
+// ============================================================================
+
+#[tokio::test]
+async fn test_roast_session_follow_up() {
+    let app = TestApp::start().await;
+    app.ollama.on(
+        "/api/chat",
+        Reply::sequence(vec![
+            Reply::chat("Nice hat."),
+            Reply::chat("That hat has its own postcode."),
+        ]),
+    );
+
+    let (status, body) = app
+        .post(
+            "/chat/session",
+            json!({ "kind": "roast", "image_base64": IMAGE, "intensity": "spicy" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["reply"], "Nice hat.");
+    assert!(body["prompt_version"]
+        .as_str()
+        .unwrap()
+        .starts_with("roast@"));
+    assert_eq!(body["session"]["kind"], "roast");
+    assert_eq!(body["session"]["intensity"], "spicy");
+    assert_eq!(body["session"]["messages"][0]["image"], "image-1");
+    let id = body["session"]["id"].as_str().unwrap().to_string();
+
+    let (status, body) = app
+        .post(
+            &format!("/chat/session/{}/messages", id),
+            json!({ "message": "make it funnier" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["reply"], "That hat has its own postcode.");
+    assert_eq!(body["session"]["messages"].as_array().unwrap().len(), 4);
+
+    // The whole conversation is sent, with the photo on the message it came with
+    let sent = app.ollama.requests("/api/chat");
+    let messages = &sent[1]["messages"];
+    assert_eq!(messages.as_array().unwrap().len(), 3);
+    assert_eq!(messages[0]["images"], json!([IMAGE]));
+    assert_eq!(messages[1]["role"], "assistant");
+    assert_eq!(messages[1]["content"], "Nice hat.");
+    assert_eq!(messages[2]["content"], "make it funnier");
+
+    let (status, body) = app.get_json(&format!("/chat/session/{}", id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["session"]["messages"][0].get("images").is_none());
+
+    let (status, _) = app.delete(&format!("/chat/session/{}", id), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = app.get_json(&format!("/chat/session/{}", id)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"]["code"], "not_found");
+}
+
+#[tokio::test]
+async fn test_roast_session_replies_are_checked() {
+    let app = TestApp::start().await;
+    app.ollama.on(
+        "/api/chat",
+        Reply::sequence(vec![
+            Reply::chat("Nice hat."),
+            Reply::chat("And what a bald head."),
+            Reply::chat("And what a hat brim."),
+        ]),
+    );
+
+    let (_, body) = app
+        .post(
+            "/chat/session",
+            json!({ "kind": "roast", "image_base64": IMAGE }),
+        )
+        .await;
+    let id = body["session"]["id"].as_str().unwrap().to_string();
+
+    let (status, body) = app
+        .post(
+            &format!("/chat/session/{}/messages", id),
+            json!({ "message": "meaner" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["reply"], "And what a hat brim.");
+    // The rejected attempt is not part of the history
+    let contents: Vec<&str> = body["session"]["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["content"].as_str().unwrap())
+        .collect();
+    assert_eq!(contents[2..], ["meaner", "And what a hat brim."]);
+}
+
+#[tokio::test]
+async fn test_chat_session_history_is_bounded() {
+    let app = TestApp::start_with(|config| config.sessions.max_history_chars = 40).await;
+    app.ollama.on("/api/chat", Reply::chat("A short reply."));
+
+    let (status, body) = app
+        .post("/chat/session", json!({ "message": "Write a poem" }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.get("prompt_version").is_none());
+    let id = body["session"]["id"].as_str().unwrap().to_string();
+
+    for message in ["shorter", "funnier", "in French"] {
+        let (status, _) = app
+            .post(
+                &format!("/chat/session/{}/messages", id),
+                json!({ "message": message }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    // The opening exchange and the newest message are always kept
+    let sent = app.ollama.requests("/api/chat");
+    let last: Vec<&str> = sent[3]["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["content"].as_str().unwrap())
+        .collect();
+    assert_eq!(last, ["Write a poem", "A short reply.", "in French"]);
+
+    let (_, body) = app.get_json(&format!("/chat/session/{}", id)).await;
+    assert_eq!(body["session"]["dropped_messages"], 4);
+}
+
+#[tokio::test]
+async fn test_chat_session_validation() {
+    let app = TestApp::start().await;
+
+    let (status, _) = app.post("/chat/session", json!({ "kind": "roast" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app.post("/chat/session", json!({ "kind": "poem" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app.post("/chat/session", json!({ "kind": "song" })).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    // A chat session may start empty
+    let (status, body) = app.post("/chat/session", json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.get("reply").is_none());
+    let id = body["session"]["id"].as_str().unwrap().to_string();
+
+    let (status, _) = app
+        .post(
+            &format!("/chat/session/{}/messages", id),
+            json!({ "message": "  " }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app
+        .post(
+            "/chat/session/unknown/messages",
+            json!({ "message": "hello" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // A failed opening doesn't leave an empty session behind
+    app.ollama.on(
+        "/api/chat",
+        Reply::error(StatusCode::SERVICE_UNAVAILABLE, "busy"),
+    );
+    let (status, _) = app
+        .post("/chat/session", json!({ "message": "hello" }))
+        .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+}
+
+// ============================================================================
+// Embeddings
+// ============================================================================
+
+#[tokio::test]
+async fn test_embed() {
+    let app = TestApp::start().await;
+    app.ollama
+        .on("/api/embed", Reply::embeddings(vec![vec![0.5, 0.5]]));
+
+    let (status, body) = app.post("/embed", json!({ "text": "cake" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["embedding"], json!([0.5, 0.5]));
+
+    let sent = app.ollama.requests("/api/embed");
+    assert_eq!(sent[0]["model"], "nomic-embed-text");
+    assert_eq!(sent[0]["input"], json!(["cake"]));
+}
+
+#[tokio::test]
+async fn test_embed_validation_and_upstream_error() {
+    let app = TestApp::start().await;
+
+    let (status, _) = app.post("/embed", json!({ "text": "" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = app.post("/embed/batch", json!({ "texts": [] })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    app.ollama.on(
+        "/api/embed",
+        Reply::error(StatusCode::BAD_REQUEST, "model does not support embeddings"),
+    );
+    let (status, body) = app.post("/embed", json!({ "text": "cake" })).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert!(body["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("does not support embeddings"));
+
+    // The mock returns no vectors at all
+    app.ollama.on("/api/embed", Reply::embeddings(vec![]));
+    let (status, body) = app.post("/embed", json!({ "text": "cake" })).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(body["error"]["message"], "No embedding returned");
+}
+
+#[tokio::test]
+async fn test_embed_batch() {
+    let app = TestApp::start().await;
+    app.ollama.on(
+        "/api/embed",
+        Reply::embeddings(vec![vec![1.0, 0.0], vec![0.0, 1.0]]),
+    );
+
+    let (status, body) = app
+        .post("/embed/batch", json!({ "texts": ["cake", "sad"] }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["embeddings"].as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_embed_search() {
+    let app = TestApp::start().await;
+    // The query takes the first vector; the corpus gets both
+    app.ollama.on(
+        "/api/embed",
+        Reply::embeddings(vec![vec![1.0, 0.0], vec![0.0, 1.0]]),
+    );
+
+    let (status, body) = app
+        .post(
+            "/embed/search",
+            json!({ "query": "cake", "corpus": ["birthday", "rain"], "top_k": 1 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let results = body["results"].as_array().unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0]["text"], "birthday");
+    assert_eq!(results[0]["score"], 1.0);
+}
+
+#[tokio::test]
+async fn test_embed_search_validation_and_upstream_error() {
+    let app = TestApp::start().await;
+
+    let (status, body) = app
+        .post("/embed/search", json!({ "query": "", "corpus": ["a"] }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["message"], "Query cannot be empty");
+
+    let (status, body) = app
+        .post("/embed/search", json!({ "query": "cake", "corpus": [] }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["message"], "Corpus cannot be empty");
+
+    let (status, body) = app
+        .post("/embed/search", json!({ "query": "cake", "corpus": ["a"] }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert!(body["error"]["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("Failed to embed query"));
+}
+
+// ============================================================================
+// Image matching and library
+// ============================================================================
+
+#[tokio::test]
+async fn test_image_match_validation() {
+    let app = TestApp::start().await;
+
+    let (status, body) = app.post("/image/match", json!({ "words": [] })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["message"], "Words cannot be empty");
+
+    let (status, _) = app
+        .post("/image/match", json!({ "words": ["cake"], "top_k": 0 }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = app
+        .post(
+            "/image/match",
+            json!({ "words": ["cake"], "min_score": 1.5 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = app
+        .post(
+            "/image/match",
+            json!({ "words": ["cake"], "mode": "fuzzy" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let (status, body) = app
+        .post(
+            "/image/match",
+            json!({ "words": ["cake"], "slot_weights": { "emotion": -1 } }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("not negative"));
+
+    // A one-word query only fills the object slot
+    let (status, body) = app
+        .post(
+            "/image/match",
+            json!({ "words": ["cake"], "mode": "slots", "slot_weights": { "object": 0 } }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("must not all be zero"));
+}
+
+#[tokio::test]
+async fn test_image_match_without_embedding_cache() {
+    // Tests never load the local embedding model, so the cache stays empty
+    let app = TestApp::start().await;
+
+    let (status, body) = app.post("/image/match", json!({ "words": ["cake"] })).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"]["code"], "library_empty");
+    assert!(body["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("not initialized"));
+
+    let (status, body) = app.post("/admin/library/reload", json!({})).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["success"], false);
+}
+
+#[tokio::test]
+async fn test_describe_image() {
+    let app = TestApp::start().await;
+    app.ollama.on(
+        "/api/chat",
+        tags_reply(&["Cake", "happy", "whimsical", "party"]),
+    );
+
+    let (status, body) = app
+        .post("/image/describe", json!({ "image_base64": IMAGE }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["words"],
+        json!(["cake", "happy", "whimsical", "party"])
+    );
+    assert_eq!(body["tags"][0]["category"], "object");
+    assert_eq!(body["tags"][3]["weight"], 0.5);
+    assert!(body["tags"][0].get("snap_score").is_none());
+
+    let sent = app.ollama.requests("/api/chat");
+    let prompt = sent[0]["messages"][0]["content"].as_str().unwrap();
+    assert!(prompt.contains("use these if they fit"), "{}", prompt);
+
+    let (status, _) = app
+        .post("/image/describe", json!({ "image_base64": "" }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_describe_image_retries_bad_json() {
+    let app = TestApp::start().await;
+    app.ollama.on(
+        "/api/chat",
+        Reply::sequence(vec![
+            Reply::chat("cake, happy, cute"),
+            Reply::chat(r#"{"object": "cake", "emotion": "", "style": "cute"}"#),
+            tags_reply(&["cake", "happy", "cute"]),
+        ]),
+    );
+
+    let (status, body) = app
+        .post("/image/describe", json!({ "image_base64": IMAGE }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["words"], json!(["cake", "happy", "cute"]));
+
+    let sent = app.ollama.requests("/api/chat");
+    assert_eq!(sent.len(), 3);
+    let messages = sent[2]["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 5);
+    assert_eq!(messages[3]["role"], "assistant");
+    let correction = messages[4]["content"].as_str().unwrap();
+    assert!(
+        correction.contains("\"emotion\" must not be empty"),
+        "{}",
+        correction
+    );
+}
+
+#[tokio::test]
+async fn test_describe_image_gives_up_after_configured_retries() {
+    let app = TestApp::start_with(|config| config.llm.structured_retries = 0).await;
+    app.ollama.on("/api/chat", Reply::chat("not json"));
+
+    let (status, body) = app
+        .post("/image/describe", json!({ "image_base64": IMAGE }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(body["error"]["code"], "model_parse_failed");
+    assert_eq!(app.ollama.requests("/api/chat").len(), 1);
+}
+
+#[tokio::test]
+async fn test_describe_image_with_vocabulary() {
+    let app = TestApp::start().await;
+    app.ollama
+        .on("/api/chat", tags_reply(&["hat", "happy", "cute"]));
+
+    // Words already in the vocabulary need no embedding model
+    let (status, body) = app
+        .post(
+            "/image/describe",
+            json!({ "image_base64": IMAGE, "vocabulary_only": true }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["words"], json!(["hat", "happy", "cute"]));
+    assert_eq!(body["tags"][1]["snap_score"], 1.0);
+    assert!(body["tags"][1].get("extracted").is_none());
+
+    let sent = app.ollama.requests("/api/chat");
+    let prompt = sent[0]["messages"][0]["content"].as_str().unwrap();
+    assert!(
+        prompt.contains("Choose only from these words"),
+        "{}",
+        prompt
+    );
+
+    // Anything else has to be snapped, which needs the model tests never load
+    app.ollama
+        .on("/api/chat", tags_reply(&["cake", "happy", "cute"]));
+    let (status, body) = app
+        .post(
+            "/image/describe",
+            json!({ "image_base64": IMAGE, "vocabulary_only": true }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"]["code"], "library_empty");
+}
+
+#[tokio::test]
+async fn test_hamster_match_without_embedding_cache() {
+    let app = TestApp::start().await;
+    app.ollama
+        .on("/api/chat", tags_reply(&["hat", "happy", "cute"]));
+
+    let (status, _) = app
+        .post(
+            "/hamster/match",
+            json!({ "image_base64": IMAGE, "slot_weights": { "object": -1 } }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    // Rejected before the photo was described
+    assert!(app.ollama.requests("/api/chat").is_empty());
+
+    let (status, body) = app
+        .post("/hamster/match", json!({ "image_base64": IMAGE }))
+        .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"]["code"], "library_empty");
+    assert_eq!(app.ollama.requests("/api/chat").len(), 1);
+}
+
+#[tokio::test]
+async fn test_photo_match_validation() {
+    let app = TestApp::start().await;
+
+    let (status, body) = app
+        .post("/image/match/photo", json!({ "image_base64": "" }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["message"], "Image data cannot be empty");
+
+    for payload in [
+        json!({ "image_base64": IMAGE, "top_k": 0 }),
+        json!({ "image_base64": IMAGE, "word_weight": 1.5 }),
+        json!({ "image_base64": IMAGE, "words": ["cake"], "slot_weights": { "style": -1 } }),
+    ] {
+        let (status, _) = app.post("/image/match/photo", payload).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
+
+#[tokio::test]
+async fn test_photo_match_without_photo_embeddings() {
+    // Photo embeddings are opt-in and never loaded in tests
+    let app = TestApp::start().await;
+    app.ollama
+        .on("/api/chat", tags_reply(&["cake", "happy", "whimsical"]));
+
+    let (status, body) = app
+        .post(
+            "/image/match/photo",
+            json!({ "image_base64": png_base64(2, 2), "extract_words": true }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"]["code"], "library_empty");
+    assert!(body["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("PHOTO_EMBEDDINGS"));
+    // The photo was described before matching
+    assert_eq!(app.ollama.requests("/api/chat").len(), 1);
+}
+
+#[tokio::test]
+async fn test_serves_images() {
+    let app = TestApp::start().await;
+    app.add_image("hamster.png", b"png bytes");
+
+    let (status, body) = app.get("/images/hamster.png").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, b"png bytes");
+
+    let (status, _) = app.get("/images/missing.png").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+// ============================================================================
+// Library generator
+// ============================================================================
+
+#[tokio::test]
+async fn test_generate_library_writes_csv() {
+    let app = TestApp::start().await;
+    app.add_image("b.jpg", b"jpeg");
+    app.add_image("a.png", b"png");
+    app.add_image("notes.txt", b"not an image");
+    app.ollama
+        .on("/api/chat", tags_reply(&["Cake", "HAPPY", "whimsical"]));
+
+    let (status, body) = app.post("/admin/generate-library", json!({})).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    assert_eq!(body["total_images_in_folder"], 2);
+    assert_eq!(body["queued_images"], 2);
+    let id = body["job_id"].as_str().unwrap();
+    assert_eq!(body["status_url"], format!("/admin/jobs/{}", id));
+
+    let job = app.wait_for_job(id).await;
+    assert_eq!(job["status"], "completed");
+    assert_eq!(job["processed_images"], 2);
+    assert_eq!(job["skipped_images"], 0);
+    assert_eq!(job["images"][0]["filename"], "a.png");
+    assert_eq!(job["images"][0]["status"], "done");
+    assert_eq!(
+        job["images"][0]["tags"],
+        json!([
+            { "word": "cake", "weight": 1.0, "category": "object" },
+            { "word": "happy", "weight": 1.0, "category": "emotion" },
+            { "word": "whimsical", "weight": 1.0, "category": "style" },
+        ])
+    );
+
+    let csv = std::fs::read_to_string(app.dir.path().join("image_library.csv")).unwrap();
+    assert_eq!(
+        csv,
+        format!(
+            "image_url,tags,hash,locked\n\
+             /images/a.png,object:cake; emotion:happy; style:whimsical,{},false\n\
+             /images/b.jpg,object:cake; emotion:happy; style:whimsical,{},false\n",
+            image_hash(b"png"),
+            image_hash(b"jpeg")
+        )
+    );
+
+    let sent = app.ollama.requests("/api/chat");
+    assert_eq!(sent.len(), 2);
+    assert_eq!(
+        sent[0]["options"]["temperature"].as_f64().unwrap() as f32,
+        0.3
+    );
+    assert_eq!(
+        sent[0]["format"]["required"],
+        json!(["object", "emotion", "style"])
+    );
+
+    let (status, body) = app.get_json("/admin/jobs").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["jobs"][0]["id"], id);
+}
+
+#[tokio::test]
+async fn test_generate_library_range() {
+    let app = TestApp::start().await;
+    for name in ["a.png", "b.png", "c.png"] {
+        app.add_image(name, b"png");
+    }
+    app.ollama
+        .on("/api/chat", tags_reply(&["cake", "happy", "cute"]));
+
+    let (status, body) = app
+        .post(
+            "/admin/generate-library",
+            json!({ "start_index": 1, "end_index": 1 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    assert_eq!(body["range"], "1-1 (b.pngto b.png)");
+
+    let job = app.wait_for_job(body["job_id"].as_str().unwrap()).await;
+    assert_eq!(job["processed_images"], 1);
+    assert_eq!(job["images"].as_array().unwrap().len(), 1);
+
+    let (status, body) = app
+        .post("/admin/generate-library", json!({ "start_index": 3 }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "validation_failed");
+
+    let (status, _) = app
+        .post(
+            "/admin/generate-library",
+            json!({ "start_index": 2, "end_index": 1 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_generate_library_fails_on_unparseable_replies() {
+    let app = TestApp::start().await;
+    app.add_image("a.png", b"png");
+    app.ollama.on("/api/chat", Reply::chat("just one word"));
+
+    let (status, body) = app.post("/admin/generate-library", json!({})).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+
+    let job = app.wait_for_job(body["job_id"].as_str().unwrap()).await;
+    assert_eq!(job["status"], "failed");
+    assert_eq!(job["skipped_images"], 1);
+    assert_eq!(job["images"][0]["status"], "failed");
+    assert_eq!(job["images"][0]["error"]["code"], "model_parse_failed");
+    assert_eq!(job["error"]["code"], "model_parse_failed");
+    assert!(job["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("1 skipped"));
+    assert!(!app.dir.path().join("image_library.csv").exists());
+
+    // The first attempt plus the two default retries
+    assert_eq!(app.ollama.requests("/api/chat").len(), 3);
+}
+
+#[tokio::test]
+async fn test_generate_library_retry_failed_images() {
+    let app = TestApp::start().await;
+    app.add_image("a.png", b"png");
+    app.ollama
+        .on("/api/chat", Reply::error(StatusCode::BAD_GATEWAY, "boom"));
+
+    let (_, body) = app.post("/admin/generate-library", json!({})).await;
+    let id = body["job_id"].as_str().unwrap().to_string();
+    let job = app.wait_for_job(&id).await;
+    assert_eq!(job["status"], "failed");
+    assert_eq!(job["error"]["code"], "upstream_error");
+
+    app.ollama
+        .on("/api/chat", tags_reply(&["cake", "happy", "cute"]));
+    let (status, body) = app
+        .post(&format!("/admin/jobs/{}/retry", id), json!({}))
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    assert_eq!(body["job"]["attempts"], 2);
+
+    let job = app.wait_for_job(&id).await;
+    assert_eq!(job["status"], "completed");
+    assert_eq!(job["processed_images"], 1);
+    assert!(job["error"].is_null());
+    assert!(app.dir.path().join("image_library.csv").exists());
+
+    // Nothing left to retry
+    let (status, _) = app
+        .post(&format!("/admin/jobs/{}/retry", id), json!({}))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_generate_library_cancel() {
+    let app = TestApp::start().await;
+    app.add_image("a.png", b"png");
+    app.add_image("b.png", b"png");
+    app.ollama.on(
+        "/api/chat",
+        tags_reply(&["cake", "happy", "cute"]).delayed(Duration::from_secs(3)),
+    );
+
+    let (_, body) = app.post("/admin/generate-library", json!({})).await;
+    let id = body["job_id"].as_str().unwrap().to_string();
+
+    // Only one job at a time
+    let (status, body) = app.post("/admin/generate-library", json!({})).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error"]["code"], "conflict");
+
+    let (status, _) = app
+        .post(&format!("/admin/jobs/{}/cancel", id), json!({}))
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+
+    let job = app.wait_for_job(&id).await;
+    assert_eq!(job["status"], "cancelled");
+    assert_eq!(job["processed_images"], 0);
+    assert!(job["images"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|image| image["status"] == "pending"));
+    assert!(!app.dir.path().join("image_library.csv").exists());
+
+    let (status, _) = app
+        .post(&format!("/admin/jobs/{}/cancel", id), json!({}))
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, _) = app.get_json("/admin/jobs/job-0-0").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_generate_library_merges_into_existing_csv() {
+    let app = TestApp::start().await;
+    for name in ["a.png", "b.png", "c.png"] {
+        app.add_image(name, name.as_bytes());
+    }
+    let csv_path = app.dir.path().join("image_library.csv");
+    std::fs::write(
+        &csv_path,
+        format!(
+            "image_url,word1,word2,word3,hash,locked\n\
+             /images/b.png,old,but,current,{},false\n\
+             /images/gone.png,no,longer,there,,false\n\
+             /images/a.png,hand,made,words,,true\n",
+            image_hash(b"b.png")
+        ),
+    )
+    .unwrap();
+    app.ollama
+        .on("/api/chat", tags_reply(&["cake", "happy", "cute"]));
+
+    let (status, body) = app.post("/admin/generate-library", json!({})).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let job = app.wait_for_job(body["job_id"].as_str().unwrap()).await;
+    assert_eq!(job["status"], "completed");
+    assert_eq!(job["mode"], "merge");
+    assert_eq!(job["processed_images"], 1);
+    assert_eq!(job["unchanged_images"], 1);
+    assert_eq!(job["locked_images"], 1);
+    assert_eq!(job["images"][0]["status"], "locked");
+    assert_eq!(job["images"][1]["tags"][0]["word"], "old");
+
+    // Only the new image went to the model
+    assert_eq!(app.ollama.requests("/api/chat").len(), 1);
+
+    let rows = read_library_rows(&csv_path).unwrap();
+    let summary: Vec<(&str, &str, bool)> = rows
+        .iter()
+        .map(|row| {
+            (
+                row.image_url.as_str(),
+                row.tags[0].word.as_str(),
+                row.locked,
+            )
+        })
+        .collect();
+    // Rows keep their order in the file; new images are appended
+    assert_eq!(
+        summary,
+        [
+            ("/images/b.png", "old", false),
+            ("/images/a.png", "hand", true),
+            ("/images/c.png", "cake", false),
+        ]
+    );
+    assert_eq!(rows[2].hash, image_hash(b"c.png"));
+
+    // A changed file is described again; rows outside the range stay
+    app.add_image("b.png", b"new pixels");
+    let (_, body) = app
+        .post(
+            "/admin/generate-library",
+            json!({ "start_index": 1, "end_index": 1 }),
+        )
+        .await;
+    let job = app.wait_for_job(body["job_id"].as_str().unwrap()).await;
+    assert_eq!(job["processed_images"], 1);
+
+    let rows = read_library_rows(&csv_path).unwrap();
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[0].image_url, "/images/b.png");
+    assert_eq!(rows[0].tags[0].word, "cake");
+    assert_eq!(rows[0].hash, image_hash(b"new pixels"));
+
+    // Replace keeps only the range, plus locked rows
+    let (_, body) = app
+        .post(
+            "/admin/generate-library",
+            json!({ "start_index": 2, "end_index": 2, "mode": "replace" }),
+        )
+        .await;
+    let job = app.wait_for_job(body["job_id"].as_str().unwrap()).await;
+    assert_eq!(job["status"], "completed");
+
+    let urls: Vec<String> = read_library_rows(&csv_path)
+        .unwrap()
+        .into_iter()
+        .map(|row| row.image_url)
+        .collect();
+    assert_eq!(urls, ["/images/a.png", "/images/c.png"]);
+}
+
+#[tokio::test]
+async fn test_generate_library_rejects_unreadable_csv_in_merge_mode() {
+    let app = TestApp::start().await;
+    app.add_image("a.png", b"png");
+    std::fs::write(
+        app.dir.path().join("image_library.csv"),
+        "image_url,word1,word2,word3,hash,locked\n/images/a.png,a,b,c,,perhaps\n",
+    )
+    .unwrap();
+
+    let (status, body) = app.post("/admin/generate-library", json!({})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("replace"));
+}
+
+#[tokio::test]
+async fn test_generate_library_empty_or_missing_dir() {
+    let app = TestApp::start().await;
+
+    let (status, _) = app.post("/admin/generate-library", json!({})).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let app = TestApp::start_with(|config| config.library.images_dir = "/nonexistent".into()).await;
+    let (status, body) = app.post("/admin/generate-library", json!({})).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(body["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("does not exist"));
+}
+
+#[tokio::test]
+async fn test_library_entry_crud() {
+    let app = TestApp::start().await;
+    app.add_image("a.png", b"a pixels");
+    let csv_path = app.dir.path().join("image_library.csv");
+
+    // No CSV yet; none of the built-in images exist here
+    let (status, body) = app.get_json("/admin/library").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["entries"], json!([]));
+
+    let (status, body) = app
+        .post(
+            "/admin/library",
+            json!({ "filename": "a.png", "words": [" Smug", "hungry ", "CUTE"] }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body["entry"]["id"], "a.png");
+    assert_eq!(body["entry"]["image_url"], "/images/a.png");
+    assert_eq!(
+        body["entry"]["tags"],
+        json!([
+            { "word": "smug", "weight": 1.0 },
+            { "word": "hungry", "weight": 1.0 },
+            { "word": "cute", "weight": 1.0 },
+        ])
+    );
+    assert_eq!(body["entry"]["locked"], true);
+    // No embedding model in tests
+    assert_eq!(body["embedded"], false);
+
+    let rows = read_library_rows(&csv_path).unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].hash, image_hash(b"a pixels"));
+
+    let (status, body) = app
+        .post(
+            "/admin/library",
+            json!({ "filename": "a.png", "words": ["a", "b", "c"] }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error"]["code"], "conflict");
+
+    let (status, body) = app
+        .put(
+            "/admin/library/a.png",
+            json!({
+                "tags": [
+                    { "word": "sleepy", "weight": 2.0, "category": "emotion" },
+                    { "word": "fluffy", "weight": 0.5 },
+                ],
+                "locked": false,
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["entry"]["tags"][0]["category"], "emotion");
+    assert_eq!(body["entry"]["tags"][1]["weight"], 0.5);
+    assert_eq!(body["entry"]["locked"], false);
+
+    let (status, body) = app.get_json("/admin/library/a.png").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["entry"]["tags"][0]["word"], "sleepy");
+
+    let (status, body) = app.delete("/admin/library/a.png", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["entry"]["id"], "a.png");
+    assert!(read_library_rows(&csv_path).unwrap().is_empty());
+    // The image is kept unless asked otherwise
+    assert!(app.dir.path().join("images/a.png").is_file());
+
+    let (status, body) = app.get_json("/admin/library/a.png").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"]["code"], "not_found");
+}
+
+#[tokio::test]
+async fn test_library_entry_upload() {
+    let app = TestApp::start().await;
+    let image = png_base64(4, 4);
+
+    let (status, body) = app
+        .post(
+            "/admin/library",
+            json!({ "filename": "new.png", "words": ["a", "b", "c"], "image_base64": image }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let path = app.dir.path().join("images/new.png");
+    let bytes = std::fs::read(&path).unwrap();
+    assert_eq!(body["entry"]["hash"], image_hash(&bytes));
+
+    // Replacing the image updates the hash
+    let (status, body) = app
+        .put(
+            "/admin/library/new.png",
+            json!({ "image_base64": png_base64(8, 8) }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["entry"]["tags"].as_array().unwrap().len(), 3);
+    assert_eq!(
+        body["entry"]["hash"],
+        image_hash(&std::fs::read(&path).unwrap())
+    );
+
+    let (status, _) = app
+        .delete(
+            "/admin/library/new.png",
+            Some(json!({ "delete_image": true })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn test_library_entry_upload_removed_when_csv_write_fails() {
+    let app = TestApp::start().await;
+    // The CSV is written through this temporary path, so the write fails
+    std::fs::create_dir(app.dir.path().join("image_library.csv.tmp")).unwrap();
+
+    let (status, _) = app
+        .post(
+            "/admin/library",
+            json!({ "filename": "new.png", "words": ["a", "b", "c"], "image_base64": png_base64(4, 4) }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(!app.dir.path().join("images/new.png").exists());
+}
+
+#[tokio::test]
+async fn test_library_entry_validation() {
+    let app = TestApp::start().await;
+    app.add_image("a.png", b"a pixels");
+
+    for (payload, message) in [
+        (json!({ "filename": "a.png" }), "tags or words are required"),
+        (json!({ "filename": "a.png", "words": [] }), "no tags"),
+        (
+            json!({ "filename": "a.png", "words": ["a", " ", "c"] }),
+            "an empty tag",
+        ),
+        (
+            json!({ "filename": "a.png", "tags": [{ "word": "a", "weight": -1 }] }),
+            "not positive",
+        ),
+        (
+            json!({ "filename": "a.png", "tags": [{ "word": "a" }], "words": ["b"] }),
+            "not both",
+        ),
+        (
+            json!({ "filename": "../a.png", "words": ["a", "b", "c"] }),
+            "plain file name",
+        ),
+        (
+            json!({ "filename": "a.txt", "words": ["a", "b", "c"] }),
+            "plain file name",
+        ),
+        (
+            json!({ "filename": "missing.png", "words": ["a", "b", "c"] }),
+            "does not exist",
+        ),
+        (
+            json!({ "filename": "b.png", "words": ["a", "b", "c"], "image_base64": IMAGE }),
+            "not a recognised image",
+        ),
+    ] {
+        let (status, body) = app.post("/admin/library", payload).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let error = body["error"]["message"].as_str().unwrap();
+        assert!(error.contains(message), "{}", error);
+    }
+
+    // Uploads never overwrite an image that is not in the library
+    let (status, _) = app
+        .post(
+            "/admin/library",
+            json!({ "filename": "a.png", "words": ["a", "b", "c"], "image_base64": png_base64(1, 1) }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, _) = app
+        .put("/admin/library/b.png", json!({ "words": ["a", "b", "c"] }))
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = app.delete("/admin/library/b.png", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(!app.dir.path().join("image_library.csv").exists());
+}
+
+// ============================================================================
+// Admin access
+// ============================================================================
+
+fn admin_request(authorization: Option<&str>) -> Request<Body> {
+    let mut request = Request::get("/admin/jobs");
+    if let Some(authorization) = authorization {
+        request = request.header(header::AUTHORIZATION, authorization);
+    }
+    request.body(Body::empty()).unwrap()
+}
+
+#[tokio::test]
+async fn test_admin_requires_credentials() {
+    let app = TestApp::start().await;
+
+    for authorization in [None, Some("Bearer wrong"), Some("Basic YWRtaW46YWRtaW4=")] {
+        let (status, body) = app.send(admin_request(authorization)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["success"], false);
+        assert_eq!(body["error"]["code"], "unauthorized");
+    }
+
+    let (status, _) = app
+        .send(admin_request(Some(&format!("Bearer {}", ADMIN_TOKEN))))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Public routes need nothing
+    let (status, _) = app
+        .send(Request::get("/health").body(Body::empty()).unwrap())
+        .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_admin_basic_auth() {
+    use base64::{engine::general_purpose, Engine};
+
+    let app = TestApp::start_with(|config| {
+        config.admin.token = None;
+        config.admin.username = Some("admin".into());
+        config.admin.password = Some("hunter2".into());
+    })
+    .await;
+
+    let basic = format!(
+        "Basic {}",
+        general_purpose::STANDARD.encode("admin:hunter2")
+    );
+    let (status, _) = app.send(admin_request(Some(&basic))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = app
+        .send(admin_request(Some(&format!("Bearer {}", ADMIN_TOKEN))))
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_admin_without_credentials_configured_is_forbidden() {
+    let app = TestApp::start_with(|config| config.admin.token = None).await;
+
+    let (status, body) = app.get_json("/admin/jobs").await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"]["code"], "forbidden");
+}
+
+#[tokio::test]
+async fn test_admin_routes_can_be_disabled() {
+    let app = TestApp::start_with(|config| config.admin.enabled = false).await;
+
+    let (status, _) = app.get("/admin/jobs").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = app.post("/admin/generate-library", json!({})).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // History stays, still behind the admin credentials
+    let (status, _) = app.get("/history").await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app
+        .send(Request::get("/history").body(Body::empty()).unwrap())
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+// ============================================================================
+// Printing
+// ============================================================================
+
+fn png_base64(width: u32, height: u32) -> String {
+    use base64::{engine::general_purpose, Engine};
+    use image::{ImageFormat, Rgba, RgbaImage};
+
+    let mut bytes = Vec::new();
+    RgbaImage::from_pixel(width, height, Rgba([90, 90, 90, 255]))
+        .write_to(&mut std::io::Cursor::new(&mut bytes), ImageFormat::Png)
+        .unwrap();
+    general_purpose::STANDARD.encode(bytes)
+}
+
+#[tokio::test]
+async fn test_print_render_png_preview() {
+    let app = TestApp::start().await;
+
+    let (status, body) = app
+        .post_raw(
+            "/print/render",
+            json!({ "image_base64": png_base64(192, 100) }).to_string(),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let preview = image::load_from_memory(&body).unwrap();
+    assert_eq!((preview.width(), preview.height()), (384, 200));
+}
+
+#[tokio::test]
+async fn test_print_render_packets_from_library_image() {
+    let app = TestApp::start().await;
+    let png = {
+        use base64::{engine::general_purpose, Engine};
+        general_purpose::STANDARD
+            .decode(png_base64(384, 10))
+            .unwrap()
+    };
+    app.add_image("hamster.png", &png);
+
+    let (status, body) = app
+        .post_raw(
+            "/print/render",
+            json!({
+                "image_url": "/images/hamster.png",
+                "format": "packets",
+                "dither": "threshold",
+                "feed_lines": 0
+            })
+            .to_string(),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Drawing mode, energy, lattice start, 10 rows, lattice end
+    assert_eq!(&body[..3], &[0x51, 0x78, 0xbe]);
+    assert_eq!(body.len(), 9 + 10 + 19 + 10 * 56 + 19);
+}
+
+#[tokio::test]
+async fn test_print_render_validation() {
+    let app = TestApp::start().await;
+
+    let (status, body) = app.post("/print/render", json!({})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "validation_failed");
+
+    let (status, _) = app
+        .post("/print/render", json!({ "image_base64": IMAGE }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = app
+        .post(
+            "/print/render",
+            json!({ "image_url": "/images/missing.png" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, body) = app
+        .post(
+            "/print/render",
+            json!({ "image_base64": IMAGE, "dither": "sparkles" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["error"]["code"], "invalid_body");
+}
+
+#[tokio::test]
+async fn test_print_receipt_with_photo_and_library_image() {
+    use base64::{engine::general_purpose, Engine};
+
+    let app = TestApp::start().await;
+    let hamster = general_purpose::STANDARD
+        .decode(png_base64(384, 50))
+        .unwrap();
+    app.add_image("hamster.png", &hamster);
+
+    let (status, text_only) = app
+        .post(
+            "/print/receipt",
+            json!({ "text": "Roses are red", "date": "2025-01-01" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = app
+        .post(
+            "/print/receipt",
+            json!({
+                "text": "Roses are red",
+                "date": "2025-01-01",
+                "photo_base64": png_base64(192, 100),
+                "image_url": "/images/hamster.png"
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["width"], 384);
+
+    // Both images are stacked into the same receipt
+    let height = body["height"].as_u64().unwrap();
+    let text_height = text_only["height"].as_u64().unwrap();
+    assert_eq!(height, text_height + 200 + 12 + 12 + 50);
+
+    let rows = general_purpose::STANDARD
+        .decode(body["rows_base64"].as_str().unwrap())
+        .unwrap();
+    assert_eq!(rows.len() as u64, height * 48);
+
+    let png = general_purpose::STANDARD
+        .decode(body["png_base64"].as_str().unwrap())
+        .unwrap();
+    let preview = image::load_from_memory(&png).unwrap();
+    assert_eq!(
+        (preview.width() as u64, preview.height() as u64),
+        (384, height)
+    );
+}
+
+#[tokio::test]
+async fn test_print_receipt_validation() {
+    let app = TestApp::start().await;
+
+    let (status, body) = app.post("/print/receipt", json!({ "text": "  " })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "validation_failed");
+
+    let (status, _) = app
+        .post("/print/receipt", json!({ "text": "x".repeat(2001) }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    for field in ["title", "date"] {
+        let (status, body) = app
+            .post(
+                "/print/receipt",
+                json!({ "text": "hi", field: "x".repeat(101) }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", field);
+        assert_eq!(
+            body["error"]["message"],
+            format!("{} must be at most 100 characters", field)
+        );
+    }
+
+    let (status, _) = app
+        .post(
+            "/print/receipt",
+            json!({ "text": "hi", "image_url": "/images/missing.png" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+// ============================================================================
+// History
+// ============================================================================
+
+#[tokio::test]
+async fn test_history_records_generations() {
+    let app = TestApp::start().await;
+    app.ollama.on("/api/chat", Reply::chat("Nice hat."));
+    app.ollama
+        .on("/api/generate", Reply::generate("Roses are red"));
+
+    let (status, _) = app
+        .post("/roast/image", json!({ "image_base64": IMAGE }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.post("/poem/text", json!({ "prompt": "spring" })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = app.get_json("/history").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["total"], 2);
+    assert_eq!(body["page"], 1);
+    assert_eq!(body["per_page"], 20);
+    let entries = body["entries"].as_array().unwrap();
+    assert_eq!(entries[0]["mode"], "poem_text");
+    assert_eq!(entries[0]["prompt"], "spring");
+    assert_eq!(entries[0]["output"], "Roses are red");
+    assert_eq!(entries[1]["mode"], "roast");
+    assert_eq!(entries[1]["output"], "Nice hat.");
+    assert_eq!(entries[1]["model"], "test-model");
+    assert!(entries[1]["prompt_version"]
+        .as_str()
+        .unwrap()
+        .starts_with("roast@"));
+    // "hello" isn't a picture, so there is nothing to shrink
+    assert!(entries[1]["thumbnail_url"].is_null());
+
+    let (status, body) = app.get_json("/history?mode=roast").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["total"], 1);
+    assert_eq!(body["entries"][0]["mode"], "roast");
+
+    let (status, body) = app.get_json("/history?page=2&per_page=1").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["total"], 2);
+    assert_eq!(body["entries"].as_array().unwrap().len(), 1);
+    assert_eq!(body["entries"][0]["mode"], "roast");
+}
+
+#[tokio::test]
+async fn test_history_get_and_delete() {
+    let app = TestApp::start().await;
+    app.ollama.on("/api/chat", Reply::chat("Nice hat."));
+    app.post("/roast/image", json!({ "image_base64": IMAGE }))
+        .await;
+
+    let (_, body) = app.get_json("/history").await;
+    let id = body["entries"][0]["id"].as_i64().unwrap();
+
+    let (status, body) = app.get_json(&format!("/history/{}", id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["entry"]["output"], "Nice hat.");
+
+    let (status, body) = app.delete(&format!("/history/{}", id), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["entry"]["id"], id);
+
+    let (status, body) = app.get_json(&format!("/history/{}", id)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"]["code"], "not_found");
+    let (status, _) = app.get_json("/history/abc").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_history_thumbnail() {
+    let app = TestApp::start().await;
+    app.ollama.on("/api/chat", Reply::chat("Nice hat."));
+    app.post(
+        "/roast/image",
+        json!({ "image_base64": png_base64(800, 400) }),
+    )
+    .await;
+
+    let (_, body) = app.get_json("/history").await;
+    let url = body["entries"][0]["thumbnail_url"].as_str().unwrap();
+
+    let (status, jpeg) = app.get(url).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        image::guess_format(&jpeg).unwrap(),
+        image::ImageFormat::Jpeg
+    );
+    let thumbnail = image::load_from_memory(&jpeg).unwrap();
+    assert_eq!((thumbnail.width(), thumbnail.height()), (256, 128));
+}
+
+#[tokio::test]
+async fn test_history_validation() {
+    let app = TestApp::start().await;
+
+    for query in ["per_page=0", "per_page=101", "page=0", "mode=essay"] {
+        let (status, body) = app.get_json(&format!("/history?{}", query)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", query);
+        assert_eq!(body["error"]["code"], "validation_failed");
+    }
+}
+
+#[tokio::test]
+async fn test_history_can_be_disabled() {
+    let app = TestApp::start_with(|config| config.history.enabled = false).await;
+    app.ollama.on("/api/chat", Reply::chat("Nice hat."));
+
+    let (status, _) = app
+        .post("/roast/image", json!({ "image_base64": IMAGE }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.get("/history").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+// ============================================================================
+// Share pages
+// ============================================================================
+
+#[tokio::test]
+async fn test_share_page() {
+    let app = TestApp::start().await;
+    app.ollama
+        .on("/api/chat", Reply::chat("<b>Nice</b> hat & shoes"));
+
+    let (status, body) = app
+        .post(
+            "/roast/image",
+            json!({ "image_base64": png_base64(400, 300) }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let share_id = body["share_id"].as_str().unwrap().to_string();
+    assert_eq!(share_id.len(), 10);
+
+    let (status, page) = app.get(&format!("/r/{}", share_id)).await;
+    assert_eq!(status, StatusCode::OK);
+    let page = String::from_utf8(page).unwrap();
+    assert!(page.contains("Your roast"));
+    assert!(page.contains("&lt;b&gt;Nice&lt;/b&gt; hat &amp; shoes"));
+    assert!(!page.contains("<b>"));
+    assert!(page.contains(&format!("/r/{}/photo.jpg", share_id)));
+
+    let (status, photo) = app.get(&format!("/r/{}/photo.jpg", share_id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        image::guess_format(&photo).unwrap(),
+        image::ImageFormat::Jpeg
+    );
+
+    // The share id also shows in the admin history
+    let (_, history) = app.get_json("/history").await;
+    assert_eq!(history["entries"][0]["share_id"], share_id.as_str());
+}
+
+#[tokio::test]
+async fn test_share_page_missing() {
+    let app = TestApp::start().await;
+
+    let (status, page) = app.get("/r/0000000000").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(String::from_utf8(page)
+        .unwrap()
+        .contains("This result is gone"));
+
+    let (status, body) = app.get_json("/r/0000000000/qr.png").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"]["code"], "not_found");
+}
+
+#[tokio::test]
+async fn test_share_qr_code() {
+    let app = TestApp::start_with(|config| {
+        config.history.public_url = Some("http://192.168.1.20:8000/".into())
+    })
+    .await;
+    app.ollama.on("/api/chat", Reply::chat("Nice hat."));
+    let (_, body) = app
+        .post("/roast/image", json!({ "image_base64": IMAGE }))
+        .await;
+    let share_id = body["share_id"].as_str().unwrap();
+
+    let (status, png) = app.get(&format!("/r/{}/qr.png", share_id)).await;
+    assert_eq!(status, StatusCode::OK);
+    let qr = image::load_from_memory(&png).unwrap();
+    assert_eq!(qr.width(), qr.height());
+
+    let (status, packets) = app
+        .get(&format!("/r/{}/qr.png?format=packets", share_id))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(&packets[..2], &[0x51, 0x78]);
+
+    let (status, body) = app
+        .get_json(&format!("/r/{}/qr.png?format=svg", share_id))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "validation_failed");
+}
+
+#[tokio::test]
+async fn test_share_qr_code_ignores_host_header() {
+    let app = TestApp::start().await;
+    app.ollama.on("/api/chat", Reply::chat("Nice hat."));
+    let (_, body) = app
+        .post("/roast/image", json!({ "image_base64": IMAGE }))
+        .await;
+    let share_id = body["share_id"].as_str().unwrap();
+
+    // Without public_url the bind address is used, whatever Host says
+    let request = Request::get(format!("/r/{}/qr.png", share_id))
+        .header(header::HOST, "evil.example")
+        .body(Body::empty())
+        .unwrap();
+    let (status, png) = app.send(request).await;
+    assert_eq!(status, StatusCode::OK);
+    let expected = crate::print::qr_png(&format!("http://localhost:8000/r/{}", share_id)).unwrap();
+    assert_eq!(png, expected);
+}
+
+#[tokio::test]
+async fn test_print_receipt_with_share_qr_code() {
+    let app = TestApp::start().await;
+    app.ollama.on("/api/chat", Reply::chat("Nice hat."));
+    let (_, body) = app
+        .post("/roast/image", json!({ "image_base64": IMAGE }))
+        .await;
+    let share_id = body["share_id"].as_str().unwrap();
+
+    let receipt = json!({ "text": "Nice hat.", "date": "2025-01-01" });
+    let (_, plain) = app.post("/print/receipt", receipt.clone()).await;
+    let mut with_qr = receipt.clone();
+    with_qr["share_id"] = json!(share_id);
+    let (status, body) = app.post("/print/receipt", with_qr).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["height"].as_u64().unwrap() > plain["height"].as_u64().unwrap() + 200);
+
+    let mut unknown = receipt;
+    unknown["share_id"] = json!("0000000000");
+    let (status, _) = app.post("/print/receipt", unknown).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_share_pages_can_be_disabled() {
+    let app = TestApp::start_with(|config| config.history.share = false).await;
+    app.ollama.on("/api/chat", Reply::chat("Nice hat."));
+
+    let (status, body) = app
+        .post("/roast/image", json!({ "image_base64": IMAGE }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.get("share_id").is_none());
+
+    let (_, history) = app.get_json("/history").await;
+    let share_id = history["entries"][0]["share_id"].as_str().unwrap();
+    let (status, _) = app.get(&format!("/r/{}", share_id)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
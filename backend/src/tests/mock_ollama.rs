@@ -0,0 +1,180 @@
+//! In-process fake Ollama for handler tests.
+//!
+//! Each path is scripted with a `Reply`; unscripted paths answer 404 like a
+//! missing model would. Every request body is recorded for later assertions.
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Json, Router,
+};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A scripted response for one Ollama endpoint
+#[derive(Debug, Clone)]
+pub enum Reply {
+    /// 200 with a JSON body
+    Json(Value),
+    /// Non-2xx status with an Ollama-style `{"error": ...}` body
+    Status(StatusCode, String),
+    /// 200 with NDJSON lines, as Ollama sends when `stream: true`
+    Lines(Vec<Value>),
+    /// Wait before answering, to exercise timeouts and slow devices
+    Delayed(Duration, Box<Reply>),
+    /// One reply per request, in order; the last one repeats
+    Sequence(Vec<Reply>),
+}
+
+impl Reply {
+    /// `/api/chat` reply with the given assistant message
+    pub fn chat(content: &str) -> Self {
+        Reply::Json(json!({
+            "model": "test-model",
+            "message": { "role": "assistant", "content": content },
+            "done": true
+        }))
+    }
+
+    /// `/api/generate` reply with the given completion
+    pub fn generate(text: &str) -> Self {
+        Reply::Json(json!({ "model": "test-model", "response": text, "done": true }))
+    }
+
+    /// `/api/embed` reply with the given vectors
+    pub fn embeddings(vectors: Vec<Vec<f32>>) -> Self {
+        Reply::Json(json!({ "model": "test-model", "embeddings": vectors }))
+    }
+
+    /// Streamed `/api/chat` reply, one line per token, then `done`
+    pub fn chat_stream(tokens: &[&str]) -> Self {
+        let mut lines: Vec<Value> = tokens
+            .iter()
+            .map(|t| json!({ "message": { "role": "assistant", "content": t }, "done": false }))
+            .collect();
+        lines.push(json!({ "message": { "role": "assistant", "content": "" }, "done": true }));
+        Reply::Lines(lines)
+    }
+
+    /// Streamed `/api/generate` reply, one line per token, then `done`
+    pub fn generate_stream(tokens: &[&str]) -> Self {
+        let mut lines: Vec<Value> = tokens
+            .iter()
+            .map(|t| json!({ "response": t, "done": false }))
+            .collect();
+        lines.push(json!({ "response": "", "done": true }));
+        Reply::Lines(lines)
+    }
+
+    pub fn error(status: StatusCode, message: &str) -> Self {
+        Reply::Status(status, message.into())
+    }
+
+    pub fn sequence(replies: Vec<Reply>) -> Self {
+        Reply::Sequence(replies)
+    }
+
+    pub fn delayed(self, delay: Duration) -> Self {
+        Reply::Delayed(delay, Box::new(self))
+    }
+}
+
+#[derive(Default)]
+struct Script {
+    replies: Mutex<HashMap<String, Reply>>,
+    requests: Mutex<Vec<(String, Value)>>,
+}
+
+/// A fake Ollama listening on a random local port
+pub struct MockOllama {
+    pub base_url: String,
+    script: Arc<Script>,
+}
+
+impl MockOllama {
+    pub async fn start() -> Self {
+        let script = Arc::new(Script::default());
+        let app = Router::new().fallback(handle).with_state(script.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        Self {
+            base_url: format!("http://{}", addr),
+            script,
+        }
+    }
+
+    /// Answer every request to `path` with `reply` from now on
+    pub fn on(&self, path: &str, reply: Reply) -> &Self {
+        self.script
+            .replies
+            .lock()
+            .unwrap()
+            .insert(path.into(), reply);
+        self
+    }
+
+    /// Bodies of the requests received on `path`, oldest first
+    pub fn requests(&self, path: &str) -> Vec<Value> {
+        self.script
+            .requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(p, _)| p == path)
+            .map(|(_, body)| body.clone())
+            .collect()
+    }
+}
+
+async fn handle(State(script): State<Arc<Script>>, uri: Uri, Json(body): Json<Value>) -> Response {
+    let path = uri.path().to_string();
+    script.requests.lock().unwrap().push((path.clone(), body));
+
+    let reply = match script.replies.lock().unwrap().get_mut(&path) {
+        Some(Reply::Sequence(replies)) if replies.len() > 1 => Some(replies.remove(0)),
+        Some(Reply::Sequence(replies)) => replies.first().cloned(),
+        reply => reply.cloned(),
+    };
+    match reply {
+        Some(reply) => respond(reply).await,
+        None => {
+            respond(Reply::error(
+                StatusCode::NOT_FOUND,
+                &format!("no reply scripted for {}", path),
+            ))
+            .await
+        }
+    }
+}
+
+async fn respond(mut reply: Reply) -> Response {
+    while let Reply::Delayed(delay, inner) = reply {
+        tokio::time::sleep(delay).await;
+        reply = *inner;
+    }
+
+    match reply {
+        Reply::Json(body) => Json(body).into_response(),
+        Reply::Status(status, message) => {
+            (status, Json(json!({ "error": message }))).into_response()
+        }
+        Reply::Lines(lines) => {
+            let body: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+            (
+                [(header::CONTENT_TYPE, "application/x-ndjson")],
+                Body::from(body),
+            )
+                .into_response()
+        }
+        Reply::Delayed(..) | Reply::Sequence(..) => unreachable!(),
+    }
+}
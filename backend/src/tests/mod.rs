@@ -0,0 +1,173 @@
+//! End-to-end tests that drive the real `Router` against a fake Ollama.
+
+mod handlers;
+mod mock_ollama;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Method, Request, StatusCode},
+    Router,
+};
+use serde_json::Value;
+use tempfile::TempDir;
+use tower::ServiceExt;
+
+use crate::config::Config;
+use crate::routes;
+use crate::state::AppState;
+use mock_ollama::MockOllama;
+
+/// Admin token the test app is configured with; every helper sends it
+pub const ADMIN_TOKEN: &str = "test-admin-token";
+
+/// The app wired to a fresh mock Ollama and a scratch library directory
+pub struct TestApp {
+    pub ollama: MockOllama,
+    pub router: Router,
+    pub dir: TempDir,
+}
+
+impl TestApp {
+    pub async fn start() -> Self {
+        Self::start_with(|_| {}).await
+    }
+
+    /// Start with config tweaks applied on top of the test defaults
+    pub async fn start_with(configure: impl FnOnce(&mut Config)) -> Self {
+        let ollama = MockOllama::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("images")).unwrap();
+
+        let mut config = Config::default();
+        config.ollama.base_url = ollama.base_url.clone();
+        config.llm.model = "test-model".into();
+        config.llm.timeout_secs = 5;
+        config.library.images_dir = dir.path().join("images");
+        config.library.csv_path = dir.path().join("image_library.csv");
+        config.admin.token = Some(ADMIN_TOKEN.into());
+        config.prompts.dir = dir.path().join("prompts");
+        config.history.db_path = dir.path().join("history.db");
+        configure(&mut config);
+
+        Self {
+            router: routes::router(AppState::new(config).unwrap()),
+            ollama,
+            dir,
+        }
+    }
+
+    /// Put a file into the scratch images directory
+    pub fn add_image(&self, name: &str, bytes: &[u8]) {
+        std::fs::write(self.dir.path().join("images").join(name), bytes).unwrap();
+    }
+
+    pub async fn get(&self, path: &str) -> (StatusCode, Vec<u8>) {
+        let request = Self::request(Method::GET, path)
+            .body(Body::empty())
+            .unwrap();
+        self.send(request).await
+    }
+
+    /// GET and decode the JSON response
+    pub async fn get_json(&self, path: &str) -> (StatusCode, Value) {
+        let (status, bytes) = self.get(path).await;
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    /// Poll `/admin/jobs/:id` until the job has finished and return it
+    pub async fn wait_for_job(&self, id: &str) -> Value {
+        for _ in 0..200 {
+            let (status, body) = self.get_json(&format!("/admin/jobs/{}", id)).await;
+            assert_eq!(status, StatusCode::OK);
+            let job = &body["job"];
+            if !matches!(job["status"].as_str(), Some("queued" | "running")) {
+                return job.clone();
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(25)).await;
+        }
+        panic!("job {} did not finish", id);
+    }
+
+    /// POST a JSON body and decode the JSON response
+    pub async fn post(&self, path: &str, body: Value) -> (StatusCode, Value) {
+        let (status, bytes) = self.post_raw(path, body.to_string()).await;
+        let json = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+        (status, json)
+    }
+
+    /// PUT a JSON body and decode the JSON response
+    pub async fn put(&self, path: &str, body: Value) -> (StatusCode, Value) {
+        self.send_json(Method::PUT, path, Some(body)).await
+    }
+
+    /// DELETE, with an optional JSON body, and decode the JSON response
+    pub async fn delete(&self, path: &str, body: Option<Value>) -> (StatusCode, Value) {
+        self.send_json(Method::DELETE, path, body).await
+    }
+
+    /// POST a raw body as JSON and return the undecoded response
+    pub async fn post_raw(&self, path: &str, body: String) -> (StatusCode, Vec<u8>) {
+        let request = Self::request(Method::POST, path)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        self.send(request).await
+    }
+
+    async fn send_json(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let mut request = Self::request(method, path);
+        let body = match body {
+            Some(body) => {
+                request = request.header(header::CONTENT_TYPE, "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+        let (status, bytes) = self.send(request.body(body).unwrap()).await;
+        let json = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+        (status, json)
+    }
+
+    /// A request carrying the admin token
+    fn request(method: Method, path: &str) -> axum::http::request::Builder {
+        Request::builder()
+            .method(method)
+            .uri(path)
+            .header(header::AUTHORIZATION, format!("Bearer {}", ADMIN_TOKEN))
+    }
+
+    /// Send a request as is, e.g. without credentials
+    pub async fn send(&self, request: Request<Body>) -> (StatusCode, Vec<u8>) {
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, bytes.to_vec())
+    }
+}
+
+/// Split a Server-Sent Events body into `(event, data)` pairs, skipping keep-alives
+pub fn sse_events(body: &[u8]) -> Vec<(String, Value)> {
+    String::from_utf8_lossy(body)
+        .split("\n\n")
+        .filter_map(|block| {
+            let mut event = None;
+            let mut data = None;
+            for line in block.lines() {
+                if let Some(v) = line.strip_prefix("event:") {
+                    event = Some(v.trim().to_string());
+                } else if let Some(v) = line.strip_prefix("data:") {
+                    data = serde_json::from_str(v.trim()).ok();
+                }
+            }
+            Some((event?, data?))
+        })
+        .collect()
+}
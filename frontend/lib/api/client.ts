@@ -15,8 +15,11 @@ export async function apiRequest<T>(
   });
 
   if (!response.ok) {
+    // Backend errors look like { success: false, error: { code, message } }
     const errorData = await response.json().catch(() => ({}));
-    throw new Error(errorData.error || `Request failed: ${response.status}`);
+    throw new Error(
+      errorData.error?.message || `Request failed: ${response.status}`
+    );
   }
 
   return response.json();
@@ -1,6 +1,6 @@
 import { apiRequest } from "./client";
 
-// Response from /image/match endpoint
+// Response from /image/match and /hamster/match
 export interface ImageMatchResponse {
   success: boolean;
   matched_image_url?: string;
@@ -9,44 +9,33 @@ export interface ImageMatchResponse {
   error?: string;
 }
 
-// Response from word extraction (using poem endpoint with special prompt)
-export interface WordExtractionResponse {
+// Response from /image/describe
+export interface DescribeImageResponse {
   success: boolean;
-  poem?: string; // Will contain the 3 words
+  // Object, emotion and style, then any extras
+  words: string[];
   error?: string;
 }
 
 /**
- * Extract 3 descriptive words from an image using LLM
+ * Extract descriptive words from an image using LLM
  */
 export async function extractWordsFromImage(
   imageBase64: string
 ): Promise<string[]> {
-  const response = await apiRequest<WordExtractionResponse>("/poem/image", {
-    method: "POST",
-    body: JSON.stringify({
-      image_base64: imageBase64,
-      prompt:
-        "Look at this person and describe their vibe/personality in EXACTLY 3 single words separated by commas. Only output the 3 words, nothing else. Example: mysterious, playful, confident",
-    }),
-  });
+  const response = await apiRequest<DescribeImageResponse>(
+    "/image/describe",
+    {
+      method: "POST",
+      body: JSON.stringify({ image_base64: imageBase64 }),
+    }
+  );
 
-  if (!response.success || !response.poem) {
-    throw new Error(response.error || "Failed to extract words from image");
+  if (!response.success || response.words.length === 0) {
+    throw new Error(response.error || "No words extracted from image");
   }
 
-  // Parse the 3 words from the response
-  const words = response.poem
-    .split(",")
-    .map((w) => w.trim().toLowerCase())
-    .filter((w) => w.length > 0)
-    .slice(0, 3);
-
-  if (words.length === 0) {
-    throw new Error("No words extracted from image");
-  }
-
-  return words;
+  return response.words.slice(0, 3);
 }
 
 /**
@@ -69,18 +58,21 @@ export async function matchHamsterImage(imageBase64: string): Promise<{
   imageUrl: string;
   similarityScore?: number;
 }> {
-  // Step 1: Extract words from the captured image
-  const words = await extractWordsFromImage(imageBase64);
-
-  // Step 2: Match words to image library
-  const matchResponse = await matchWordsToImage(words);
+  // Describe the captured image and match it to the library in one call
+  const matchResponse = await apiRequest<ImageMatchResponse>(
+    "/hamster/match",
+    {
+      method: "POST",
+      body: JSON.stringify({ image_base64: imageBase64 }),
+    }
+  );
 
   if (!matchResponse.success || !matchResponse.matched_image_url) {
     throw new Error(matchResponse.error || "Failed to find matching image");
   }
 
   return {
-    words: matchResponse.extracted_words || words,
+    words: (matchResponse.extracted_words || []).slice(0, 3),
     imageUrl: matchResponse.matched_image_url,
     similarityScore: matchResponse.similarity_score,
   };